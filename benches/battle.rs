@@ -0,0 +1,71 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use ribs::{
+    engine::{
+        battle_engine::BattleEngine, battle_rules::BattleRules, battlefield::BattlefieldData,
+        combatant::Combatant, map::Map,
+    },
+    types::point::Point,
+};
+
+fn combatants(count: usize) -> Vec<Combatant> {
+    (0..count)
+        .map(|i| Combatant {
+            name: format!("combatant {}", i),
+            hp: 20,
+            max_hp: 20,
+            dmg: 5,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Free for all battles on maps that leave room for about ten locations per combatant
+fn battle(c: &mut Criterion) {
+    let mut group = c.benchmark_group("battle");
+    group.sample_size(10);
+
+    for count in [10, 100, 300] {
+        let size = ((count * 10) as f64).sqrt().ceil() as u16;
+
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, &count| {
+            b.iter(|| {
+                let battlefield = BattlefieldData {
+                    battlefield_height: size,
+                    battlefield_width: size,
+                    combatants: combatants(count),
+                };
+
+                BattleEngine::new(battlefield, BattleRules::default())
+                    .unwrap()
+                    .start_battle()
+                    .unwrap()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Lookups on a large map crowded with combatants
+fn map_queries(c: &mut Criterion) {
+    let mut map = Map::new(1000, 1000);
+    for i in 0..5000 {
+        map.place_randomly(format!("combatant {}", i)).unwrap();
+    }
+    let center = map.position_for("combatant 0").unwrap();
+
+    c.bench_function("map/position_for", |b| b.iter(|| map.position_for(black_box("combatant 4999"))));
+    c.bench_function("map/occupants_within", |b| b.iter(|| map.occupants_within(black_box(center), 5)));
+    c.bench_function("map/get_occupied_neighbors", |b| b.iter(|| map.get_occupied_neighbors(black_box(center))));
+    c.bench_function("map/move_to", |b| {
+        b.iter(|| {
+            let goal = Point::new(center.x ^ 1, center.y);
+            if map.move_to(center, goal).is_ok() {
+                map.move_to(goal, center).unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, battle, map_queries);
+criterion_main!(benches);
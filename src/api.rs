@@ -3,21 +3,43 @@ use rocket::request::Request;
 use rocket::response::{self, Responder, Response};
 use rocket::State;
 use serde::Serialize;
-use surrealdb::{engine::remote::ws::Client, Surreal};
 
 use crate::engine::err::Error;
-use crate::storage::{Entity, GenericEntity, Record};
+use crate::engine::replay::ReplayError;
+use crate::storage::err::Error as StorageError;
+use crate::storage::{Entity, GenericEntity, Record, Storage};
 
+use self::auth::AuthenticatedUser;
 use self::battle::PointContract;
+use self::etag::{etag_for, IfMatch};
 
+pub mod ability;
+pub mod auth;
 pub mod battlefield;
 pub mod combatant;
 pub mod battle;
+pub mod battle_log;
+pub mod bulk;
+pub mod etag;
+pub mod health;
+pub mod item;
+pub mod leaderboard;
+pub mod openapi;
+pub mod render;
+pub mod replay;
+pub mod status_effect;
+#[cfg(test)]
+pub mod test_client;
+pub mod tournament;
+pub mod user;
+pub mod v2;
+pub mod versioning;
 
 #[derive(Debug)]
 pub struct ApiResponse {
     pub json: String,
     pub status: Status,
+    pub etag: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -28,59 +50,131 @@ struct DestinationOccupiedContract {
 
 impl ApiResponse {
     pub fn empty(status: Status) -> Self {
-        ApiResponse { json: String::from(""), status }
+        ApiResponse { json: String::from(""), status, etag: None }
     }
 }
 
 impl From<Error> for ApiResponse {
     fn from(value: Error) -> Self {
         match value {
-            Error::UserAlreadyOnMap => todo!(),
+            Error::UserAlreadyOnMap => ApiResponse {
+                json: String::from("a combatant takes part in the battle more than once"),
+                status: Status::BadRequest,
+                etag: None,
+            },
             Error::DestinationOccupied(origin, goal) => {
                 ApiResponse{
                     json: serde_json::to_string(&DestinationOccupiedContract{
                         origin: PointContract::from(origin),
                         goal: PointContract::from(goal)
                     }).unwrap(),
-                    status: Status::Conflict
+                    status: Status::Conflict,
+                    etag: None
                 }
             },
-            Error::DestinationOutOfBounds(_, _) => todo!(),
-            Error::MapLocationEmpty(_) => todo!(),
-            Error::NoOpponentsPresent => todo!(),
+            Error::NoOpponentsPresent => ApiResponse {
+                json: String::from("a combatant has no opponents"),
+                status: Status::BadRequest,
+                etag: None,
+            },
+            // Movement is planned by the engine itself, these are faults of the engine and not of the request
+            Error::DestinationOutOfBounds(goal, bounds) => ApiResponse {
+                json: format!("the destination {:?} is outside of the map {:?}", goal, bounds),
+                status: Status::InternalServerError,
+                etag: None,
+            },
+            Error::MapLocationEmpty(location) => ApiResponse {
+                json: format!("no combatant at {:?}", location),
+                status: Status::InternalServerError,
+                etag: None,
+            },
+            Error::MapFull => ApiResponse::empty(Status::BadRequest),
+        }
+    }
+}
+
+impl From<StorageError> for ApiResponse {
+    fn from(value: StorageError) -> Self {
+        match value {
+            StorageError::NotFound => ApiResponse::empty(Status::NotFound),
+            StorageError::VersionMismatch(_, actual) => ApiResponse {
+                json: value.to_string(),
+                status: Status::PreconditionFailed,
+                etag: Some(etag_for(actual)),
+            },
+            StorageError::Database(e) => ApiResponse {
+                json: e.to_string(),
+                status: Status::BadRequest,
+                etag: None,
+            },
+            StorageError::Serialization(e) => ApiResponse {
+                json: e.to_string(),
+                status: Status::InternalServerError,
+                etag: None,
+            },
+        }
+    }
+}
+
+/// A stored log that does not replay, the reason names the round and the combatant
+///
+/// Combatants placed where they can not be are a broken log rather than one that does not replay.
+impl From<ReplayError> for ApiResponse {
+    fn from(value: ReplayError) -> Self {
+        let status = match value {
+            ReplayError::InvalidPlacement(_, _) => Status::BadRequest,
+            _ => Status::UnprocessableEntity,
+        };
+
+        ApiResponse {
+            json: value.to_string(),
+            status,
+            etag: None,
         }
     }
 }
 
 impl<'r> Responder<'r, 'static> for ApiResponse {
     fn respond_to(self, req: &Request) -> response::Result<'static> {
-        Response::build_from(self.json.respond_to(&req).unwrap())
-            .status(self.status)
-            .header(ContentType::JSON)
-            .ok()
+        let mut response = Response::build_from(self.json.respond_to(&req).unwrap());
+        response.status(self.status).header(ContentType::JSON);
+
+        if let Some(etag) = self.etag {
+            response.raw_header("ETag", etag);
+        }
+
+        response.ok()
     }
 }
 
 pub struct CrudApiScaffold;
 
 impl CrudApiScaffold {
-    pub async fn get_all<TEntity, TRecord>(db: &State<Surreal<Client>>) -> Vec<TRecord>
+    /// Returns all records the user is allowed to access
+    pub async fn get_all<TEntity, TRecord>(
+        db: &State<Storage>,
+        user: &AuthenticatedUser,
+    ) -> Vec<TRecord>
     where
         TEntity: Entity,
         TRecord: Record<TEntity>,
     {
         let db_access: GenericEntity<'_> = GenericEntity::new::<TEntity>(db.inner());
-        let entities: Result<Vec<TRecord>, surrealdb::Error> = db_access.get_all().await;
+        let entities: Result<Vec<TRecord>, StorageError> = db_access.get_all().await;
 
         match entities {
-            Ok(c) => c,
+            Ok(c) => c
+                .into_iter()
+                .filter(|record| user.can_access(&record.get_owner()))
+                .collect(),
             Err(_) => vec![],
         }
     }
 
     pub async fn get_by_id<TEntity, TRecord, TContract>(
-        db: &State<Surreal<Client>>,
+        db: &State<Storage>,
         id: &str,
+        user: &AuthenticatedUser,
         transformation_function: impl Fn(TRecord) -> TContract,
     ) -> ApiResponse
     where
@@ -89,26 +183,20 @@ impl CrudApiScaffold {
         TContract: Serialize,
     {
         let db_access: GenericEntity<'_> = GenericEntity::new::<TEntity>(db.inner());
-        let entity: Result<TRecord, surrealdb::Error> = db_access.get_by_id(id).await;
+        let entity: Result<TRecord, StorageError> =
+            CrudApiScaffold::accessible_record(&db_access, id, user).await;
 
         match entity {
-            Ok(e) => {
-                let contract: TContract = transformation_function(e);
-                ApiResponse {
-                    json: serde_json::to_string(&contract).unwrap(),
-                    status: Status::Ok,
-                }
-            }
-            Err(_e) => ApiResponse {
-                json: String::new(),
-                status: Status::NotFound,
-            },
+            Ok(e) => CrudApiScaffold::record_response(e, Status::Ok, transformation_function),
+            Err(e) => ApiResponse::from(e),
         }
     }
 
     pub async fn delete<TEntity, TRecord, TContract>(
-        db: &State<Surreal<Client>>,
+        db: &State<Storage>,
         id: &str,
+        if_match: IfMatch,
+        user: &AuthenticatedUser,
         transformation_function: impl Fn(TRecord) -> TContract,
     ) -> ApiResponse
     where
@@ -117,26 +205,26 @@ impl CrudApiScaffold {
         TContract: Serialize,
     {
         let db_access: GenericEntity<'_> = GenericEntity::new::<TEntity>(db.inner());
-        let entity = db_access.delete::<TEntity, TRecord>(id).await;
+
+        let expected_version: Option<u32> =
+            match CrudApiScaffold::expected_version::<TEntity, TRecord>(&db_access, id, if_match, user).await {
+                Ok(v) => v,
+                Err(response) => return response,
+            };
+
+        let entity = db_access.delete::<TEntity, TRecord>(id, expected_version).await;
 
         match entity {
-            Ok(e) => {
-                let contract: TContract = transformation_function(e);
-                ApiResponse {
-                    json: serde_json::to_string(&contract).unwrap(),
-                    status: Status::Ok,
-                }
-            }
-            Err(e) => ApiResponse {
-                json: e.to_string(),
-                status: Status::BadRequest,
-            },
+            Ok(e) => CrudApiScaffold::record_response(e, Status::Ok, transformation_function),
+            Err(e) => ApiResponse::from(e),
         }
     }
 
+    /// Stores the entity as owned by the user
     pub async fn create_new<TEntity, TRecord, TContract>(
-        db: &State<Surreal<Client>>,
+        db: &State<Storage>,
         entity: TEntity,
+        user: &AuthenticatedUser,
         transformation_function: impl Fn(TRecord) -> TContract,
     ) -> ApiResponse
     where
@@ -145,28 +233,21 @@ impl CrudApiScaffold {
         TContract: Serialize,
     {
         let db_access: GenericEntity<'_> = GenericEntity::new::<TEntity>(db.inner());
-        let new_entity_result: Result<TRecord, surrealdb::Error> =
-            db_access.create_new(entity).await;
+        let new_entity_result: Result<TRecord, StorageError> =
+            db_access.create_new(entity, user.id.clone()).await;
 
         match new_entity_result {
-            Ok(e) => {
-                let contract: TContract = transformation_function(e);
-                ApiResponse {
-                    json: serde_json::to_string(&contract).unwrap(),
-                    status: Status::Ok,
-                }
-            }
-            Err(e) => ApiResponse {
-                json: e.to_string(),
-                status: Status::BadRequest,
-            },
+            Ok(e) => CrudApiScaffold::record_response(e, Status::Ok, transformation_function),
+            Err(e) => ApiResponse::from(e),
         }
     }
 
     pub async fn update<TEntity, TRecord, TContract>(
-        db: &State<Surreal<Client>>,
+        db: &State<Storage>,
         id: &str,
         entity: TEntity,
+        if_match: IfMatch,
+        user: &AuthenticatedUser,
         transformation_function: impl Fn(TRecord) -> TContract,
     ) -> ApiResponse
     where
@@ -175,25 +256,87 @@ impl CrudApiScaffold {
         TContract: Serialize,
     {
         let db_access: GenericEntity<'_> = GenericEntity::new::<TEntity>(db.inner());
-        let updated_entity_result: Result<TRecord, surrealdb::Error> =
-            db_access.update(id, entity).await;
+
+        let expected_version: Option<u32> =
+            match CrudApiScaffold::expected_version::<TEntity, TRecord>(&db_access, id, if_match, user).await {
+                Ok(v) => v,
+                Err(response) => return response,
+            };
+
+        let updated_entity_result: Result<TRecord, StorageError> =
+            db_access.update(id, entity, expected_version).await;
 
         match updated_entity_result {
-            Ok(e) => {
-                let contract: TContract = transformation_function(e);
-                ApiResponse {
-                    json: serde_json::to_string(&contract).unwrap(),
-                    status: Status::Ok,
-                }
-            }
-            Err(e) => ApiResponse {
-                json: e.to_string(),
-                status: Status::BadRequest,
-            },
+            Ok(e) => CrudApiScaffold::record_response(e, Status::Ok, transformation_function),
+            Err(e) => ApiResponse::from(e),
+        }
+    }
+
+    /// Loads the record if the user is allowed to access it
+    ///
+    /// Records of other users are reported as not found, so that their existence is not revealed
+    async fn accessible_record<TEntity, TRecord>(
+        db_access: &GenericEntity<'_>,
+        id: &str,
+        user: &AuthenticatedUser,
+    ) -> Result<TRecord, StorageError>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let record: TRecord = db_access.get_by_id(id).await?;
+
+        match user.can_access(&record.get_owner()) {
+            true => Ok(record),
+            false => Err(StorageError::NotFound),
+        }
+    }
+
+    /// Decides the version an accessible record is written against, based on the If-Match header of the request
+    async fn expected_version<TEntity, TRecord>(
+        db_access: &GenericEntity<'_>,
+        id: &str,
+        if_match: IfMatch,
+        user: &AuthenticatedUser,
+    ) -> Result<Option<u32>, ApiResponse>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        match CrudApiScaffold::accessible_record::<TEntity, TRecord>(db_access, id, user).await {
+            Ok(record) => if_match.expected_version(record.get_version()),
+            Err(e) => Err(ApiResponse::from(e)),
+        }
+    }
+
+    /// Serializes the record into its contract and tags the response with the record version
+    fn record_response<TEntity, TRecord, TContract>(
+        record: TRecord,
+        status: Status,
+        transformation_function: impl Fn(TRecord) -> TContract,
+    ) -> ApiResponse
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+        TContract: Serialize,
+    {
+        let version = record.get_version();
+        let contract: TContract = transformation_function(record);
+
+        ApiResponse {
+            json: serde_json::to_string(&contract).unwrap(),
+            status,
+            etag: Some(etag_for(version)),
         }
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "health",
+    responses((status = 200, description = "A greeting", body = String))
+)]
 #[get("/")]
 pub fn index() -> &'static str {
     "Hello, world!"
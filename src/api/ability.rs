@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    engine::ability::{Ability, AbilityKind},
+    storage::combatants::{AbilityEntity, AbilityKindEntity, StatusEffectEntity},
+};
+
+use super::status_effect::StatusEffectContract;
+
+#[derive(Deserialize, Serialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AbilityKindContract {
+    AreaAttack,
+    Charge,
+    HealAlly,
+    Shield,
+}
+
+/// A special action used instead of moving and attacking, power is the damage dealt,
+/// the hit points healed or the damage absorbed by a shield
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct AbilityContract {
+    pub name: String,
+    pub kind: AbilityKindContract,
+    pub range: u32,
+    #[serde(default)]
+    pub cooldown: u32,
+    #[serde(default)]
+    pub power: u16,
+    #[serde(default)]
+    pub effect: Option<StatusEffectContract>,
+}
+
+impl From<AbilityKind> for AbilityKindContract {
+    fn from(value: AbilityKind) -> Self {
+        match value {
+            AbilityKind::AreaAttack => AbilityKindContract::AreaAttack,
+            AbilityKind::Charge => AbilityKindContract::Charge,
+            AbilityKind::HealAlly => AbilityKindContract::HealAlly,
+            AbilityKind::Shield => AbilityKindContract::Shield,
+        }
+    }
+}
+
+impl From<AbilityKindContract> for AbilityKind {
+    fn from(value: AbilityKindContract) -> Self {
+        match value {
+            AbilityKindContract::AreaAttack => AbilityKind::AreaAttack,
+            AbilityKindContract::Charge => AbilityKind::Charge,
+            AbilityKindContract::HealAlly => AbilityKind::HealAlly,
+            AbilityKindContract::Shield => AbilityKind::Shield,
+        }
+    }
+}
+
+impl From<AbilityKindEntity> for AbilityKindContract {
+    fn from(value: AbilityKindEntity) -> Self {
+        match value {
+            AbilityKindEntity::AreaAttack => AbilityKindContract::AreaAttack,
+            AbilityKindEntity::Charge => AbilityKindContract::Charge,
+            AbilityKindEntity::HealAlly => AbilityKindContract::HealAlly,
+            AbilityKindEntity::Shield => AbilityKindContract::Shield,
+        }
+    }
+}
+
+impl From<AbilityKindContract> for AbilityKindEntity {
+    fn from(value: AbilityKindContract) -> Self {
+        match value {
+            AbilityKindContract::AreaAttack => AbilityKindEntity::AreaAttack,
+            AbilityKindContract::Charge => AbilityKindEntity::Charge,
+            AbilityKindContract::HealAlly => AbilityKindEntity::HealAlly,
+            AbilityKindContract::Shield => AbilityKindEntity::Shield,
+        }
+    }
+}
+
+impl From<&Ability> for AbilityContract {
+    fn from(value: &Ability) -> Self {
+        AbilityContract {
+            name: value.name.clone(),
+            kind: AbilityKindContract::from(value.kind),
+            range: value.range,
+            cooldown: value.cooldown,
+            power: value.power,
+            effect: value.effect.as_ref().map(StatusEffectContract::from),
+        }
+    }
+}
+
+impl From<&AbilityContract> for Ability {
+    fn from(value: &AbilityContract) -> Self {
+        Ability {
+            effect: value.effect.as_ref().map(|e| e.into()),
+            ..Ability::new(
+                &value.name,
+                AbilityKind::from(value.kind),
+                value.range,
+                value.cooldown,
+                value.power,
+            )
+        }
+    }
+}
+
+impl From<&AbilityEntity> for AbilityContract {
+    fn from(value: &AbilityEntity) -> Self {
+        AbilityContract {
+            name: value.name.clone(),
+            kind: AbilityKindContract::from(value.kind),
+            range: value.range,
+            cooldown: value.cooldown,
+            power: value.power,
+            effect: value.effect.as_ref().map(StatusEffectContract::from),
+        }
+    }
+}
+
+impl From<&AbilityContract> for AbilityEntity {
+    fn from(value: &AbilityContract) -> Self {
+        AbilityEntity {
+            name: value.name.clone(),
+            kind: AbilityKindEntity::from(value.kind),
+            range: value.range,
+            cooldown: value.cooldown,
+            power: value.power,
+            effect: value.effect.as_ref().map(StatusEffectEntity::from),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rocket::{
+    fairing::{Fairing, Info, Kind, Result},
+    http::Status,
+    request::{FromRequest, Outcome},
+    Build, Request, Rocket,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::AppConfig,
+    storage::{
+        users::{UserEntity, UserRecord},
+        GenericEntity, Record, Storage,
+    },
+};
+
+// The length of the generated API keys
+const API_KEY_LENGTH: usize = 40;
+
+// The name of the user created from the configured admin API key
+const ADMIN_USER_NAME: &str = "admin";
+
+/// Hashes the API key, so that the key itself never has to be stored
+pub fn hash_api_key(api_key: &str) -> String {
+    format!("{:x}", Sha256::digest(api_key.as_bytes()))
+}
+
+pub fn generate_api_key() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_KEY_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// The user on whose behalf a request is executed
+///
+/// The API key is expected in the X-Api-Key header or as a bearer token in the Authorization header.
+/// If authentication is disabled every request is executed as an anonymous admin.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub id: Option<String>,
+    pub name: String,
+    pub is_admin: bool,
+}
+
+impl AuthenticatedUser {
+    fn anonymous() -> Self {
+        AuthenticatedUser {
+            id: None,
+            name: String::from("anonymous"),
+            is_admin: true,
+        }
+    }
+
+    /// Admins can access everything, all other users only what they own
+    pub fn can_access(&self, owner: &Option<String>) -> bool {
+        self.is_admin || (owner.is_some() && *owner == self.id)
+    }
+}
+
+impl From<&UserRecord> for AuthenticatedUser {
+    fn from(value: &UserRecord) -> Self {
+        AuthenticatedUser {
+            id: Some(value.get_id()),
+            name: value.name.clone(),
+            is_admin: value.is_admin,
+        }
+    }
+}
+
+fn api_key_from(request: &Request<'_>) -> Option<String> {
+    match request.headers().get_one("X-Api-Key") {
+        Some(key) => Some(key.trim().to_owned()),
+        None => request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|key| key.trim().to_owned()),
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthenticatedUser {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let auth_enabled = request
+            .rocket()
+            .state::<AppConfig>()
+            .map(|c| c.auth.enabled)
+            .unwrap_or(true);
+
+        if !auth_enabled {
+            return Outcome::Success(AuthenticatedUser::anonymous());
+        }
+
+        let api_key = match api_key_from(request) {
+            Some(key) => key,
+            None => {
+                return Outcome::Failure((Status::Unauthorized, String::from("API key missing")))
+            }
+        };
+
+        let storage = match request.rocket().state::<Storage>() {
+            Some(s) => s,
+            None => {
+                return Outcome::Failure((
+                    Status::InternalServerError,
+                    String::from("no storage configured"),
+                ))
+            }
+        };
+
+        let users: std::result::Result<Vec<UserRecord>, _> =
+            GenericEntity::new::<UserEntity>(storage)
+                .find_by("api_key_hash", &hash_api_key(&api_key))
+                .await;
+
+        match users {
+            Ok(users) => match users.first() {
+                Some(user) => Outcome::Success(AuthenticatedUser::from(user)),
+                None => Outcome::Failure((Status::Unauthorized, String::from("unknown API key"))),
+            },
+            Err(e) => Outcome::Failure((Status::InternalServerError, e.to_string())),
+        }
+    }
+}
+
+/// Makes sure that the admin user for the configured admin API key exists
+pub struct AuthMiddleware;
+
+#[rocket::async_trait]
+impl Fairing for AuthMiddleware {
+    fn info(&self) -> Info {
+        Info {
+            name: "Authentication Middleware",
+            kind: Kind::Ignite,
+        }
+    }
+
+    /// Has to be attached after the DbMiddleware, as the storage is needed to create the admin
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> Result {
+        let admin_api_key = match rocket.state::<AppConfig>() {
+            Some(config) if config.auth.enabled => config.auth.admin_api_key.clone(),
+            _ => None,
+        };
+
+        let (api_key, storage) = match (admin_api_key, rocket.state::<Storage>()) {
+            (Some(key), Some(storage)) => (key, storage),
+            _ => return Ok(rocket),
+        };
+
+        let db_access: GenericEntity<'_> = GenericEntity::new::<UserEntity>(storage);
+        let api_key_hash = hash_api_key(&api_key);
+        let existing: std::result::Result<Vec<UserRecord>, _> =
+            db_access.find_by("api_key_hash", &api_key_hash).await;
+
+        let created: std::result::Result<UserRecord, _> = match existing {
+            Ok(users) if !users.is_empty() => return Ok(rocket),
+            Ok(_) => {
+                db_access
+                    .create_new(
+                        UserEntity {
+                            name: String::from(ADMIN_USER_NAME),
+                            api_key_hash,
+                            is_admin: true,
+                        },
+                        None,
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match created {
+            Ok(_) => {
+                info!("created the {} user for the configured API key", ADMIN_USER_NAME);
+                Ok(rocket)
+            }
+            Err(e) => {
+                error!("Could not create the {} user: {}", ADMIN_USER_NAME, e);
+                Err(rocket)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use super::*;
+    use crate::api::test_client::{api_key, client};
+
+    #[test]
+    fn hash_api_key_is_stable() {
+        assert_eq!(hash_api_key("some key"), hash_api_key("some key"));
+        assert_ne!(hash_api_key("some key"), hash_api_key("other key"));
+    }
+
+    #[test]
+    fn generate_api_key_length() {
+        assert_eq!(API_KEY_LENGTH, generate_api_key().len());
+    }
+
+    #[test]
+    fn admin_can_access_unowned() {
+        let admin = AuthenticatedUser {
+            id: Some(String::from("admin")),
+            name: String::from("admin"),
+            is_admin: true,
+        };
+
+        assert!(admin.can_access(&None));
+    }
+
+    #[test]
+    fn user_can_access_only_owned() {
+        let user = AuthenticatedUser {
+            id: Some(String::from("user")),
+            name: String::from("user"),
+            is_admin: false,
+        };
+
+        assert!(user.can_access(&Some(String::from("user"))));
+        assert!(!user.can_access(&Some(String::from("other"))));
+        assert!(!user.can_access(&None));
+    }
+
+    #[test]
+    fn combatants_without_api_key() {
+        let client = client();
+
+        let response = client.get("/combatants").dispatch();
+
+        assert_eq!(Status::Unauthorized, response.status());
+    }
+
+    #[test]
+    fn combatants_with_unknown_api_key() {
+        let client = client();
+
+        let response = client
+            .get("/combatants")
+            .header(api_key("not-a-valid-key"))
+            .dispatch();
+
+        assert_eq!(Status::Unauthorized, response.status());
+    }
+}
@@ -1,32 +1,91 @@
-use rocket::{http::Status, serde::json::Json};
+use std::collections::HashMap;
+
+use rocket::{http::Status, serde::json::Json, State};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use crate::{
+    config::AppConfig,
     engine::{
         battle_actions::BattleAction, battle_engine::BattleEngine, battle_result::BattleResult,
-        battlefield::BattlefieldData, combatant::Combatant, map::Map,
+        battle_rules::BattleRules, battlefield::BattlefieldData, combatant::Combatant, map::Map,
+        status_effect::StatusEffect, ability::Ability, battle_actions::AbilityTarget,
+        movement::MovementResult, item::Item, progression::ProgressionRules, err::Error,
+    },
+    storage::{
+        combatants::{CombatantEntity, CombatantRecord},
+        GenericEntity, Storage,
+    },
+    types::{
+        point::{Coordinate, Point},
+        topology::Topology,
     },
-    types::point::Point,
 };
 
-use super::{combatant::CombatantContract, ApiResponse};
+use super::{
+    ability::{AbilityContract, AbilityKindContract},
+    auth::AuthenticatedUser,
+    combatant::{name_apart, record_battle, CombatantContract},
+    item::equipped_items,
+    replay::store_battle,
+    status_effect::{StatusEffectContract, StatusEffectKindContract},
+    ApiResponse, CrudApiScaffold,
+};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct CreateBattleContract {
-    map: BattleBapContract,
-    combatants: Vec<CombatantContract>,
+    pub map: BattleBapContract,
+    pub combatants: Vec<CombatantContract>,
+    /// The id of the stored battlefield the battle is fought on, used to filter the leaderboard
+    #[serde(default)]
+    pub battlefield: Option<String>,
+    /// Fighting again with the same seed leads to the same battle, a random seed is drawn if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct BattleBapContract {
-    pub height: u8,
-    pub width: u8,
+    pub height: Coordinate,
+    pub width: Coordinate,
+    /// The configured topology is used if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topology: Option<TopologyContract>,
+}
+
+/// How the locations of the map are connected, hex maps use axial coordinates
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TopologyContract {
+    Square4,
+    Square8,
+    Hex,
+}
+
+impl From<Topology> for TopologyContract {
+    fn from(value: Topology) -> Self {
+        match value {
+            Topology::Square4 => TopologyContract::Square4,
+            Topology::Square8 => TopologyContract::Square8,
+            Topology::Hex => TopologyContract::Hex,
+        }
+    }
+}
+
+impl From<TopologyContract> for Topology {
+    fn from(value: TopologyContract) -> Self {
+        match value {
+            TopologyContract::Square4 => Topology::Square4,
+            TopologyContract::Square8 => Topology::Square8,
+            TopologyContract::Hex => Topology::Hex,
+        }
+    }
 }
 
-impl TryFrom<Json<CreateBattleContract>> for BattlefieldData {
-    type Error = crate::engine::err::Error;
+impl TryFrom<&CreateBattleContract> for BattlefieldData {
+    type Error = Error;
 
-    fn try_from(value: Json<CreateBattleContract>) -> Result<Self, Self::Error> {
+    fn try_from(value: &CreateBattleContract) -> Result<Self, Self::Error> {
         let battlefield = BattlefieldData {
             battlefield_height: value.map.height,
             battlefield_width: value.map.width,
@@ -46,15 +105,29 @@ impl From<&CombatantContract> for Combatant {
         Combatant {
             name: value.name.clone(),
             hp: value.hp,
+            max_hp: value.max_hp.unwrap_or(value.hp),
             dmg: value.dmg,
             avatar: value.avatar.clone(),
-            id: value.id.clone()
+            id: value.id.clone(),
+            effects: value.effects.iter().map(StatusEffect::from).collect(),
+            on_hit: value.on_hit.iter().map(StatusEffect::from).collect(),
+            abilities: value.abilities.iter().map(Ability::from).collect(),
+            team: value.team.clone(),
+            ..Default::default()
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct BattleResultContract {
+    /// The id to replay the battle with, not set if the battle could not be stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The ids of the stored combatants whose progression could not be stored, the battle does not count for them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unrecorded: Vec<String>,
+    /// Fighting the same combatants with this seed leads to the same battle
+    pub seed: u64,
     pub combatants: Vec<CombatantContract>,
     pub map: MapContract,
     pub actions: Vec<BattleActionContract>,
@@ -62,32 +135,38 @@ pub struct BattleResultContract {
     pub winner: Option<CombatantContract>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct MapContract {
-    width: u8,
-    height: u8,
+    width: Coordinate,
+    height: Coordinate,
+    topology: TopologyContract,
     pois: Vec<PoiContract>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct PoiContract {
     location: PointContract,
     name: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy)]
 pub struct PointContract {
-    x: u8,
-    y: u8,
+    x: Coordinate,
+    y: Coordinate,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub enum BattleActionContract {
     Move(BattleActionMoveContract),
     Attack(BattleActionAttackContract),
+    EffectApplied(BattleActionEffectAppliedContract),
+    EffectTicked(BattleActionEffectTickedContract),
+    Ability(BattleActionAbilityContract),
+    Heal(BattleActionHealContract),
+    Revive(BattleActionReviveContract),
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct BattleActionMoveContract {
     round: u32,
     combatant: String,
@@ -96,13 +175,70 @@ pub struct BattleActionMoveContract {
     path: Vec<PointContract>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct BattleActionAttackContract {
     round: u32,
     attacker: String,
     attacked: String,
     dmg: u16,
     remaining_hp: u16,
+    max_hp: u16,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BattleActionHealContract {
+    round: u32,
+    healer: String,
+    healed: String,
+    amount: u16,
+    remaining_hp: u16,
+    max_hp: u16,
+    /// The consumable used, not set for heals by abilities
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BattleActionReviveContract {
+    round: u32,
+    combatant: String,
+    location: PointContract,
+    hp: u16,
+    max_hp: u16,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BattleActionEffectAppliedContract {
+    round: u32,
+    combatant: String,
+    effect: StatusEffectContract,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BattleActionAbilityContract {
+    round: u32,
+    combatant: String,
+    ability: String,
+    kind: AbilityKindContract,
+    targets: Vec<AbilityTargetContract>,
+    path: Vec<PointContract>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AbilityTargetContract {
+    name: String,
+    amount: u16,
+    remaining_hp: u16,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BattleActionEffectTickedContract {
+    round: u32,
+    combatant: String,
+    kind: StatusEffectKindContract,
+    amount: u16,
+    remaining_hp: u16,
+    remaining_turns: u32,
 }
 
 impl From<Point> for PointContract {
@@ -114,11 +250,38 @@ impl From<Point> for PointContract {
     }
 }
 
+impl From<&PointContract> for Point {
+    fn from(value: &PointContract) -> Self {
+        Point::new(value.x, value.y)
+    }
+}
+
+impl PointContract {
+    /// The steps taken by an action that might include a movement
+    pub fn path_of(movement: &Option<MovementResult>) -> Vec<PointContract> {
+        match movement {
+            Some(m) => m.steps.iter().map(|p| PointContract::from(*p)).collect(),
+            None => vec![],
+        }
+    }
+}
+
+impl From<&AbilityTarget> for AbilityTargetContract {
+    fn from(value: &AbilityTarget) -> Self {
+        Self {
+            name: value.name.clone(),
+            amount: value.amount,
+            remaining_hp: value.remaining_hp,
+        }
+    }
+}
+
 impl From<Map> for MapContract {
     fn from(value: Map) -> Self {
         Self {
             width: value.get_width(),
             height: value.get_height(),
+            topology: TopologyContract::from(value.get_topology()),
             pois: value
                 .get_pois()
                 .iter()
@@ -140,6 +303,9 @@ impl From<&(Point, String)> for PoiContract {
 impl From<BattleResult> for BattleResultContract {
     fn from(value: BattleResult) -> Self {
         Self {
+            id: value.id.clone(),
+            unrecorded: value.unrecorded.clone(),
+            seed: value.seed,
             combatants: value
                 .combatants
                 .iter()
@@ -163,14 +329,21 @@ impl From<&Combatant> for CombatantContract {
             name: value.name.clone(),
             id: value.id.clone(),
             hp: value.hp,
+            max_hp: Some(value.max_hp),
             dmg: value.dmg,
-            avatar: value.avatar.clone()
+            avatar: value.avatar.clone(),
+            effects: value.effects.iter().map(StatusEffectContract::from).collect(),
+            on_hit: value.on_hit.iter().map(StatusEffectContract::from).collect(),
+            abilities: value.abilities.iter().map(AbilityContract::from).collect(),
+            team: value.team.clone(),
+            equipment: vec![],
+            progression: None,
         }
     }
 }
 
 impl CombatantContract {
-    fn from_option(value: Option<Combatant>) -> Option<Self> {
+    pub fn from_option(value: Option<Combatant>) -> Option<Self> {
         match value {
             Some(_v) => Some(CombatantContract::from(&_v)),
             None => None,
@@ -199,45 +372,301 @@ impl From<&BattleAction> for BattleActionContract {
                     round: r_num.clone(),
                     attacker: action.assailant.name.clone(),
                     attacked: action.victim.name.clone(),
-                    dmg: action.assailant.dmg,
+                    dmg: action.damage,
                     remaining_hp: action.victim.hp,
+                    max_hp: action.victim.max_hp,
+                })
+            }
+            BattleAction::EffectApplied(r_num, combatant, effect) => {
+                BattleActionContract::EffectApplied(BattleActionEffectAppliedContract {
+                    round: *r_num,
+                    combatant: combatant.clone(),
+                    effect: StatusEffectContract::from(effect),
+                })
+            }
+            BattleAction::EffectTicked(r_num, combatant, tick) => {
+                BattleActionContract::EffectTicked(BattleActionEffectTickedContract {
+                    round: *r_num,
+                    combatant: combatant.clone(),
+                    kind: StatusEffectKindContract::from(tick.kind),
+                    amount: tick.amount,
+                    remaining_hp: tick.remaining_hp,
+                    remaining_turns: tick.remaining_turns,
+                })
+            }
+            BattleAction::Ability(r_num, action) => {
+                BattleActionContract::Ability(BattleActionAbilityContract {
+                    round: *r_num,
+                    combatant: action.user.clone(),
+                    ability: action.ability.clone(),
+                    kind: AbilityKindContract::from(action.kind),
+                    targets: action.targets.iter().map(AbilityTargetContract::from).collect(),
+                    path: PointContract::path_of(&action.movement),
+                })
+            }
+            BattleAction::Heal(r_num, action) => BattleActionContract::Heal(BattleActionHealContract {
+                round: *r_num,
+                healer: action.healer.name.clone(),
+                healed: action.target.name.clone(),
+                amount: action.amount,
+                remaining_hp: action.target.hp,
+                max_hp: action.target.max_hp,
+                item: action.item.clone(),
+            }),
+            BattleAction::Revive(r_num, combatant, location) => {
+                BattleActionContract::Revive(BattleActionReviveContract {
+                    round: *r_num,
+                    combatant: combatant.name.clone(),
+                    location: PointContract::from(*location),
+                    hp: combatant.hp,
+                    max_hp: combatant.max_hp,
                 })
             }
         }
     }
 }
 
-#[post("/", format = "json", data = "<post_data>")]
-pub async fn start_new_battle(post_data: Json<CreateBattleContract>) -> ApiResponse {
-    let battlefield = BattlefieldData::try_from(post_data);
-
-    match battlefield {
-        Ok(b) => {
-            let engine = BattleEngine::new(b);
-
-            match engine {
-                Ok(mut e) => {
-                    let battle_results = e.start_battle();
-
-                    match battle_results {
-                        Ok(result) => {
-                            let serialization_result =
-                                serde_json::to_string(&BattleResultContract::from(result));
-
-                            match serialization_result {
-                                Ok(json) => ApiResponse {
-                                    json: json,
-                                    status: Status::Ok,
-                                },
-                                Err(_) => ApiResponse::empty(Status::InternalServerError),
-                            }
-                        }
-                        Err(error) => ApiResponse::from(error),
-                    }
-                }
-                Err(_) => ApiResponse::empty(Status::InternalServerError),
+/// Sets up the battle from the posted data and lets it play out
+///
+/// Shared by all API versions, only the contract of the result differs between them.
+/// Combatants with an id fight as stored, with their stored items, and progress afterwards.
+pub async fn run_battle(
+    post_data: Json<CreateBattleContract>,
+    config: &AppConfig,
+    db: &Storage,
+    user: &AuthenticatedUser,
+) -> Result<BattleResult, ApiResponse> {
+    let mut post_data = post_data.into_inner();
+    post_data.combatants = load_stored(db, user, &post_data.combatants).await?;
+
+    let items = equipped_items(db, user, &post_data.combatants).await;
+    let stored: Vec<(String, String)> = post_data
+        .combatants
+        .iter()
+        .filter_map(|c| c.id.clone().map(|id| (id, c.name.clone())))
+        .collect();
+    let battlefield = post_data.battlefield.clone();
+
+    let mut result = play_battle(&post_data, BattleRules::from(&config.battle), &items)?;
+
+    result.unrecorded = record_battle(
+        db,
+        user,
+        &ProgressionRules::from(&config.progression),
+        &stored,
+        &result.outcomes(),
+        battlefield,
+    )
+    .await;
+    result.id = store_battle(db, user, &result).await;
+
+    Ok(result)
+}
+
+/// Replaces the posted combatants with an id by the stored ones, the posted stats of those are ignored
+///
+/// Stored combatants with the same name as another combatant are told apart by their position,
+/// the battle map needs unique names. A stored combatant can only take part once.
+async fn load_stored(
+    db: &Storage,
+    user: &AuthenticatedUser,
+    combatants: &[CombatantContract],
+) -> Result<Vec<CombatantContract>, ApiResponse> {
+    let db_access: GenericEntity<'_> = GenericEntity::new::<CombatantEntity>(db);
+    let mut loaded: Vec<CombatantContract> = vec![];
+
+    for contract in combatants.iter() {
+        let id = match &contract.id {
+            Some(id) => id,
+            None => {
+                loaded.push(contract.clone());
+                continue;
             }
+        };
+
+        if combatants.iter().filter(|c| c.id.as_ref() == Some(id)).count() > 1 {
+            return Err(ApiResponse::from(Error::UserAlreadyOnMap));
         }
-        Err(_) => ApiResponse::empty(Status::BadRequest),
+
+        let record: CombatantRecord =
+            CrudApiScaffold::accessible_record::<CombatantEntity, CombatantRecord>(&db_access, id, user).await?;
+        loaded.push(CombatantContract::from(&record));
+    }
+
+    name_apart(&mut loaded);
+
+    Ok(loaded)
+}
+
+/// Equips the combatants with the given items, looked up by the ids in their equipment, and runs the battle
+pub fn play_battle(
+    post_data: &CreateBattleContract,
+    mut rules: BattleRules,
+    items: &HashMap<String, Item>,
+) -> Result<BattleResult, ApiResponse> {
+    if let Some(topology) = post_data.map.topology {
+        rules.topology = Topology::from(topology);
+    }
+    rules.seed = post_data.seed;
+    let equipment: Vec<Vec<Item>> = post_data
+        .combatants
+        .iter()
+        .map(|c| c.equipment.iter().filter_map(|id| items.get(id).cloned()).collect())
+        .collect();
+    let mut battlefield = BattlefieldData::try_from(post_data)
+        .map_err(|_| ApiResponse::empty(Status::BadRequest))?;
+
+    for (combatant, items) in battlefield.combatants.iter_mut().zip(equipment) {
+        combatant.equip(items);
+    }
+
+    let mut engine = BattleEngine::new(battlefield, rules).map_err(ApiResponse::from)?;
+
+    engine.start_battle().map_err(ApiResponse::from)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/battle",
+    tag = "battle",
+    request_body = CreateBattleContract,
+    responses(
+        (status = 200, description = "The course and outcome of the battle", body = BattleResultContract),
+        (status = 400, description = "The battle can not be set up"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 409, description = "Two combatants were placed on the same location")
+    ),
+    security(("api_key" = []))
+)]
+#[post("/", format = "json", data = "<post_data>")]
+pub async fn start_new_battle(
+    post_data: Json<CreateBattleContract>,
+    config: &State<AppConfig>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    match run_battle(post_data, config.inner(), db.inner(), &user).await {
+        Ok(result) => match serde_json::to_string(&BattleResultContract::from(result)) {
+            Ok(json) => ApiResponse {
+                json,
+                status: Status::Ok,
+                etag: None,
+            },
+            Err(_) => ApiResponse::empty(Status::InternalServerError),
+        },
+        Err(response) => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use crate::api::test_client::{battle_of, client, create_combatant, create_user, get_json, post_json, ADMIN_KEY};
+
+    #[test]
+    fn battle_on_map_larger_than_255() {
+        let client = client();
+
+        let response = post_json(
+            &client,
+            "/v1/battle",
+            ADMIN_KEY,
+            r#"{"map": {"height": 2, "width": 1000, "topology": "square8"}, "combatants": [
+                {"name": "a", "dmg": 50, "hp": 10}, {"name": "b", "dmg": 50, "hp": 10}]}"#,
+        );
+
+        assert_eq!(Status::Ok, response.status());
+        let result: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(1000, result["map"]["width"]);
+        assert_eq!("square8", result["map"]["topology"]);
+    }
+
+    #[test]
+    fn battle_on_full_map() {
+        let client = client();
+
+        let response = post_json(
+            &client,
+            "/v1/battle",
+            ADMIN_KEY,
+            r#"{"map": {"height": 1, "width": 1}, "combatants": [
+                {"name": "a", "dmg": 2, "hp": 10}, {"name": "b", "dmg": 2, "hp": 10}]}"#,
+        );
+
+        assert_eq!(Status::BadRequest, response.status());
+    }
+
+    #[test]
+    fn battle_with_combatant_twice() {
+        let client = client();
+
+        let response = post_json(
+            &client,
+            "/v1/battle",
+            ADMIN_KEY,
+            r#"{"map": {"height": 4, "width": 4}, "combatants": [
+                {"name": "a", "dmg": 2, "hp": 10}, {"name": "a", "dmg": 2, "hp": 10}]}"#,
+        );
+
+        assert_eq!(Status::BadRequest, response.status());
+    }
+
+    #[test]
+    fn battle_progresses_stored_combatants() {
+        let client = client();
+        let first = create_combatant(&client, ADMIN_KEY);
+        let second = create_combatant(&client, ADMIN_KEY);
+
+        let response = post_json(&client, "/battle", ADMIN_KEY, battle_of(&first, &second));
+        assert_eq!(Status::Ok, response.status());
+
+        let progression: Vec<serde_json::Value> = [first, second]
+            .iter()
+            .map(|id| {
+                let combatant: serde_json::Value = get_json(&client, format!("/combatants/{}", id), ADMIN_KEY);
+                combatant["progression"].clone()
+            })
+            .collect();
+
+        assert!(progression.iter().all(|p| p["battles"] == 1));
+        assert_eq!(1, progression.iter().map(|p| p["kills"].as_u64().unwrap()).sum::<u64>());
+        assert_eq!(1, progression.iter().map(|p| p["wins"].as_u64().unwrap()).sum::<u64>());
+    }
+
+    #[test]
+    fn battle_uses_stored_combatants() {
+        let client = client();
+        let user_key = create_user(&client);
+        let id = create_combatant(&client, ADMIN_KEY);
+        let inflated = format!(
+            r#"{{"map": {{"height": 8, "width": 8}}, "combatants": [
+                {{"name": "test", "hp": 500, "max_hp": 500, "dmg": 500, "id": "{}"}},
+                {{"name": "test", "hp": 10, "dmg": 1}}]}}"#,
+            id
+        );
+        let twice = format!(
+            r#"{{"map": {{"height": 8, "width": 8}}, "combatants": [
+                {{"name": "a", "hp": 10, "dmg": 2, "id": "{0}"}}, {{"name": "b", "hp": 10, "dmg": 2, "id": "{0}"}}]}}"#,
+            id
+        );
+
+        let response = post_json(&client, "/battle", ADMIN_KEY, &inflated);
+        let hidden = post_json(&client, "/battle", &user_key, &inflated);
+        let repeated = post_json(&client, "/battle", ADMIN_KEY, twice);
+
+        assert_eq!(Status::Ok, response.status());
+        let result: serde_json::Value = response.into_json().unwrap();
+        let stored = result["combatants"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["id"] == id.as_str())
+            .unwrap();
+        assert_eq!(10, stored["max_hp"]);
+        assert_eq!(2, stored["dmg"]);
+        assert_eq!("test #1", stored["name"]);
+        assert_eq!(Status::NotFound, hidden.status());
+        assert_eq!(Status::BadRequest, repeated.status());
     }
 }
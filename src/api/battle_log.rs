@@ -0,0 +1,893 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use rocket::{
+    data::{Data, Limits, ToByteUnit},
+    http::{ContentType, Status},
+    State,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    engine::{
+        ability::{Ability, AbilityKind},
+        battle_actions::{
+            AbilityTarget, BattleAbilityAction, BattleAction, BattleAttackAction, BattleHealAction,
+        },
+        battle_engine::BattleRoundState,
+        battle_result::BattleResult,
+        battle_rules::{BattleRules, ReviveRules},
+        combatant::Combatant,
+        item::{Item, ItemKind},
+        map::Map,
+        movement::MovementResult,
+        replay::Replay,
+        status_effect::{StatusEffect, StatusEffectKind, StatusEffectTick},
+    },
+    storage::{
+        battles::{BattleEntity, BattleRecord},
+        items::ItemKindEntity,
+        GenericEntity, Record, Storage,
+    },
+    types::{
+        point::{Coordinate, Point},
+        topology::Topology,
+    },
+};
+
+use super::{
+    ability::{AbilityContract, AbilityKindContract},
+    auth::AuthenticatedUser,
+    battle::{PointContract, TopologyContract},
+    item::{ItemContract, ItemKindContract},
+    replay::{initial_state, logged, state_response},
+    status_effect::{StatusEffectContract, StatusEffectKindContract},
+    ApiResponse, CrudApiScaffold,
+};
+
+/// Names the format in the header of every log
+pub const LOG_FORMAT: &str = "ribs-battle-log";
+
+/// Raised whenever the log changes in a way older readers can not follow
+pub const LOG_VERSION: u32 = 1;
+
+/// The name of the Rocket limit for imported logs
+const LOG_LIMIT: &str = "battle-log";
+
+/// Everything about the battle but its events, the first line of a JSON Lines log
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BattleLogHeaderContract {
+    /// Always `ribs-battle-log`
+    pub format: String,
+    pub version: u32,
+    /// The id of the stored battle, ignored when the log is imported
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub fought_at: Option<DateTime<Utc>>,
+    /// Playing the combatants with the rules and this seed again leads to the same battle
+    pub seed: u64,
+    pub rules: BattleLogRulesContract,
+    pub map: BattleLogMapContract,
+    /// The combatants before the first round, with the stats of their equipment included
+    pub combatants: Vec<BattleLogCombatantContract>,
+    pub rounds: u32,
+    pub winner: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BattleLogRulesContract {
+    pub max_rounds: u32,
+    pub max_combatant_move: u32,
+    pub revive: Option<BattleLogReviveContract>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BattleLogReviveContract {
+    pub after_rounds: u32,
+    pub hp_percent: u8,
+    pub max_revives: u32,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BattleLogMapContract {
+    pub width: Coordinate,
+    pub height: Coordinate,
+    pub topology: TopologyContract,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BattleLogCombatantContract {
+    pub name: String,
+    /// The id of the stored combatant, if the combatant was stored
+    pub id: Option<String>,
+    pub avatar: Option<String>,
+    pub team: Option<String>,
+    pub hp: u16,
+    pub max_hp: u16,
+    pub dmg: u16,
+    pub armor: u16,
+    pub attack_range: u32,
+    pub speed: i8,
+    pub effects: Vec<StatusEffectContract>,
+    pub on_hit: Vec<StatusEffectContract>,
+    pub abilities: Vec<AbilityContract>,
+    pub consumables: Vec<ItemContract>,
+    pub location: PointContract,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BattleLogMovementContract {
+    pub start: PointContract,
+    pub goal: PointContract,
+    /// Every location stepped on, the last one is where the combatant ended up
+    pub path: Vec<PointContract>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BattleLogTargetContract {
+    pub name: String,
+    pub amount: u16,
+    pub remaining_hp: u16,
+}
+
+/// A single event of the battle, the kind of event is given in the type field
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BattleLogEventContract {
+    Move {
+        round: u32,
+        combatant: String,
+        movement: BattleLogMovementContract,
+    },
+    Attack {
+        round: u32,
+        attacker: String,
+        attacked: String,
+        damage: u16,
+        remaining_hp: u16,
+    },
+    EffectApplied {
+        round: u32,
+        combatant: String,
+        effect: StatusEffectContract,
+    },
+    EffectTicked {
+        round: u32,
+        combatant: String,
+        kind: StatusEffectKindContract,
+        amount: u16,
+        remaining_hp: u16,
+        remaining_turns: u32,
+    },
+    Ability {
+        round: u32,
+        combatant: String,
+        ability: String,
+        kind: AbilityKindContract,
+        targets: Vec<BattleLogTargetContract>,
+        movement: Option<BattleLogMovementContract>,
+    },
+    Heal {
+        round: u32,
+        healer: String,
+        healed: String,
+        amount: u16,
+        remaining_hp: u16,
+        item: Option<String>,
+    },
+    Revive {
+        round: u32,
+        combatant: String,
+        location: PointContract,
+        hp: u16,
+    },
+}
+
+/// A whole battle, the header followed by its events in the order they took place
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BattleLogContract {
+    pub header: BattleLogHeaderContract,
+    pub events: Vec<BattleLogEventContract>,
+}
+
+/// The encodings a log is exported and imported in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// The header on the first line and one event per line
+    JsonLines,
+    /// The whole log as a single CBOR document
+    Cbor,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "jsonl" => Ok(LogFormat::JsonLines),
+            "cbor" => Ok(LogFormat::Cbor),
+            other => Err(format!("unknown log format {}, use jsonl or cbor", other)),
+        }
+    }
+}
+
+impl LogFormat {
+    fn parse(value: Option<&str>) -> Result<Self, ApiResponse> {
+        value.map_or(Ok(LogFormat::JsonLines), LogFormat::from_str).map_err(|json| ApiResponse {
+            json,
+            status: Status::BadRequest,
+            etag: None,
+        })
+    }
+
+    fn content_type(&self) -> ContentType {
+        match self {
+            LogFormat::JsonLines => ContentType::new("application", "x-ndjson"),
+            LogFormat::Cbor => ContentType::new("application", "cbor"),
+        }
+    }
+}
+
+impl BattleLogContract {
+    pub fn encode(&self, format: LogFormat) -> Result<Vec<u8>, String> {
+        match format {
+            LogFormat::JsonLines => {
+                let mut lines: Vec<String> = vec![serde_json::to_string(&self.header).map_err(|e| e.to_string())?];
+                for event in self.events.iter() {
+                    lines.push(serde_json::to_string(event).map_err(|e| e.to_string())?);
+                }
+
+                Ok((lines.join("\n") + "\n").into_bytes())
+            }
+            LogFormat::Cbor => {
+                let mut bytes: Vec<u8> = vec![];
+                ciborium::ser::into_writer(self, &mut bytes).map_err(|e| e.to_string())?;
+
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Reads a log of any version, the version is checked when the log is replayed
+    pub fn decode(bytes: &[u8], format: LogFormat) -> Result<Self, String> {
+        match format {
+            LogFormat::JsonLines => {
+                let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+                let mut lines = text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+
+                let header: BattleLogHeaderContract = match lines.next() {
+                    Some((_, line)) => serde_json::from_str(line).map_err(|e| format!("line 1: {}", e))?,
+                    None => return Err(String::from("the log is empty")),
+                };
+                let events = lines
+                    .map(|(number, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {}", number + 1, e)))
+                    .collect::<Result<Vec<BattleLogEventContract>, String>>()?;
+
+                Ok(BattleLogContract { header, events })
+            }
+            LogFormat::Cbor => ciborium::de::from_reader(bytes).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// The state before the first round, the rules and the actions of the battle
+    pub fn to_battle(&self) -> Result<(BattleRoundState, BattleRules, Vec<BattleAction>), ApiResponse> {
+        if self.header.format != LOG_FORMAT || self.header.version != LOG_VERSION {
+            return Err(ApiResponse {
+                json: format!(
+                    "unsupported log {} version {}, expected {} version {}",
+                    self.header.format, self.header.version, LOG_FORMAT, LOG_VERSION
+                ),
+                status: Status::BadRequest,
+                etag: None,
+            });
+        }
+
+        let topology = Topology::from(self.header.map.topology);
+        let initial = initial_state(
+            Map::with_topology(self.header.map.width, self.header.map.height, topology),
+            self.header
+                .combatants
+                .iter()
+                .map(|c| (Combatant::from(c), Point::from(&c.location)))
+                .collect(),
+        )?;
+        let rules = BattleRules {
+            topology,
+            seed: Some(self.header.seed),
+            ..BattleRules::from(&self.header.rules)
+        };
+
+        Ok((initial, rules, self.events.iter().map(BattleAction::from).collect()))
+    }
+}
+
+impl From<&BattleLogRulesContract> for BattleRules {
+    fn from(value: &BattleLogRulesContract) -> Self {
+        BattleRules {
+            max_rounds: value.max_rounds,
+            max_combatant_move: value.max_combatant_move as usize,
+            revive: value.revive.as_ref().map(|r| ReviveRules {
+                after_rounds: r.after_rounds,
+                hp_percent: r.hp_percent,
+                max_revives: r.max_revives,
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&BattleRules> for BattleLogRulesContract {
+    fn from(value: &BattleRules) -> Self {
+        BattleLogRulesContract {
+            max_rounds: value.max_rounds,
+            max_combatant_move: value.max_combatant_move as u32,
+            revive: value.revive.map(|r| BattleLogReviveContract {
+                after_rounds: r.after_rounds,
+                hp_percent: r.hp_percent,
+                max_revives: r.max_revives,
+            }),
+        }
+    }
+}
+
+impl BattleLogCombatantContract {
+    fn new(combatant: &Combatant, location: Point) -> Self {
+        BattleLogCombatantContract {
+            name: combatant.name.clone(),
+            id: combatant.id.clone(),
+            avatar: combatant.avatar.clone(),
+            team: combatant.team.clone(),
+            hp: combatant.hp,
+            max_hp: combatant.max_hp,
+            dmg: combatant.dmg,
+            armor: combatant.armor,
+            attack_range: combatant.attack_range,
+            speed: combatant.speed,
+            effects: combatant.effects.iter().map(StatusEffectContract::from).collect(),
+            on_hit: combatant.on_hit.iter().map(StatusEffectContract::from).collect(),
+            abilities: combatant.abilities.iter().map(AbilityContract::from).collect(),
+            consumables: combatant
+                .consumables
+                .iter()
+                .map(|i| ItemContract {
+                    id: None,
+                    name: i.name.clone(),
+                    kind: ItemKindContract::Consumable,
+                    damage: i.damage,
+                    armor: i.armor,
+                    range: i.range,
+                    speed: i.speed,
+                    heal: i.heal,
+                })
+                .collect(),
+            location: PointContract::from(location),
+        }
+    }
+}
+
+impl From<&BattleLogCombatantContract> for Combatant {
+    fn from(value: &BattleLogCombatantContract) -> Self {
+        Combatant {
+            name: value.name.clone(),
+            id: value.id.clone(),
+            avatar: value.avatar.clone(),
+            team: value.team.clone(),
+            hp: value.hp,
+            max_hp: value.max_hp.max(value.hp),
+            dmg: value.dmg,
+            armor: value.armor,
+            attack_range: value.attack_range,
+            speed: value.speed,
+            effects: value.effects.iter().map(StatusEffect::from).collect(),
+            on_hit: value.on_hit.iter().map(StatusEffect::from).collect(),
+            abilities: value.abilities.iter().map(Ability::from).collect(),
+            consumables: value
+                .consumables
+                .iter()
+                .map(|i| Item {
+                    name: i.name.clone(),
+                    kind: ItemKind::from(ItemKindEntity::from(i.kind)),
+                    damage: i.damage,
+                    armor: i.armor,
+                    range: i.range,
+                    speed: i.speed,
+                    heal: i.heal,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&MovementResult> for BattleLogMovementContract {
+    fn from(value: &MovementResult) -> Self {
+        BattleLogMovementContract {
+            start: PointContract::from(value.start),
+            goal: PointContract::from(value.goal),
+            path: value.steps.iter().map(|p| PointContract::from(*p)).collect(),
+        }
+    }
+}
+
+impl From<&BattleLogMovementContract> for MovementResult {
+    fn from(value: &BattleLogMovementContract) -> Self {
+        let steps: Vec<Point> = value.path.iter().map(Point::from).collect();
+
+        MovementResult {
+            start: Point::from(&value.start),
+            goal: Point::from(&value.goal),
+            last_position: steps.last().copied().unwrap_or(Point::from(&value.start)),
+            steps,
+        }
+    }
+}
+
+impl From<&BattleAction> for BattleLogEventContract {
+    fn from(value: &BattleAction) -> Self {
+        match value {
+            BattleAction::Move(round, combatant, movement) => BattleLogEventContract::Move {
+                round: *round,
+                combatant: combatant.clone(),
+                movement: BattleLogMovementContract::from(movement),
+            },
+            BattleAction::Attack(round, attack) => BattleLogEventContract::Attack {
+                round: *round,
+                attacker: attack.assailant.name.clone(),
+                attacked: attack.victim.name.clone(),
+                damage: attack.damage,
+                remaining_hp: attack.victim.hp,
+            },
+            BattleAction::EffectApplied(round, combatant, effect) => BattleLogEventContract::EffectApplied {
+                round: *round,
+                combatant: combatant.clone(),
+                effect: StatusEffectContract::from(effect),
+            },
+            BattleAction::EffectTicked(round, combatant, tick) => BattleLogEventContract::EffectTicked {
+                round: *round,
+                combatant: combatant.clone(),
+                kind: StatusEffectKindContract::from(tick.kind),
+                amount: tick.amount,
+                remaining_hp: tick.remaining_hp,
+                remaining_turns: tick.remaining_turns,
+            },
+            BattleAction::Ability(round, ability) => BattleLogEventContract::Ability {
+                round: *round,
+                combatant: ability.user.clone(),
+                ability: ability.ability.clone(),
+                kind: AbilityKindContract::from(ability.kind),
+                targets: ability
+                    .targets
+                    .iter()
+                    .map(|t| BattleLogTargetContract {
+                        name: t.name.clone(),
+                        amount: t.amount,
+                        remaining_hp: t.remaining_hp,
+                    })
+                    .collect(),
+                movement: ability.movement.as_ref().map(BattleLogMovementContract::from),
+            },
+            BattleAction::Heal(round, heal) => BattleLogEventContract::Heal {
+                round: *round,
+                healer: heal.healer.name.clone(),
+                healed: heal.target.name.clone(),
+                amount: heal.amount,
+                remaining_hp: heal.target.hp,
+                item: heal.item.clone(),
+            },
+            BattleAction::Revive(round, combatant, location) => BattleLogEventContract::Revive {
+                round: *round,
+                combatant: combatant.name.clone(),
+                location: PointContract::from(*location),
+                hp: combatant.hp,
+            },
+        }
+    }
+}
+
+impl From<&BattleLogEventContract> for BattleAction {
+    fn from(value: &BattleLogEventContract) -> Self {
+        match value {
+            BattleLogEventContract::Move { round, combatant, movement } => {
+                BattleAction::Move(*round, combatant.clone(), MovementResult::from(movement))
+            }
+            BattleLogEventContract::Attack { round, attacker, attacked, damage, remaining_hp } => BattleAction::Attack(
+                *round,
+                BattleAttackAction {
+                    assailant: logged(attacker, 0),
+                    victim: logged(attacked, *remaining_hp),
+                    damage: *damage,
+                },
+            ),
+            BattleLogEventContract::EffectApplied { round, combatant, effect } => {
+                BattleAction::EffectApplied(*round, combatant.clone(), StatusEffect::from(effect))
+            }
+            BattleLogEventContract::EffectTicked { round, combatant, kind, amount, remaining_hp, remaining_turns } => {
+                BattleAction::EffectTicked(
+                    *round,
+                    combatant.clone(),
+                    StatusEffectTick {
+                        kind: StatusEffectKind::from(*kind),
+                        amount: *amount,
+                        remaining_hp: *remaining_hp,
+                        remaining_turns: *remaining_turns,
+                    },
+                )
+            }
+            BattleLogEventContract::Ability { round, combatant, ability, kind, targets, movement } => {
+                BattleAction::Ability(
+                    *round,
+                    BattleAbilityAction {
+                        user: combatant.clone(),
+                        ability: ability.clone(),
+                        kind: AbilityKind::from(*kind),
+                        targets: targets
+                            .iter()
+                            .map(|t| AbilityTarget {
+                                name: t.name.clone(),
+                                amount: t.amount,
+                                remaining_hp: t.remaining_hp,
+                            })
+                            .collect(),
+                        movement: movement.as_ref().map(MovementResult::from),
+                    },
+                )
+            }
+            BattleLogEventContract::Heal { round, healer, healed, amount, remaining_hp, item } => BattleAction::Heal(
+                *round,
+                BattleHealAction {
+                    healer: logged(healer, 0),
+                    target: logged(healed, *remaining_hp),
+                    amount: *amount,
+                    item: item.clone(),
+                },
+            ),
+            BattleLogEventContract::Revive { round, combatant, location, hp } => {
+                BattleAction::Revive(*round, logged(combatant, *hp), Point::from(location))
+            }
+        }
+    }
+}
+
+impl BattleLogContract {
+    /// The log of the stored battle, replayed from the stored record
+    pub fn from_record(record: &BattleRecord) -> Result<Self, ApiResponse> {
+        let initial = BattleRoundState::try_from(record)?;
+        let rules = BattleRules::from(record);
+
+        Ok(BattleLogContract {
+            header: BattleLogHeaderContract {
+                format: String::from(LOG_FORMAT),
+                version: LOG_VERSION,
+                id: Some(record.get_id()),
+                fought_at: Some(record.fought_at),
+                seed: record.seed,
+                rules: BattleLogRulesContract::from(&rules),
+                map: BattleLogMapContract {
+                    width: record.width,
+                    height: record.height,
+                    topology: TopologyContract::from(rules.topology),
+                },
+                combatants: initial
+                    .combatants
+                    .iter()
+                    .map(|c| {
+                        let location = initial.map.position_for(&c.name).unwrap_or(Point::new(0, 0));
+                        BattleLogCombatantContract::new(c, location)
+                    })
+                    .collect(),
+                rounds: record.rounds,
+                winner: record.winner.clone(),
+            },
+            events: record
+                .actions
+                .iter()
+                .map(|a| BattleLogEventContract::from(&BattleAction::from(a)))
+                .collect(),
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/battle/{id}/log",
+    tag = "battle",
+    params(
+        ("id" = String, Path, description = "The id of the battle, as returned when it was fought"),
+        ("format" = Option<String>, Query, description = "jsonl (the default) or cbor")
+    ),
+    responses(
+        (status = 200, description = "The log of the battle, as JSON Lines the header is followed by one event per line",
+            content(("application/cbor" = BattleLogContract), ("application/x-ndjson" = String))),
+        (status = 400, description = "Unknown format"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "Battle not found")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/<id>/log?<format>")]
+pub async fn get_log(
+    id: &str,
+    format: Option<&str>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> Result<(ContentType, Vec<u8>), ApiResponse> {
+    let format = LogFormat::parse(format)?;
+    let db_access: GenericEntity<'_> = GenericEntity::new::<BattleEntity>(db.inner());
+    let record: BattleRecord = CrudApiScaffold::accessible_record::<BattleEntity, BattleRecord>(&db_access, id, &user)
+        .await
+        .map_err(ApiResponse::from)?;
+
+    let bytes = BattleLogContract::from_record(&record)?
+        .encode(format)
+        .map_err(|_| ApiResponse::empty(Status::InternalServerError))?;
+
+    Ok((format.content_type(), bytes))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/battle/log",
+    tag = "battle",
+    params(
+        ("format" = Option<String>, Query, description = "jsonl (the default) or cbor")
+    ),
+    request_body(content = BattleLogContract, description = "A log as exported, in the given format"),
+    responses(
+        (status = 201, description = "The log was replayed and stored, the state at the end of the battle", body = BattleStateContract),
+        (status = 400, description = "The log can not be read or is of an unsupported version"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 413, description = "The log is larger than the battle-log limit"),
+        (status = 422, description = "The events do not replay, the reason is given")
+    ),
+    security(("api_key" = []))
+)]
+#[post("/log?<format>", data = "<data>")]
+pub async fn import_log(
+    format: Option<&str>,
+    data: Data<'_>,
+    limits: &Limits,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    let format = match LogFormat::parse(format) {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    let bytes = match data.open(limits.get(LOG_LIMIT).unwrap_or(16.mebibytes())).into_bytes().await {
+        Ok(b) if b.is_complete() => b.into_inner(),
+        Ok(_) => return ApiResponse::empty(Status::PayloadTooLarge),
+        Err(_) => return ApiResponse::empty(Status::BadRequest),
+    };
+
+    let log = match BattleLogContract::decode(&bytes, format) {
+        Ok(l) => l,
+        Err(message) => {
+            return ApiResponse {
+                json: message,
+                status: Status::BadRequest,
+                etag: None,
+            }
+        }
+    };
+
+    match store_log(&log, db.inner(), &user).await {
+        Ok(response) => response,
+        Err(response) => response,
+    }
+}
+
+/// Replays the whole log and stores it as a battle of the user
+async fn store_log(log: &BattleLogContract, db: &Storage, user: &AuthenticatedUser) -> Result<ApiResponse, ApiResponse> {
+    let (initial, rules, actions) = log.to_battle()?;
+    let rounds = log.header.rounds;
+
+    let state = Replay::new(initial.clone()).state_at(&actions, rounds)?;
+    if state.actions.len() != actions.len() {
+        return Err(ApiResponse {
+            json: format!("the log has events after round {}", rounds),
+            status: Status::UnprocessableEntity,
+            etag: None,
+        });
+    }
+
+    let result = BattleResult::new(initial, state, rules, log.header.seed).analyze_results();
+    let entity = BattleEntity {
+        fought_at: log.header.fought_at.unwrap_or_else(Utc::now),
+        ..BattleEntity::from(&result)
+    };
+
+    let record: BattleRecord = GenericEntity::new::<BattleEntity>(db)
+        .create_new(entity, user.id.clone())
+        .await
+        .map_err(ApiResponse::from)?;
+
+    Ok(state_response(record.get_id(), rounds, &result.combatants, result.map, Status::Created))
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use super::*;
+    use crate::api::test_client::{api_key, client, create_user, post_json, ADMIN_KEY, BATTLE};
+
+    fn point(x: Coordinate, y: Coordinate) -> PointContract {
+        PointContract::from(Point::new(x, y))
+    }
+
+    fn log() -> BattleLogContract {
+        let combatant = |name: &str, x: Coordinate| BattleLogCombatantContract {
+            name: String::from(name),
+            id: None,
+            avatar: None,
+            team: None,
+            hp: 10,
+            max_hp: 10,
+            dmg: 5,
+            armor: 0,
+            attack_range: 1,
+            speed: 0,
+            effects: vec![],
+            on_hit: vec![],
+            abilities: vec![],
+            consumables: vec![],
+            location: point(x, 0),
+        };
+
+        BattleLogContract {
+            header: BattleLogHeaderContract {
+                format: String::from(LOG_FORMAT),
+                version: LOG_VERSION,
+                id: None,
+                fought_at: None,
+                seed: 7,
+                rules: BattleLogRulesContract::from(&BattleRules::default()),
+                map: BattleLogMapContract {
+                    width: 4,
+                    height: 1,
+                    topology: TopologyContract::Square4,
+                },
+                combatants: vec![combatant("first", 0), combatant("second", 3)],
+                rounds: 1,
+                winner: None,
+            },
+            events: vec![
+                BattleLogEventContract::Move {
+                    round: 1,
+                    combatant: String::from("first"),
+                    movement: BattleLogMovementContract {
+                        start: point(0, 0),
+                        goal: point(3, 0),
+                        path: vec![point(1, 0), point(2, 0)],
+                    },
+                },
+                BattleLogEventContract::Attack {
+                    round: 1,
+                    attacker: String::from("first"),
+                    attacked: String::from("second"),
+                    damage: 5,
+                    remaining_hp: 5,
+                },
+            ],
+        }
+    }
+
+    fn replayed(log: &BattleLogContract) -> BattleRoundState {
+        let (initial, _, actions) = log.to_battle().ok().unwrap();
+
+        Replay::new(initial).state_at(&actions, log.header.rounds).unwrap()
+    }
+
+    #[test]
+    fn json_lines_have_the_header_first() {
+        let bytes = log().encode(LogFormat::JsonLines).unwrap();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(3, lines.len());
+        assert!(lines[0].contains(r#""format":"ribs-battle-log""#));
+        assert!(lines[1].starts_with(r#"{"type":"move""#));
+
+        let decoded = BattleLogContract::decode(&bytes, LogFormat::JsonLines).unwrap();
+        assert_eq!(Some(5), replayed(&decoded).combatants.iter().find(|c| c.name == "second").map(|c| c.hp));
+    }
+
+    #[test]
+    fn cbor_round_trip() {
+        let bytes = log().encode(LogFormat::Cbor).unwrap();
+        let decoded = BattleLogContract::decode(&bytes, LogFormat::Cbor).unwrap();
+
+        assert_eq!(7, decoded.header.seed);
+        assert_eq!(2, decoded.events.len());
+        assert_eq!(Some(5), replayed(&decoded).combatants.iter().find(|c| c.name == "second").map(|c| c.hp));
+    }
+
+    #[test]
+    fn reject_other_versions() {
+        let mut log = log();
+        log.header.version = LOG_VERSION + 1;
+
+        assert_eq!(Status::BadRequest, log.to_battle().err().unwrap().status);
+    }
+
+    #[test]
+    fn report_the_broken_line() {
+        let mut bytes = log().encode(LogFormat::JsonLines).unwrap();
+        bytes.extend_from_slice(b"{\"type\": \"teleport\"}\n");
+
+        let error = BattleLogContract::decode(&bytes, LogFormat::JsonLines).err().unwrap();
+        assert!(error.starts_with("line 4"));
+    }
+
+    #[test]
+    fn import_exported_battle_log() {
+        let client = client();
+        let user_key = create_user(&client);
+        let result: serde_json::Value = post_json(&client, "/battle", &user_key, BATTLE).into_json().unwrap();
+        let id = result["id"].as_str().unwrap();
+
+        for format in ["jsonl", "cbor"] {
+            let log = client
+                .get(format!("/battle/{}/log?format={}", id, format))
+                .header(api_key(&user_key))
+                .dispatch()
+                .into_bytes()
+                .unwrap();
+            let imported = client
+                .post(format!("/battle/log?format={}", format))
+                .header(api_key(&user_key))
+                .body(log)
+                .dispatch();
+            assert_eq!(Status::Created, imported.status());
+
+            let state: serde_json::Value = imported.into_json().unwrap();
+            assert_ne!(result["id"], state["id"]);
+            assert_eq!(result["round_number"], state["round"]);
+            assert_eq!(result["combatants"], state["combatants"]);
+        }
+
+        let unknown = client
+            .get(format!("/battle/{}/log?format=xml", id))
+            .header(api_key(&user_key))
+            .dispatch();
+        let garbage = client
+            .post("/battle/log")
+            .header(api_key(&user_key))
+            .body("not a log")
+            .dispatch();
+        assert_eq!(Status::BadRequest, unknown.status());
+        assert_eq!(Status::BadRequest, garbage.status());
+    }
+
+    #[test]
+    fn import_log_with_invalid_locations() {
+        let client = client();
+        let result: serde_json::Value = post_json(&client, "/battle", ADMIN_KEY, BATTLE).into_json().unwrap();
+        let log = client
+            .get(format!("/battle/{}/log", result["id"].as_str().unwrap()))
+            .header(api_key(ADMIN_KEY))
+            .dispatch()
+            .into_string()
+            .unwrap();
+        let (header, events) = log.split_once('\n').unwrap();
+        let tampered = |change: &dyn Fn(&mut serde_json::Value)| {
+            let mut header: serde_json::Value = serde_json::from_str(header).unwrap();
+            change(&mut header);
+            client
+                .post("/battle/log")
+                .header(api_key(ADMIN_KEY))
+                .body(format!("{}\n{}", header, events))
+                .dispatch()
+                .status()
+        };
+
+        let outside = tampered(&|header| header["combatants"][0]["location"]["x"] = serde_json::json!(8));
+        let twice = tampered(&|header| header["combatants"][1]["name"] = header["combatants"][0]["name"].clone());
+
+        assert_eq!(Status::BadRequest, outside);
+        assert_eq!(Status::BadRequest, twice);
+    }
+}
@@ -1,18 +1,30 @@
-use rocket::{serde::json::Json, State};
+use rocket::{
+    data::{Data, Limits},
+    http::ContentType,
+    serde::json::Json,
+    State,
+};
 use serde::{Deserialize, Serialize};
-use surrealdb::{engine::remote::ws::Client, Surreal};
+use utoipa::ToSchema;
 
 use crate::{
     api::ApiResponse,
-    storage::{battlefields::*, Record, combatants::CombatantEntity},
+    storage::{battlefields::*, Record, Storage, combatants::CombatantEntity},
+    types::point::Coordinate,
 };
 
-use super::{CrudApiScaffold, combatant::CombatantContract};
+use super::{
+    auth::AuthenticatedUser,
+    bulk::{self, from_cell, to_cell, BulkContract},
+    etag::IfMatch,
+    CrudApiScaffold,
+    combatant::CombatantContract,
+};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct BattleFieldContract {
-    pub height: u8,
-    pub width: u8,
+    pub height: Coordinate,
+    pub width: Coordinate,
     pub id: Option<String>,
     pub combatants: Vec<CombatantContract>
 }
@@ -38,53 +50,249 @@ impl From<&BattleFieldRecord> for BattleFieldContract {
     }
 }
 
+/// A battlefield as a line of CSV, the combatants as JSON
+#[derive(Serialize, Deserialize)]
+pub struct BattleFieldRowContract {
+    pub id: Option<String>,
+    pub height: Coordinate,
+    pub width: Coordinate,
+    pub combatants: String,
+}
+
+/// Battlefields have no name, they are matched by their id
+impl BulkContract for BattleFieldContract {
+    type Entity = BattleFieldEntity;
+    type Record = BattleFieldRecord;
+    type Row = BattleFieldRowContract;
+
+    const KEY: &'static str = "id";
+
+    fn from_record(record: &BattleFieldRecord) -> Self {
+        BattleFieldContract::from(record)
+    }
+
+    fn to_entity(&self, _stored: Option<&BattleFieldRecord>) -> BattleFieldEntity {
+        BattleFieldEntity {
+            height: self.height,
+            width: self.width,
+            combatants: self.combatants.iter().map(|c| CombatantEntity::from(Json(c.clone()))).collect()
+        }
+    }
+
+    fn key(&self) -> Option<String> {
+        self.id.clone()
+    }
+
+    fn record_key(record: &BattleFieldRecord) -> String {
+        record.get_id()
+    }
+
+    fn to_row(&self) -> Result<BattleFieldRowContract, String> {
+        Ok(BattleFieldRowContract {
+            id: self.id.clone(),
+            height: self.height,
+            width: self.width,
+            combatants: to_cell(&self.combatants)?,
+        })
+    }
+
+    fn from_row(row: BattleFieldRowContract) -> Result<Self, String> {
+        Ok(BattleFieldContract {
+            height: row.height,
+            width: row.width,
+            id: row.id,
+            combatants: from_cell(&row.combatants).map_err(|e| format!("combatants: {}", e))?,
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/battlefields",
+    tag = "battlefields",
+    responses(
+        (status = 200, description = "All battlefields the user can access", body = [BattleFieldContract]),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
 #[get("/")]
-pub async fn get_all(db: &State<Surreal<Client>>) -> Json<Vec<BattleFieldContract>> {
+pub async fn get_all(db: &State<Storage>, user: AuthenticatedUser) -> Json<Vec<BattleFieldContract>> {
     let all_battlefields: Vec<BattleFieldRecord> =
-        CrudApiScaffold::get_all::<BattleFieldEntity, BattleFieldRecord>(db).await;
+        CrudApiScaffold::get_all::<BattleFieldEntity, BattleFieldRecord>(db, &user).await;
 
     return Json(Vec::from_iter(all_battlefields.iter().map(
         |record: &BattleFieldRecord| BattleFieldContract::from(record),
     )));
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/battlefields",
+    tag = "battlefields",
+    request_body = BattleFieldContract,
+    responses(
+        (status = 200, description = "The created battlefield, its version in the ETag header", body = BattleFieldContract),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
 #[post("/", format = "json", data = "<post_data>")]
 pub async fn create_new(
     post_data: Json<BattleFieldContract>,
-    db: &State<Surreal<Client>>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
 ) -> ApiResponse {
     let entity: BattleFieldEntity = BattleFieldEntity::from(post_data);
-    CrudApiScaffold::create_new(db, entity, |record: BattleFieldRecord| {
+    CrudApiScaffold::create_new(db, entity, &user, |record: BattleFieldRecord| {
         BattleFieldContract::from(&record)
     })
     .await
 }
 
+#[utoipa::path(
+    put,
+    path = "/v1/battlefields/{id}",
+    tag = "battlefields",
+    params(
+        ("id" = String, Path, description = "The record id"),
+        ("If-Match" = Option<String>, Header, description = "The expected version, as returned in the ETag header")
+    ),
+    request_body = BattleFieldContract,
+    responses(
+        (status = 200, description = "The updated battlefield, its version in the ETag header", body = BattleFieldContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No battlefield with this id the user can access"),
+        (status = 412, description = "The battlefield was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
 #[put("/<id>", format = "json", data = "<post_data>")]
 pub async fn update(
     id: &str,
     post_data: Json<BattleFieldContract>,
-    db: &State<Surreal<Client>>,
+    db: &State<Storage>,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
 ) -> ApiResponse {
     let entity: BattleFieldEntity = BattleFieldEntity::from(post_data);
-    CrudApiScaffold::update(db, id, entity, |record: BattleFieldRecord| {
+    CrudApiScaffold::update(db, id, entity, if_match, &user, |record: BattleFieldRecord| {
         BattleFieldContract::from(&record)
     })
     .await
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/battlefields/{id}",
+    tag = "battlefields",
+    params(("id" = String, Path, description = "The record id")),
+    responses(
+        (status = 200, description = "The battlefield, its version in the ETag header", body = BattleFieldContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No battlefield with this id the user can access")
+    ),
+    security(("api_key" = []))
+)]
 #[get("/<id>")]
-pub async fn get_by_id(id: &str, db: &State<Surreal<Client>>) -> ApiResponse {
-    CrudApiScaffold::get_by_id(db, id, |record: BattleFieldRecord| {
+pub async fn get_by_id(id: &str, db: &State<Storage>, user: AuthenticatedUser) -> ApiResponse {
+    CrudApiScaffold::get_by_id(db, id, &user, |record: BattleFieldRecord| {
         BattleFieldContract::from(&record)
     })
     .await
 }
 
+#[utoipa::path(
+    delete,
+    path = "/v1/battlefields/{id}",
+    tag = "battlefields",
+    params(
+        ("id" = String, Path, description = "The record id"),
+        ("If-Match" = Option<String>, Header, description = "The expected version, as returned in the ETag header")
+    ),
+    responses(
+        (status = 200, description = "The deleted battlefield", body = BattleFieldContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No battlefield with this id the user can access"),
+        (status = 412, description = "The battlefield was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
 #[delete("/<id>")]
-pub async fn delete(id: &str, db: &State<Surreal<Client>>) -> ApiResponse {
-    CrudApiScaffold::delete(db, id, |record: BattleFieldRecord| {
+pub async fn delete(
+    id: &str,
+    db: &State<Storage>,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    CrudApiScaffold::delete(db, id, if_match, &user, |record: BattleFieldRecord| {
         BattleFieldContract::from(&record)
     })
     .await
 }
+
+#[utoipa::path(
+    get,
+    path = "/v1/battlefields/export",
+    tag = "battlefields",
+    params(("format" = Option<String>, Query, description = "json (the default), ndjson or csv")),
+    responses(
+        (status = 200, description = "All battlefields the user can access", content(
+            ("application/json" = [BattleFieldContract]),
+            ("application/x-ndjson" = String),
+            ("text/csv" = String)
+        )),
+        (status = 400, description = "Unknown format"),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/export?<format>")]
+pub async fn export(
+    format: Option<&str>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> Result<(ContentType, Vec<u8>), ApiResponse> {
+    bulk::export::<BattleFieldContract>(format, db, &user).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/battlefields/import",
+    tag = "battlefields",
+    params(
+        ("format" = Option<String>, Query, description = "json (the default), ndjson or csv"),
+        ("upsert" = Option<bool>, Query, description = "Replace the battlefields of the user with the same id instead of adding new ones"),
+        ("atomic" = Option<bool>, Query, description = "Store either all battlefields or none, if any row fails")
+    ),
+    request_body(content = [BattleFieldContract], description = "The battlefields in the given format, as exported"),
+    responses(
+        (status = 200, description = "What happened to every row", body = ImportReportContract),
+        (status = 400, description = "Unknown format or the import can not be read at all"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 413, description = "The import is larger than the import limit"),
+        (status = 422, description = "A row of an atomic import failed, nothing was stored", body = ImportReportContract)
+    ),
+    security(("api_key" = []))
+)]
+#[post("/import?<format>&<upsert>&<atomic>", data = "<data>")]
+pub async fn import(
+    format: Option<&str>,
+    upsert: Option<bool>,
+    atomic: Option<bool>,
+    data: Data<'_>,
+    limits: &Limits,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    bulk::import::<BattleFieldContract>(
+        format,
+        upsert.unwrap_or(false),
+        atomic.unwrap_or(false),
+        data,
+        limits,
+        db,
+        &user,
+    )
+    .await
+}
@@ -0,0 +1,481 @@
+use std::{collections::HashMap, str::FromStr};
+
+use rocket::{
+    data::{Data, Limits, ToByteUnit},
+    http::{ContentType, Status},
+    State,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::storage::{err::Error as StorageError, BatchWrite, Entity, GenericEntity, Record, Storage};
+
+use super::{auth::AuthenticatedUser, ApiResponse, CrudApiScaffold};
+
+/// The name of the Rocket limit for imports
+const IMPORT_LIMIT: &str = "import";
+
+/// The decoded rows of an import by their row number
+type Rows<C> = Vec<(u64, Result<C, String>)>;
+
+/// What every row of an import writes
+type Writes<E> = Vec<(u64, Result<BatchWrite<E>, String>)>;
+
+/// The encodings records are exported and imported in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BulkFormat {
+    /// A single array of contracts
+    Json,
+    /// One contract per line
+    NdJson,
+    /// A header line and one line per record, nested values are JSON in their cell
+    Csv,
+}
+
+impl FromStr for BulkFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(BulkFormat::Json),
+            "ndjson" => Ok(BulkFormat::NdJson),
+            "csv" => Ok(BulkFormat::Csv),
+            other => Err(format!("unknown format {}, use json, ndjson or csv", other)),
+        }
+    }
+}
+
+impl BulkFormat {
+    fn parse(value: Option<&str>) -> Result<Self, ApiResponse> {
+        value.map_or(Ok(BulkFormat::Json), BulkFormat::from_str).map_err(|json| ApiResponse {
+            json,
+            status: Status::BadRequest,
+            etag: None,
+        })
+    }
+
+    fn content_type(&self) -> ContentType {
+        match self {
+            BulkFormat::Json => ContentType::JSON,
+            BulkFormat::NdJson => ContentType::new("application", "x-ndjson"),
+            BulkFormat::Csv => ContentType::CSV,
+        }
+    }
+}
+
+/// A contract that is exported and imported in bulk
+pub trait BulkContract: Serialize + DeserializeOwned {
+    type Entity: Entity;
+    type Record: Record<Self::Entity>;
+    /// The flat shape of a CSV line
+    type Row: Serialize + DeserializeOwned;
+
+    /// The field an import is matched with the stored records by when upserting
+    const KEY: &'static str;
+
+    fn from_record(record: &Self::Record) -> Self;
+
+    /// The entity replacing the stored record, or the new entity if nothing is stored yet
+    fn to_entity(&self, stored: Option<&Self::Record>) -> Self::Entity;
+
+    /// The value of the key field, contracts without one are always created
+    fn key(&self) -> Option<String>;
+
+    fn record_key(record: &Self::Record) -> String;
+
+    fn to_row(&self) -> Result<Self::Row, String>;
+
+    fn from_row(row: Self::Row) -> Result<Self, String>;
+}
+
+/// Reads a cell holding nested values as JSON, an empty cell is the default
+pub fn from_cell<T: DeserializeOwned + Default>(cell: &str) -> Result<T, String> {
+    match cell.trim().is_empty() {
+        true => Ok(T::default()),
+        false => serde_json::from_str(cell).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn to_cell<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| e.to_string())
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportRowStatusContract {
+    Created,
+    Updated,
+    Failed,
+    /// The row is fine, but nothing was written because another row failed in an atomic import
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ImportRowContract {
+    /// The position in a JSON array, the line in NDJSON and CSV, starting at 1
+    pub row: u64,
+    pub status: ImportRowStatusContract,
+    /// The id of the written record
+    pub id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// What happened to every row of an import
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ImportReportContract {
+    pub created: u32,
+    pub updated: u32,
+    pub failed: u32,
+    pub rows: Vec<ImportRowContract>,
+}
+
+impl ImportReportContract {
+    fn new(rows: Vec<ImportRowContract>) -> Self {
+        let count = |status: ImportRowStatusContract| rows.iter().filter(|r| r.status == status).count() as u32;
+
+        ImportReportContract {
+            created: count(ImportRowStatusContract::Created),
+            updated: count(ImportRowStatusContract::Updated),
+            failed: count(ImportRowStatusContract::Failed),
+            rows,
+        }
+    }
+
+    fn response(self, status: Status) -> ApiResponse {
+        ApiResponse {
+            json: serde_json::to_string(&self).unwrap(),
+            status,
+            etag: None,
+        }
+    }
+}
+
+/// Reads every row on its own, so that a broken row does not hide the others
+pub fn decode<C: BulkContract>(bytes: &[u8], format: BulkFormat) -> Result<Rows<C>, String> {
+    match format {
+        BulkFormat::Json => {
+            let values: Vec<serde_json::Value> = serde_json::from_slice(bytes).map_err(|e| e.to_string())?;
+
+            Ok(values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| (i as u64 + 1, serde_json::from_value(value).map_err(|e| e.to_string())))
+                .collect())
+        }
+        BulkFormat::NdJson => {
+            let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+
+            Ok(text
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| (i as u64 + 1, serde_json::from_str(line).map_err(|e| e.to_string())))
+                .collect())
+        }
+        BulkFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(bytes);
+            reader.headers().map_err(|e| e.to_string())?;
+
+            Ok(reader
+                .deserialize::<C::Row>()
+                .enumerate()
+                .map(|(i, row)| match row {
+                    Ok(row) => (i as u64 + 2, C::from_row(row)),
+                    Err(e) => (
+                        e.position().map_or(i as u64 + 2, |p| p.line()),
+                        Err(e.to_string()),
+                    ),
+                })
+                .collect())
+        }
+    }
+}
+
+pub fn encode<C: BulkContract>(contracts: &[C], format: BulkFormat) -> Result<Vec<u8>, String> {
+    match format {
+        BulkFormat::Json => serde_json::to_vec(contracts).map_err(|e| e.to_string()),
+        BulkFormat::NdJson => {
+            let mut lines: Vec<String> = vec![];
+            for contract in contracts.iter() {
+                lines.push(serde_json::to_string(contract).map_err(|e| e.to_string())? + "\n");
+            }
+
+            Ok(lines.concat().into_bytes())
+        }
+        BulkFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            for contract in contracts.iter() {
+                writer.serialize(contract.to_row()?).map_err(|e| e.to_string())?;
+            }
+
+            writer.into_inner().map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// All records of the user in the format
+pub async fn export<C: BulkContract>(
+    format: Option<&str>,
+    db: &State<Storage>,
+    user: &AuthenticatedUser,
+) -> Result<(ContentType, Vec<u8>), ApiResponse> {
+    let format = BulkFormat::parse(format)?;
+    let records: Vec<C::Record> = CrudApiScaffold::get_all::<C::Entity, C::Record>(db, user).await;
+    let contracts: Vec<C> = records.iter().map(C::from_record).collect();
+
+    let bytes = encode(&contracts, format).map_err(|_| ApiResponse::empty(Status::InternalServerError))?;
+
+    Ok((format.content_type(), bytes))
+}
+
+/// Stores every row as a record of the user
+///
+/// When upserting, a row replaces the record of the user with the same key, rows with a key no record has are created.
+/// Rows are written one by one and failing rows are reported, unless the import is atomic:
+/// then either all rows are written in a single transaction or none, if any row fails.
+pub async fn import<C: BulkContract>(
+    format: Option<&str>,
+    upsert: bool,
+    atomic: bool,
+    data: Data<'_>,
+    limits: &Limits,
+    db: &State<Storage>,
+    user: &AuthenticatedUser,
+) -> ApiResponse {
+    let format = match BulkFormat::parse(format) {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    let bytes = match data.open(limits.get(IMPORT_LIMIT).unwrap_or(8.mebibytes())).into_bytes().await {
+        Ok(b) if b.is_complete() => b.into_inner(),
+        Ok(_) => return ApiResponse::empty(Status::PayloadTooLarge),
+        Err(_) => return ApiResponse::empty(Status::BadRequest),
+    };
+
+    let rows = match decode::<C>(&bytes, format) {
+        Ok(r) => r,
+        Err(message) => {
+            return ApiResponse {
+                json: message,
+                status: Status::BadRequest,
+                etag: None,
+            }
+        }
+    };
+
+    let stored: Vec<C::Record> = match upsert {
+        true => CrudApiScaffold::get_all::<C::Entity, C::Record>(db, user).await,
+        false => vec![],
+    };
+    let writes = plan::<C>(rows, upsert, &stored, user);
+
+    match atomic {
+        true => write_atomic::<C>(writes, db).await,
+        false => write_rows::<C>(writes, db).await,
+    }
+}
+
+/// Decides for every row if it creates or replaces a record
+fn plan<C: BulkContract>(
+    rows: Rows<C>,
+    upsert: bool,
+    stored: &[C::Record],
+    user: &AuthenticatedUser,
+) -> Writes<C::Entity> {
+    // Admins can access the records of all users, but only their own ones are replaced
+    let mut by_key: HashMap<String, Vec<&C::Record>> = HashMap::new();
+    for record in stored.iter().filter(|record| record.get_owner() == user.id) {
+        by_key.entry(C::record_key(record)).or_default().push(record);
+    }
+    let mut imported: HashMap<String, u64> = HashMap::new();
+
+    rows.into_iter()
+        .map(|(row, contract)| {
+            let write = contract.and_then(|contract| {
+                let create = |contract: &C| BatchWrite::Create {
+                    entity: contract.to_entity(None),
+                    owner: user.id.clone(),
+                };
+                let key = match contract.key() {
+                    Some(key) if upsert => key,
+                    _ => return Ok(create(&contract)),
+                };
+
+                if let Some(first) = imported.insert(key.clone(), row) {
+                    return Err(format!("row {} has the same {} {}", first, C::KEY, key));
+                }
+
+                match by_key.get(&key).map(|records| records.as_slice()) {
+                    Some([record]) => Ok(BatchWrite::Update {
+                        id: record.get_id(),
+                        entity: contract.to_entity(Some(record)),
+                        expected_version: Some(record.get_version()),
+                    }),
+                    Some(records) => Err(format!("{} stored records have the {} {}", records.len(), C::KEY, key)),
+                    None => Ok(create(&contract)),
+                }
+            });
+
+            (row, write)
+        })
+        .collect()
+}
+
+async fn write_rows<C: BulkContract>(
+    writes: Writes<C::Entity>,
+    db: &Storage,
+) -> ApiResponse {
+    let db_access: GenericEntity<'_> = GenericEntity::new::<C::Entity>(db);
+    let mut rows: Vec<ImportRowContract> = vec![];
+
+    for (row, write) in writes.into_iter() {
+        let (status, written): (ImportRowStatusContract, Result<C::Record, String>) = match write {
+            Ok(BatchWrite::Create { entity, owner }) => (
+                ImportRowStatusContract::Created,
+                db_access.create_new(entity, owner).await.map_err(|e| e.to_string()),
+            ),
+            Ok(BatchWrite::Update {
+                id,
+                entity,
+                expected_version,
+            }) => (
+                ImportRowStatusContract::Updated,
+                db_access.update(&id, entity, expected_version).await.map_err(|e| e.to_string()),
+            ),
+            Err(e) => (ImportRowStatusContract::Failed, Err(e)),
+        };
+
+        rows.push(match written {
+            Ok(record) => ImportRowContract {
+                row,
+                status,
+                id: Some(record.get_id()),
+                error: None,
+            },
+            Err(e) => ImportRowContract {
+                row,
+                status: ImportRowStatusContract::Failed,
+                id: None,
+                error: Some(e),
+            },
+        });
+    }
+
+    ImportReportContract::new(rows).response(Status::Ok)
+}
+
+async fn write_atomic<C: BulkContract>(
+    writes: Writes<C::Entity>,
+    db: &Storage,
+) -> ApiResponse {
+    if writes.iter().any(|(_, write)| write.is_err()) {
+        let rows = writes
+            .into_iter()
+            .map(|(row, write)| match write {
+                Ok(_) => ImportRowContract {
+                    row,
+                    status: ImportRowStatusContract::Skipped,
+                    id: None,
+                    error: None,
+                },
+                Err(e) => ImportRowContract {
+                    row,
+                    status: ImportRowStatusContract::Failed,
+                    id: None,
+                    error: Some(e),
+                },
+            })
+            .collect();
+
+        return ImportReportContract::new(rows).response(Status::UnprocessableEntity);
+    }
+
+    let (numbers, writes): (Vec<u64>, Vec<BatchWrite<C::Entity>>) =
+        writes.into_iter().map(|(row, write)| (row, write.unwrap())).unzip();
+    let statuses: Vec<ImportRowStatusContract> = writes
+        .iter()
+        .map(|write| match write {
+            BatchWrite::Create { .. } => ImportRowStatusContract::Created,
+            BatchWrite::Update { .. } => ImportRowStatusContract::Updated,
+        })
+        .collect();
+
+    let written: Result<Vec<C::Record>, StorageError> =
+        GenericEntity::new::<C::Entity>(db).write_all(writes).await;
+
+    match written {
+        Ok(records) => ImportReportContract::new(
+            numbers
+                .into_iter()
+                .zip(statuses)
+                .zip(records.iter())
+                .map(|((row, status), record)| ImportRowContract {
+                    row,
+                    status,
+                    id: Some(record.get_id()),
+                    error: None,
+                })
+                .collect(),
+        )
+        .response(Status::Ok),
+        Err(e) => ApiResponse::from(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::combatant::CombatantContract;
+
+    fn combatant(name: &str) -> CombatantContract {
+        serde_json::from_str(&format!(r#"{{"name": "{}", "hp": 10, "dmg": 2, "id": null, "avatar": null}}"#, name)).unwrap()
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut first = combatant("first");
+        first.team = Some(String::from("red, and blue"));
+        first.equipment = vec![String::from("sword")];
+        let contracts = vec![first, combatant("second")];
+
+        let bytes = encode(&contracts, BulkFormat::Csv).unwrap();
+        let rows = decode::<CombatantContract>(&bytes, BulkFormat::Csv).unwrap();
+
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("name,id,avatar,hp,max_hp,dmg,team,on_hit,abilities,equipment\n"));
+        assert_eq!(vec![2, 3], rows.iter().map(|(row, _)| *row).collect::<Vec<u64>>());
+        let decoded: Vec<CombatantContract> = rows.into_iter().map(|(_, c)| c.unwrap()).collect();
+        assert_eq!(Some(String::from("red, and blue")), decoded[0].team);
+        assert_eq!(vec![String::from("sword")], decoded[0].equipment);
+        assert_eq!("second", decoded[1].name);
+    }
+
+    #[test]
+    fn broken_rows_are_reported_by_number() {
+        let ndjson = "{\"name\": \"first\", \"hp\": 10, \"dmg\": 2, \"id\": null, \"avatar\": null}\n\n{\"name\": \"second\"}\n";
+        let csv = "name,id,avatar,hp,max_hp,dmg,team,on_hit,abilities,equipment\n\
+                   first,,,10,,2,,,,\n\
+                   second,,,lots,,2,,,,\n\
+                   third,,,10,,2,,[{,,\n";
+
+        let ndjson_rows = decode::<CombatantContract>(ndjson.as_bytes(), BulkFormat::NdJson).unwrap();
+        let csv_rows = decode::<CombatantContract>(csv.as_bytes(), BulkFormat::Csv).unwrap();
+
+        assert_eq!(2, ndjson_rows.len());
+        assert!(ndjson_rows[0].1.is_ok());
+        assert_eq!(3, ndjson_rows[1].0);
+        assert!(ndjson_rows[1].1.is_err());
+        assert_eq!(3, csv_rows.len());
+        assert!(csv_rows[0].1.is_ok());
+        assert_eq!(3, csv_rows[1].0);
+        assert!(csv_rows[1].1.is_err());
+        assert!(csv_rows[2].1.as_ref().err().unwrap().starts_with("on_hit: "));
+    }
+
+    #[test]
+    fn json_must_be_an_array() {
+        assert!(decode::<CombatantContract>(b"{}", BulkFormat::Json).is_err());
+        assert!("xml".parse::<BulkFormat>().is_err());
+    }
+}
@@ -1,17 +1,92 @@
-use crate::storage::{combatants::*, Record};
-use rocket::{serde::json::Json, State};
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::engine::{
+    progression::{BattleOutcome, ProgressionRules},
+    rating::{rating_changes, INITIAL_RATING},
+};
+use crate::storage::{
+    battle_results::{BattleResultEntity, BattleResultRecord, ParticipantEntity},
+    combatants::*,
+    err::Error as StorageError,
+    items::{ItemEntity, ItemKindEntity, ItemRecord},
+    BatchWrite, GenericEntity, Record, Storage,
+};
+use rocket::{
+    data::{Data, Limits},
+    http::ContentType,
+    serde::json::Json,
+    State,
+};
 use serde::{Deserialize, Serialize};
-use surrealdb::{engine::remote::ws::Client, Surreal};
+use utoipa::ToSchema;
 
-use super::{ApiResponse, CrudApiScaffold};
+use super::{
+    ability::AbilityContract,
+    auth::AuthenticatedUser,
+    bulk::{self, from_cell, to_cell, BulkContract},
+    etag::IfMatch,
+    status_effect::StatusEffectContract,
+    ApiResponse, CrudApiScaffold,
+};
 
-#[derive(Deserialize, Serialize, Clone)]
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
 pub struct CombatantContract {
     pub name: String,
     pub id: Option<String>,
     pub avatar: Option<String>,
     pub hp: u16,
+    /// The hit points healing stops at, the starting hit points if not set
+    #[serde(default)]
+    pub max_hp: Option<u16>,
     pub dmg: u16,
+    /// The effects currently active, only used in battles
+    #[serde(default)]
+    pub effects: Vec<StatusEffectContract>,
+    /// The effects applied to every opponent the combatant hits
+    #[serde(default)]
+    pub on_hit: Vec<StatusEffectContract>,
+    /// Used in the order they are declared, whenever they are ready and their conditions are met
+    #[serde(default)]
+    pub abilities: Vec<AbilityContract>,
+    /// Combatants of the same team are allies
+    #[serde(default)]
+    pub team: Option<String>,
+    /// Ids of the equipped items, changed through the equipment endpoints
+    #[serde(default)]
+    pub equipment: Vec<String>,
+    /// Only set for stored combatants, changed by battles only
+    #[serde(default)]
+    pub progression: Option<ProgressionContract>,
+}
+
+/// The experience and career of a stored combatant
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct ProgressionContract {
+    pub experience: u32,
+    pub level: u32,
+    pub battles: u32,
+    pub wins: u32,
+    pub kills: u32,
+    /// The Elo rating, updated by battles against other stored combatants
+    pub rating: i32,
+}
+
+// How often a combatant changed by someone else during the update is read again
+const MAX_PROGRESSION_ATTEMPTS: u32 = 3;
+
+impl From<&CombatantEntity> for ProgressionContract {
+    fn from(value: &CombatantEntity) -> Self {
+        ProgressionContract {
+            experience: value.experience,
+            level: value.level,
+            battles: value.career.battles,
+            wins: value.career.wins,
+            kills: value.career.kills,
+            rating: value.rating.round() as i32,
+        }
+    }
 }
 
 impl From<&CombatantRecord> for CombatantContract {
@@ -21,8 +96,15 @@ impl From<&CombatantRecord> for CombatantContract {
             name: entity.name.clone(),
             id: Some(value.get_id()),
             hp: entity.hit_points,
+            max_hp: entity.max_hit_points,
             dmg: entity.damage_rating,
+            progression: Some(ProgressionContract::from(&entity)),
             avatar: entity.avatar,
+            effects: vec![],
+            on_hit: entity.on_hit.iter().map(StatusEffectContract::from).collect(),
+            abilities: entity.abilities.iter().map(AbilityContract::from).collect(),
+            team: entity.team,
+            equipment: entity.equipment,
         }
     }
 }
@@ -33,8 +115,15 @@ impl From<&CombatantEntity> for CombatantContract {
             name: value.name.clone(),
             id: None,
             hp: value.hit_points,
+            max_hp: value.max_hit_points,
             dmg: value.damage_rating,
             avatar: value.avatar.clone(),
+            effects: vec![],
+            on_hit: value.on_hit.iter().map(StatusEffectContract::from).collect(),
+            abilities: value.abilities.iter().map(AbilityContract::from).collect(),
+            team: value.team.clone(),
+            equipment: value.equipment.to_vec(),
+            progression: None,
         }
     }
 }
@@ -45,15 +134,276 @@ impl From<Json<CombatantContract>> for CombatantEntity {
             name: value.name.clone(),
             damage_rating: value.dmg,
             hit_points: value.hp,
+            max_hit_points: value.max_hp,
             avatar: value.avatar.clone(),
+            on_hit: value.on_hit.iter().map(StatusEffectEntity::from).collect(),
+            abilities: value.abilities.iter().map(AbilityEntity::from).collect(),
+            team: value.team.clone(),
+            equipment: value.equipment.to_vec(),
+            experience: 0,
+            level: 1,
+            career: CareerEntity::default(),
+            rating: INITIAL_RATING,
+        }
+    }
+}
+
+/// A combatant as a line of CSV, the effects, abilities and equipment as JSON
+#[derive(Deserialize, Serialize)]
+pub struct CombatantRowContract {
+    pub name: String,
+    pub id: Option<String>,
+    pub avatar: Option<String>,
+    pub hp: u16,
+    pub max_hp: Option<u16>,
+    pub dmg: u16,
+    pub team: Option<String>,
+    pub on_hit: String,
+    pub abilities: String,
+    pub equipment: String,
+}
+
+/// Combatants are matched by name, the progression of a replaced combatant is kept
+impl BulkContract for CombatantContract {
+    type Entity = CombatantEntity;
+    type Record = CombatantRecord;
+    type Row = CombatantRowContract;
+
+    const KEY: &'static str = "name";
+
+    fn from_record(record: &CombatantRecord) -> Self {
+        CombatantContract::from(record)
+    }
+
+    fn to_entity(&self, stored: Option<&CombatantRecord>) -> CombatantEntity {
+        let entity = CombatantEntity::from(Json(self.clone()));
+
+        match stored {
+            Some(record) => CombatantEntity {
+                experience: record.experience,
+                level: record.level,
+                career: record.career,
+                rating: record.rating,
+                ..entity
+            },
+            None => entity,
+        }
+    }
+
+    fn key(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn record_key(record: &CombatantRecord) -> String {
+        record.name.clone()
+    }
+
+    fn to_row(&self) -> Result<CombatantRowContract, String> {
+        Ok(CombatantRowContract {
+            name: self.name.clone(),
+            id: self.id.clone(),
+            avatar: self.avatar.clone(),
+            hp: self.hp,
+            max_hp: self.max_hp,
+            dmg: self.dmg,
+            team: self.team.clone(),
+            on_hit: to_cell(&self.on_hit)?,
+            abilities: to_cell(&self.abilities)?,
+            equipment: to_cell(&self.equipment)?,
+        })
+    }
+
+    fn from_row(row: CombatantRowContract) -> Result<Self, String> {
+        Ok(CombatantContract {
+            name: row.name,
+            id: row.id,
+            avatar: row.avatar,
+            hp: row.hp,
+            max_hp: row.max_hp,
+            dmg: row.dmg,
+            effects: vec![],
+            on_hit: from_cell(&row.on_hit).map_err(|e| format!("on_hit: {}", e))?,
+            abilities: from_cell(&row.abilities).map_err(|e| format!("abilities: {}", e))?,
+            team: row.team,
+            equipment: from_cell(&row.equipment).map_err(|e| format!("equipment: {}", e))?,
+            progression: None,
+        })
+    }
+}
+
+/// Adds the experience for the outcome of a battle, grows the stats for every level reached
+/// and records the battle in the career
+fn progress(entity: &mut CombatantEntity, outcome: &BattleOutcome, rules: &ProgressionRules) {
+    entity.experience = entity.experience.saturating_add(rules.experience_for(outcome));
+
+    let level = rules.level_for(entity.experience).max(entity.level);
+    let gained = u16::try_from(level - entity.level).unwrap_or(u16::MAX);
+
+    entity.hit_points = entity.hit_points.saturating_add(rules.hp_per_level.saturating_mul(gained));
+    entity.max_hit_points = entity
+        .max_hit_points
+        .map(|max| max.saturating_add(rules.hp_per_level.saturating_mul(gained)));
+    entity.damage_rating = entity.damage_rating.saturating_add(rules.dmg_per_level.saturating_mul(gained));
+    entity.level = level;
+
+    entity.career.battles += 1;
+    entity.career.kills += outcome.kills;
+    if outcome.won {
+        entity.career.wins += 1;
+    }
+}
+
+/// Renames stored combatants that share their name with another combatant to `name #N`, N being their position
+///
+/// The engine tells combatants apart by name, two stored ones may still be named alike.
+pub fn name_apart(contracts: &mut [CombatantContract]) {
+    let names: Vec<String> = contracts.iter().map(|c| c.name.clone()).collect();
+
+    for (position, contract) in contracts.iter_mut().enumerate() {
+        if contract.id.is_some() && names.iter().filter(|name| **name == contract.name).count() > 1 {
+            contract.name = format!("{} #{}", contract.name, position + 1);
+        }
+    }
+}
+
+/// Lets the stored combatants that took part in a battle progress and rates them, given as pairs of id and name
+///
+/// Only combatants that fought with their stored stats may be given, otherwise any rating could be bought with a better body.
+/// All combatants are written in one batch against the versions they were read with, the batch is read and written
+/// again if one of them was changed in the meantime. Combatants the user can not access are left alone.
+/// Battles of at least two stored combatants are kept for the leaderboard, unless the combatants could not be written.
+/// Returns the ids of the combatants the battle could not be recorded for, either all of them or none.
+pub async fn record_battle(
+    db: &Storage,
+    user: &AuthenticatedUser,
+    rules: &ProgressionRules,
+    combatants: &[(String, String)],
+    outcomes: &HashMap<String, BattleOutcome>,
+    battlefield: Option<String>,
+) -> Vec<String> {
+    let db_access: GenericEntity<'_> = GenericEntity::new::<CombatantEntity>(db);
+    let mut participants: Vec<(String, BattleOutcome, f64)> = vec![];
+
+    for (id, name) in combatants.iter() {
+        let outcome = match outcomes.get(name) {
+            Some(o) => *o,
+            None => continue,
+        };
+
+        if let Ok(record) =
+            CrudApiScaffold::accessible_record::<CombatantEntity, CombatantRecord>(&db_access, id, user).await
+        {
+            participants.push((id.clone(), outcome, record.rating));
+        }
+    }
+
+    let changes: Vec<f64> = rating_changes(
+        &participants
+            .iter()
+            .map(|(_, outcome, rating)| (*rating, outcome.place))
+            .collect::<Vec<(f64, u32)>>(),
+    );
+
+    let mut failure: Option<String> = Some(String::from("they were changed too often in the meantime"));
+
+    for _ in 0..MAX_PROGRESSION_ATTEMPTS {
+        let mut writes: Vec<BatchWrite<CombatantEntity>> = vec![];
+        for ((id, outcome, _), change) in participants.iter().zip(changes.iter()) {
+            let record: CombatantRecord = match db_access.get_by_id::<CombatantEntity, CombatantRecord>(id).await {
+                Ok(r) => r,
+                Err(e) => {
+                    failure = Some(e.to_string());
+                    break;
+                }
+            };
+            let mut entity = record.get_entity();
+            progress(&mut entity, outcome, rules);
+            entity.rating += change;
+
+            writes.push(BatchWrite::Update {
+                id: id.clone(),
+                entity,
+                expected_version: Some(record.get_version()),
+            });
+        }
+        if writes.len() < participants.len() {
+            break;
+        }
+
+        let written: Result<Vec<CombatantRecord>, StorageError> = db_access.write_all(writes).await;
+
+        match written {
+            Ok(_) => {
+                failure = None;
+                break;
+            }
+            Err(StorageError::VersionMismatch(_, _)) => continue,
+            Err(e) => {
+                failure = Some(e.to_string());
+                break;
+            }
+        }
+    }
+
+    match failure {
+        Some(reason) => {
+            warn!("The battle could not be recorded for its combatants: {}", reason);
+            participants.into_iter().map(|(id, _, _)| id).collect()
         }
+        None => {
+            if participants.len() > 1 {
+                store_result(db, user, battlefield, &participants, &changes).await;
+            }
+            vec![]
+        }
+    }
+}
+
+async fn store_result(
+    db: &Storage,
+    user: &AuthenticatedUser,
+    battlefield: Option<String>,
+    participants: &[(String, BattleOutcome, f64)],
+    changes: &[f64],
+) {
+    let result = BattleResultEntity {
+        battlefield,
+        fought_at: Utc::now(),
+        participants: participants
+            .iter()
+            .zip(changes.iter())
+            .map(|((id, outcome, rating), change)| ParticipantEntity {
+                combatant: id.clone(),
+                place: outcome.place,
+                rating_before: *rating,
+                rating_after: rating + change,
+            })
+            .collect(),
+    };
+
+    let stored: Result<BattleResultRecord, StorageError> = GenericEntity::new::<BattleResultEntity>(db)
+        .create_new(result, user.id.clone())
+        .await;
+
+    if let Err(e) = stored {
+        warn!("The battle result could not be stored: {}", e);
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/combatants",
+    tag = "combatants",
+    responses(
+        (status = 200, description = "All combatants the user can access", body = [CombatantContract]),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
 #[get("/")]
-pub async fn get_all(db: &State<Surreal<Client>>) -> Json<Vec<CombatantContract>> {
+pub async fn get_all(db: &State<Storage>, user: AuthenticatedUser) -> Json<Vec<CombatantContract>> {
     let all_combatants: Vec<CombatantRecord> =
-        CrudApiScaffold::get_all::<CombatantEntity, CombatantRecord>(db).await;
+        CrudApiScaffold::get_all::<CombatantEntity, CombatantRecord>(db, &user).await;
 
     return Json(Vec::from_iter(
         all_combatants
@@ -62,47 +412,552 @@ pub async fn get_all(db: &State<Surreal<Client>>) -> Json<Vec<CombatantContract>
     ));
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/combatants/{id}",
+    tag = "combatants",
+    params(("id" = String, Path, description = "The record id")),
+    responses(
+        (status = 200, description = "The combatant, its version in the ETag header", body = CombatantContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No combatant with this id the user can access")
+    ),
+    security(("api_key" = []))
+)]
 #[get("/<id>")]
-pub async fn get_by_id(db: &State<Surreal<Client>>, id: &str) -> ApiResponse {
+pub async fn get_by_id(db: &State<Storage>, id: &str, user: AuthenticatedUser) -> ApiResponse {
     return CrudApiScaffold::get_by_id::<CombatantEntity, CombatantRecord, CombatantContract>(
         db,
         id,
+        &user,
         |record: CombatantRecord| CombatantContract::from(&record),
     )
     .await;
 }
 
+#[utoipa::path(
+    delete,
+    path = "/v1/combatants/{id}",
+    tag = "combatants",
+    params(
+        ("id" = String, Path, description = "The record id"),
+        ("If-Match" = Option<String>, Header, description = "The expected version, as returned in the ETag header")
+    ),
+    responses(
+        (status = 200, description = "The deleted combatant", body = CombatantContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No combatant with this id the user can access"),
+        (status = 412, description = "The combatant was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
 #[delete("/<id>")]
-pub async fn delete(db: &State<Surreal<Client>>, id: &str) -> ApiResponse {
+pub async fn delete(
+    db: &State<Storage>,
+    id: &str,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
+) -> ApiResponse {
     return CrudApiScaffold::delete::<CombatantEntity, CombatantRecord, CombatantContract>(
         db,
         id,
+        if_match,
+        &user,
         |record: CombatantRecord| CombatantContract::from(&record),
     )
     .await;
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/combatants",
+    tag = "combatants",
+    request_body = CombatantContract,
+    responses(
+        (status = 200, description = "The created combatant, its version in the ETag header", body = CombatantContract),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
 #[post("/", format = "json", data = "<combatant_post_data>")]
 pub async fn create_new(
     combatant_post_data: Json<CombatantContract>,
-    db: &State<Surreal<Client>>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
 ) -> ApiResponse {
     let entity: CombatantEntity = CombatantEntity::from(combatant_post_data);
-    return CrudApiScaffold::create_new(db, entity, |record: CombatantRecord| {
+    return CrudApiScaffold::create_new(db, entity, &user, |record: CombatantRecord| {
         CombatantContract::from(&record)
     })
     .await;
 }
 
+#[utoipa::path(
+    put,
+    path = "/v1/combatants/{id}",
+    tag = "combatants",
+    params(
+        ("id" = String, Path, description = "The record id"),
+        ("If-Match" = Option<String>, Header, description = "The expected version, as returned in the ETag header")
+    ),
+    request_body = CombatantContract,
+    responses(
+        (status = 200, description = "The updated combatant, its version in the ETag header", body = CombatantContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No combatant with this id the user can access"),
+        (status = 412, description = "The combatant was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
 #[put("/<id>", format = "json", data = "<post_data>")]
 pub async fn update(
     id: &str,
     post_data: Json<CombatantContract>,
-    db: &State<Surreal<Client>>,
+    db: &State<Storage>,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    let mut entity: CombatantEntity = CombatantEntity::from(post_data);
+
+    // The progression is only changed by battles
+    let stored: Result<CombatantRecord, StorageError> = GenericEntity::new::<CombatantEntity>(db.inner())
+        .get_by_id::<CombatantEntity, CombatantRecord>(id)
+        .await;
+    if let Ok(record) = stored {
+        entity.experience = record.experience;
+        entity.level = record.level;
+        entity.career = record.career;
+        entity.rating = record.rating;
+    }
+
+    return CrudApiScaffold::update(db, id, entity, if_match, &user, |record: CombatantRecord| {
+        CombatantContract::from(&record)
+    })
+    .await;
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/combatants/{id}/equipment/{item_id}",
+    tag = "combatants",
+    params(
+        ("id" = String, Path, description = "The record id of the combatant"),
+        ("item_id" = String, Path, description = "The record id of the item"),
+        ("If-Match" = Option<String>, Header, description = "The expected version of the combatant, as returned in the ETag header")
+    ),
+    responses(
+        (status = 200, description = "The combatant, a weapon or armor replaces the one equipped before", body = CombatantContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No combatant or item with this id the user can access"),
+        (status = 412, description = "The combatant was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
+#[put("/<id>/equipment/<item_id>")]
+pub async fn equip(
+    id: &str,
+    item_id: &str,
+    db: &State<Storage>,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    let combatants: GenericEntity<'_> = GenericEntity::new::<CombatantEntity>(db.inner());
+    let items: GenericEntity<'_> = GenericEntity::new::<ItemEntity>(db.inner());
+
+    let mut entity: CombatantEntity = match CrudApiScaffold::accessible_record::<
+        CombatantEntity,
+        CombatantRecord,
+    >(&combatants, id, &user)
+    .await
+    {
+        Ok(record) => record.get_entity(),
+        Err(e) => return ApiResponse::from(e),
+    };
+    let item: ItemRecord =
+        match CrudApiScaffold::accessible_record::<ItemEntity, ItemRecord>(&items, item_id, &user).await {
+            Ok(record) => record,
+            Err(e) => return ApiResponse::from(e),
+        };
+
+    // A combatant carries one weapon and one armor, any number of consumables
+    let mut equipment: Vec<String> = vec![];
+    for equipped in entity.equipment.iter().filter(|e| e.as_str() != item_id) {
+        let same_slot = match items.get_by_id::<ItemEntity, ItemRecord>(equipped).await {
+            Ok(other) => item.kind != ItemKindEntity::Consumable && other.kind == item.kind,
+            Err(_) => false,
+        };
+
+        if !same_slot {
+            equipment.push(equipped.clone());
+        }
+    }
+    equipment.push(item_id.to_owned());
+    entity.equipment = equipment;
+
+    return CrudApiScaffold::update(db, id, entity, if_match, &user, |record: CombatantRecord| {
+        CombatantContract::from(&record)
+    })
+    .await;
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/combatants/{id}/equipment/{item_id}",
+    tag = "combatants",
+    params(
+        ("id" = String, Path, description = "The record id of the combatant"),
+        ("item_id" = String, Path, description = "The record id of the item"),
+        ("If-Match" = Option<String>, Header, description = "The expected version of the combatant, as returned in the ETag header")
+    ),
+    responses(
+        (status = 200, description = "The combatant without the item", body = CombatantContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No combatant with this id the user can access or the item is not equipped"),
+        (status = 412, description = "The combatant was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
+#[delete("/<id>/equipment/<item_id>")]
+pub async fn unequip(
+    id: &str,
+    item_id: &str,
+    db: &State<Storage>,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
 ) -> ApiResponse {
-    let entity: CombatantEntity = CombatantEntity::from(post_data);
-    return CrudApiScaffold::update(db, id, entity, |record: CombatantRecord| {
+    let combatants: GenericEntity<'_> = GenericEntity::new::<CombatantEntity>(db.inner());
+
+    let mut entity: CombatantEntity = match CrudApiScaffold::accessible_record::<
+        CombatantEntity,
+        CombatantRecord,
+    >(&combatants, id, &user)
+    .await
+    {
+        Ok(record) => record.get_entity(),
+        Err(e) => return ApiResponse::from(e),
+    };
+
+    if !entity.equipment.iter().any(|e| e == item_id) {
+        return ApiResponse::from(StorageError::NotFound);
+    }
+    entity.equipment.retain(|e| e != item_id);
+
+    return CrudApiScaffold::update(db, id, entity, if_match, &user, |record: CombatantRecord| {
         CombatantContract::from(&record)
     })
     .await;
 }
+
+#[utoipa::path(
+    get,
+    path = "/v1/combatants/export",
+    tag = "combatants",
+    params(("format" = Option<String>, Query, description = "json (the default), ndjson or csv")),
+    responses(
+        (status = 200, description = "All combatants the user can access", content(
+            ("application/json" = [CombatantContract]),
+            ("application/x-ndjson" = String),
+            ("text/csv" = String)
+        )),
+        (status = 400, description = "Unknown format"),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/export?<format>")]
+pub async fn export(
+    format: Option<&str>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> Result<(ContentType, Vec<u8>), ApiResponse> {
+    bulk::export::<CombatantContract>(format, db, &user).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/combatants/import",
+    tag = "combatants",
+    params(
+        ("format" = Option<String>, Query, description = "json (the default), ndjson or csv"),
+        ("upsert" = Option<bool>, Query, description = "Replace the combatants of the user with the same name instead of adding new ones"),
+        ("atomic" = Option<bool>, Query, description = "Store either all combatants or none, if any row fails")
+    ),
+    request_body(content = [CombatantContract], description = "The combatants in the given format, as exported"),
+    responses(
+        (status = 200, description = "What happened to every row", body = ImportReportContract),
+        (status = 400, description = "Unknown format or the import can not be read at all"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 413, description = "The import is larger than the import limit"),
+        (status = 422, description = "A row of an atomic import failed, nothing was stored", body = ImportReportContract)
+    ),
+    security(("api_key" = []))
+)]
+#[post("/import?<format>&<upsert>&<atomic>", data = "<data>")]
+pub async fn import(
+    format: Option<&str>,
+    upsert: Option<bool>,
+    atomic: Option<bool>,
+    data: Data<'_>,
+    limits: &Limits,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    bulk::import::<CombatantContract>(
+        format,
+        upsert.unwrap_or(false),
+        atomic.unwrap_or(false),
+        data,
+        limits,
+        db,
+        &user,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use super::*;
+    use crate::api::test_client::{api_key, client, create_combatant, create_item, create_user, get_json, ADMIN_KEY, COMBATANT};
+
+    #[test]
+    fn progress_grows_stats_per_level() {
+        let mut entity = CombatantEntity::from(Json(CombatantContract {
+            name: String::from("test"),
+            id: None,
+            avatar: None,
+            hp: 10,
+            max_hp: Some(12),
+            dmg: 2,
+            effects: vec![],
+            on_hit: vec![],
+            abilities: vec![],
+            team: None,
+            equipment: vec![],
+            progression: None,
+        }));
+        let outcome = BattleOutcome { kills: 3, survived: true, won: true, place: 1 };
+
+        progress(&mut entity, &outcome, &ProgressionRules::default());
+
+        assert_eq!(270, entity.experience);
+        assert_eq!(2, entity.level);
+        assert_eq!(15, entity.hit_points);
+        assert_eq!(Some(17), entity.max_hit_points);
+        assert_eq!(3, entity.damage_rating);
+        assert_eq!(CareerEntity { battles: 1, wins: 1, kills: 3 }, entity.career);
+    }
+
+    #[test]
+    fn name_apart_only_stored_combatants() {
+        let contract = |name: &str, id: Option<&str>| CombatantContract {
+            name: String::from(name),
+            id: id.map(String::from),
+            avatar: None,
+            hp: 10,
+            max_hp: None,
+            dmg: 2,
+            effects: vec![],
+            on_hit: vec![],
+            abilities: vec![],
+            team: None,
+            equipment: vec![],
+            progression: None,
+        };
+        let mut contracts = vec![
+            contract("knight", Some("a")),
+            contract("knight", None),
+            contract("knight", Some("b")),
+            contract("archer", Some("c")),
+        ];
+
+        name_apart(&mut contracts);
+
+        let names: Vec<&str> = contracts.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(vec!["knight #1", "knight", "knight #3", "archer"], names);
+    }
+
+    #[test]
+    fn get_created_combatant() {
+        let client = client();
+        let id = create_combatant(&client, ADMIN_KEY);
+
+        let response = client
+            .get(format!("/combatants/{}", id))
+            .header(api_key(ADMIN_KEY))
+            .dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        assert_eq!(Some("\"1\""), response.headers().get_one("ETag"));
+    }
+
+    #[test]
+    fn delete_unknown_combatant() {
+        let client = client();
+
+        let response = client
+            .delete("/combatants/unknown")
+            .header(api_key(ADMIN_KEY))
+            .dispatch();
+
+        assert_eq!(Status::NotFound, response.status());
+    }
+
+    #[test]
+    fn equipped_weapon_replaces_previous_weapon() {
+        let client = client();
+        let id = create_combatant(&client, ADMIN_KEY);
+        let sword = create_item(&client, r#"{"id": null, "name": "sword", "kind": "weapon", "damage": 3}"#);
+        let axe = create_item(&client, r#"{"id": null, "name": "axe", "kind": "weapon", "damage": 5}"#);
+        let potion = create_item(&client, r#"{"id": null, "name": "potion", "kind": "consumable", "heal": 5}"#);
+
+        for item in [&sword, &potion, &axe] {
+            let response = client
+                .put(format!("/combatants/{}/equipment/{}", id, item))
+                .header(api_key(ADMIN_KEY))
+                .dispatch();
+            assert_eq!(Status::Ok, response.status());
+        }
+
+        let combatant: serde_json::Value = get_json(&client, format!("/combatants/{}", id), ADMIN_KEY);
+
+        assert_eq!(serde_json::json!([potion, axe]), combatant["equipment"]);
+    }
+
+    #[test]
+    fn unequip_item_not_equipped() {
+        let client = client();
+        let id = create_combatant(&client, ADMIN_KEY);
+
+        let response = client
+            .delete(format!("/combatants/{}/equipment/unknown", id))
+            .header(api_key(ADMIN_KEY))
+            .dispatch();
+
+        assert_eq!(Status::NotFound, response.status());
+    }
+
+    #[test]
+    fn import_combatants_with_upsert() {
+        let client = client();
+        let user_key = create_user(&client);
+        let id = create_combatant(&client, &user_key);
+        let csv = "name,id,avatar,hp,max_hp,dmg,team,on_hit,abilities,equipment\n\
+                   test,,,20,,4,,,,\n\
+                   other,,,10,,2,red,,,\n\
+                   broken,,,lots,,2,,,,\n";
+
+        let report: serde_json::Value = client
+            .post("/combatants/import?format=csv&upsert=true")
+            .header(api_key(&user_key))
+            .body(csv)
+            .dispatch()
+            .into_json()
+            .unwrap();
+        let updated: serde_json::Value = get_json(&client, format!("/combatants/{}", id), &user_key);
+
+        assert_eq!(1, report["created"]);
+        assert_eq!(1, report["updated"]);
+        assert_eq!(1, report["failed"]);
+        assert_eq!("updated", report["rows"][0]["status"]);
+        assert_eq!(id, report["rows"][0]["id"]);
+        assert_eq!(4, report["rows"][2]["row"]);
+        assert_eq!(20, updated["hp"]);
+
+        let exported = client
+            .get("/combatants/export?format=ndjson")
+            .header(api_key(&user_key))
+            .dispatch();
+        assert_eq!(Some(ContentType::new("application", "x-ndjson")), exported.content_type());
+        assert_eq!(2, exported.into_string().unwrap().lines().count());
+    }
+
+    #[test]
+    fn admin_upsert_keeps_combatants_of_users() {
+        let client = client();
+        let user_key = create_user(&client);
+        let id = create_combatant(&client, &user_key);
+        let csv = "name,id,avatar,hp,max_hp,dmg,team,on_hit,abilities,equipment\n\
+                   test,,,20,,4,,,,\n";
+
+        let report: serde_json::Value = client
+            .post("/combatants/import?format=csv&upsert=true")
+            .header(api_key(ADMIN_KEY))
+            .body(csv)
+            .dispatch()
+            .into_json()
+            .unwrap();
+        let kept: serde_json::Value = get_json(&client, format!("/combatants/{}", id), &user_key);
+
+        assert_eq!(1, report["created"]);
+        assert_eq!(0, report["updated"]);
+        assert_ne!(id, report["rows"][0]["id"]);
+        assert_eq!(10, kept["hp"]);
+    }
+
+    #[test]
+    fn atomic_import_stores_nothing_on_failure() {
+        let client = client();
+        let user_key = create_user(&client);
+        let duplicate = format!("[{}, {}, {}]", COMBATANT, COMBATANT.replace("test", "other"), COMBATANT);
+
+        let failed = client
+            .post("/combatants/import?upsert=true&atomic=true")
+            .header(api_key(&user_key))
+            .body(duplicate)
+            .dispatch();
+        assert_eq!(Status::UnprocessableEntity, failed.status());
+        let report: serde_json::Value = failed.into_json().unwrap();
+        let all: Vec<serde_json::Value> = get_json(&client, "/combatants", &user_key);
+
+        assert_eq!("skipped", report["rows"][0]["status"]);
+        assert_eq!("failed", report["rows"][2]["status"]);
+        assert!(all.is_empty());
+
+        let stored = client
+            .post("/battlefields/import?format=ndjson&atomic=true")
+            .header(api_key(&user_key))
+            .body(format!("{{\"height\": 4, \"width\": 4, \"id\": null, \"combatants\": [{}]}}\n", COMBATANT))
+            .dispatch();
+        assert_eq!(Status::Ok, stored.status());
+        let exported = client
+            .get("/battlefields/export?format=csv")
+            .header(api_key(&user_key))
+            .dispatch()
+            .into_string()
+            .unwrap();
+        assert!(exported.starts_with("id,height,width,combatants\n"));
+        assert_eq!(2, exported.lines().count());
+    }
+
+    #[test]
+    fn combatant_of_other_user_is_hidden() {
+        let client = client();
+        let id = create_combatant(&client, ADMIN_KEY);
+        let user_key = create_user(&client);
+
+        let response = client
+            .get(format!("/combatants/{}", id))
+            .header(api_key(&user_key))
+            .dispatch();
+        let all: Vec<serde_json::Value> = get_json(&client, "/combatants", &user_key);
+
+        assert_eq!(Status::NotFound, response.status());
+        assert!(all.is_empty());
+    }
+
+    #[test]
+    fn admin_sees_combatant_of_user() {
+        let client = client();
+        let user_key = create_user(&client);
+        let id = create_combatant(&client, &user_key);
+
+        let response = client
+            .get(format!("/combatants/{}", id))
+            .header(api_key(ADMIN_KEY))
+            .dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+    }
+}
@@ -0,0 +1,192 @@
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+use super::ApiResponse;
+
+/// Builds the strong entity tag for the given record version
+pub fn etag_for(version: u32) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Parses a strong entity tag back into the record version
+pub fn version_from(etag: &str) -> Option<u32> {
+    etag.trim()
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .and_then(|t| t.parse::<u32>().ok())
+}
+
+/// Determines if the value is a single entity tag, like "3", W/"3" or "abc"
+fn is_entity_tag(value: &str) -> bool {
+    let tag = value.trim();
+    let tag = tag.strip_prefix("W/").unwrap_or(tag);
+
+    tag.len() >= 2
+        && tag.starts_with('"')
+        && tag.ends_with('"')
+        && tag[1..tag.len() - 1].chars().all(|c| c != '"' && !c.is_whitespace() && !c.is_control())
+}
+
+/// Parses a list of entity tags, like "3" or W/"3", "abc", into the versions of its strong tags
+///
+/// If-Match compares strongly, so weak tags never match, just like tags that are no version.
+/// Returns None if the value is not a list of entity tags.
+fn versions_from(value: &str) -> Option<Vec<u32>> {
+    let mut versions: Vec<u32> = vec![];
+    for tag in value.split(',') {
+        if !is_entity_tag(tag) {
+            return None;
+        }
+        if let Some(version) = version_from(tag) {
+            versions.push(version);
+        }
+    }
+
+    Some(versions)
+}
+
+/// The versions a client expects a record to have, taken from the If-Match header
+///
+/// A missing header or a wildcard (*) does not impose any expectation. A header that is not a list of entity tags
+/// is rejected with 400, whether one of the tags matches is decided against the stored record.
+pub struct IfMatch(pub Option<Vec<u32>>);
+
+impl IfMatch {
+    /// The version to write the record against, or the 412 response with the current version if no tag matches
+    pub fn expected_version(&self, current_version: u32) -> Result<Option<u32>, ApiResponse> {
+        match &self.0 {
+            None => Ok(None),
+            Some(versions) if versions.contains(&current_version) => Ok(Some(current_version)),
+            Some(_) => Err(ApiResponse {
+                json: format!("no entity tag matches the stored version {}", current_version),
+                status: Status::PreconditionFailed,
+                etag: Some(etag_for(current_version)),
+            }),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.headers().get_one("If-Match") {
+            None => Outcome::Success(IfMatch(None)),
+            Some(value) if value.trim() == "*" => Outcome::Success(IfMatch(None)),
+            Some(value) => match versions_from(value) {
+                Some(versions) => Outcome::Success(IfMatch(Some(versions))),
+                None => Outcome::Failure((
+                    Status::BadRequest,
+                    format!("{} is not a valid entity tag", value),
+                )),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{ContentType, Header};
+
+    use super::*;
+    use crate::api::test_client::{api_key, client, create_combatant, ADMIN_KEY, COMBATANT};
+
+    #[test]
+    fn etag_round_trip() {
+        assert_eq!(Some(42), version_from(&etag_for(42)));
+    }
+
+    #[test]
+    fn version_from_weak_etag() {
+        assert_eq!(None, version_from("W/\"3\""));
+    }
+
+    #[test]
+    fn version_from_unquoted_etag() {
+        assert_eq!(None, version_from("3"));
+    }
+
+    #[test]
+    fn version_from_garbage() {
+        assert_eq!(None, version_from("\"abc\""));
+    }
+
+    #[test]
+    fn entity_tag_syntax() {
+        assert!(is_entity_tag("\"abc\""));
+        assert!(is_entity_tag(" W/\"1\""));
+        assert!(!is_entity_tag("3"));
+        assert!(!is_entity_tag("\"a b\""));
+        assert!(!is_entity_tag("\""));
+    }
+
+    #[test]
+    fn versions_from_list() {
+        assert_eq!(Some(vec![1, 2]), versions_from("\"1\", \"2\""));
+        assert_eq!(Some(vec![2]), versions_from("W/\"1\", \"2\", \"abc\""));
+        assert_eq!(None, versions_from("\"1\", 2"));
+    }
+
+    #[test]
+    fn expected_version_of_list() {
+        assert_eq!(Some(2), IfMatch(Some(vec![1, 2])).expected_version(2).ok().flatten());
+        assert_eq!(None, IfMatch(None).expected_version(2).ok().flatten());
+
+        let mismatch = IfMatch(Some(vec![1])).expected_version(2).err().unwrap();
+        assert_eq!(Status::PreconditionFailed, mismatch.status);
+        assert_eq!(Some(String::from("\"2\"")), mismatch.etag);
+    }
+
+    #[test]
+    fn update_with_stale_etag() {
+        let client = client();
+        let id = create_combatant(&client, ADMIN_KEY);
+        let update = || {
+            client
+                .put(format!("/combatants/{}", id))
+                .header(ContentType::JSON)
+                .header(api_key(ADMIN_KEY))
+                .header(Header::new("If-Match", "\"1\""))
+                .body(COMBATANT)
+                .dispatch()
+        };
+
+        let first = update();
+        let second = update();
+
+        assert_eq!(Status::Ok, first.status());
+        assert_eq!(Status::PreconditionFailed, second.status());
+        assert_eq!(Some("\"2\""), second.headers().get_one("ETag"));
+    }
+
+    #[test]
+    fn update_with_malformed_etag() {
+        let client = client();
+        let id = create_combatant(&client, ADMIN_KEY);
+        let update = |if_match: &'static str| {
+            client
+                .put(format!("/combatants/{}", id))
+                .header(ContentType::JSON)
+                .header(api_key(ADMIN_KEY))
+                .header(Header::new("If-Match", if_match))
+                .body(COMBATANT)
+                .dispatch()
+        };
+
+        assert_eq!(Status::BadRequest, update("1").status());
+        assert_eq!(Status::BadRequest, update("\"1").status());
+        assert_eq!(Status::BadRequest, update("\"1\", 2").status());
+        assert_eq!(Status::PreconditionFailed, update("\"abc\"").status());
+        assert_eq!(Status::PreconditionFailed, update("\"7\"").status());
+
+        let weak = update("W/\"1\"");
+        assert_eq!(Status::PreconditionFailed, weak.status());
+        assert_eq!(Some("\"1\""), weak.headers().get_one("ETag"));
+
+        assert_eq!(Status::Ok, update("\"7\", \"1\"").status());
+    }
+}
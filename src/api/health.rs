@@ -0,0 +1,80 @@
+use rocket::{http::Status, State};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::storage::Storage;
+
+use super::ApiResponse;
+
+#[derive(Serialize, ToSchema)]
+pub struct HealthContract {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
+}
+
+/// The application is running and able to answer requests
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses((status = 200, description = "The application is up", body = HealthContract))
+)]
+#[get("/live")]
+pub fn live() -> ApiResponse {
+    ApiResponse {
+        json: serde_json::to_string(&HealthContract {
+            status: String::from("up"),
+            database: None,
+        })
+        .unwrap(),
+        status: Status::Ok,
+        etag: None,
+    }
+}
+
+/// The application is able to serve requests that need the storage
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "The application and the storage are up", body = HealthContract),
+        (status = 503, description = "The storage is not reachable", body = HealthContract)
+    )
+)]
+#[get("/ready")]
+pub async fn ready(storage: &State<Storage>) -> ApiResponse {
+    let is_ready = storage.is_ready().await;
+
+    let contract = HealthContract {
+        status: String::from(if is_ready { "up" } else { "down" }),
+        database: Some(String::from(if is_ready { "up" } else { "down" })),
+    };
+
+    ApiResponse {
+        json: serde_json::to_string(&contract).unwrap(),
+        status: if is_ready {
+            Status::Ok
+        } else {
+            Status::ServiceUnavailable
+        },
+        etag: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use crate::api::test_client::client;
+
+    #[test]
+    fn ready_with_memory_storage() {
+        let client = client();
+
+        let response = client.get("/health/ready").dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+    }
+}
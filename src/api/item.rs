@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use rocket::{serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    engine::item::{Item, ItemKind},
+    storage::{items::*, GenericEntity, Record, Storage},
+};
+
+use super::{
+    auth::AuthenticatedUser, combatant::CombatantContract, etag::IfMatch, ApiResponse,
+    CrudApiScaffold,
+};
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKindContract {
+    Weapon,
+    Armor,
+    Consumable,
+}
+
+/// A piece of equipment, weapons and armor add their stats to the combatant,
+/// consumables heal it once it is below half of its maximal hit points
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct ItemContract {
+    pub id: Option<String>,
+    pub name: String,
+    pub kind: ItemKindContract,
+    #[serde(default)]
+    pub damage: u16,
+    #[serde(default)]
+    pub armor: u16,
+    /// The number of steps attacks reach
+    #[serde(default)]
+    pub range: u32,
+    /// Added to the steps the combatant can move, negative values slow it down
+    #[serde(default)]
+    pub speed: i8,
+    #[serde(default)]
+    pub heal: u16,
+}
+
+impl From<ItemKindEntity> for ItemKindContract {
+    fn from(value: ItemKindEntity) -> Self {
+        match value {
+            ItemKindEntity::Weapon => ItemKindContract::Weapon,
+            ItemKindEntity::Armor => ItemKindContract::Armor,
+            ItemKindEntity::Consumable => ItemKindContract::Consumable,
+        }
+    }
+}
+
+impl From<ItemKindContract> for ItemKindEntity {
+    fn from(value: ItemKindContract) -> Self {
+        match value {
+            ItemKindContract::Weapon => ItemKindEntity::Weapon,
+            ItemKindContract::Armor => ItemKindEntity::Armor,
+            ItemKindContract::Consumable => ItemKindEntity::Consumable,
+        }
+    }
+}
+
+impl From<ItemKindEntity> for ItemKind {
+    fn from(value: ItemKindEntity) -> Self {
+        match value {
+            ItemKindEntity::Weapon => ItemKind::Weapon,
+            ItemKindEntity::Armor => ItemKind::Armor,
+            ItemKindEntity::Consumable => ItemKind::Consumable,
+        }
+    }
+}
+
+impl From<&ItemRecord> for ItemContract {
+    fn from(value: &ItemRecord) -> Self {
+        ItemContract {
+            id: Some(value.get_id()),
+            name: value.name.clone(),
+            kind: ItemKindContract::from(value.kind),
+            damage: value.damage,
+            armor: value.armor,
+            range: value.range,
+            speed: value.speed,
+            heal: value.heal,
+        }
+    }
+}
+
+impl From<Json<ItemContract>> for ItemEntity {
+    fn from(value: Json<ItemContract>) -> Self {
+        ItemEntity {
+            name: value.name.clone(),
+            kind: ItemKindEntity::from(value.kind),
+            damage: value.damage,
+            armor: value.armor,
+            range: value.range,
+            speed: value.speed,
+            heal: value.heal,
+        }
+    }
+}
+
+impl From<&ItemRecord> for Item {
+    fn from(value: &ItemRecord) -> Self {
+        Item {
+            name: value.name.clone(),
+            kind: ItemKind::from(value.kind),
+            damage: value.damage,
+            armor: value.armor,
+            range: value.range,
+            speed: value.speed,
+            heal: value.heal,
+        }
+    }
+}
+
+/// Loads the items equipped by the combatants of a battle, keyed by their id
+///
+/// Unknown items and items of other users are left out, the combatants fight without them.
+pub async fn equipped_items(
+    db: &Storage,
+    user: &AuthenticatedUser,
+    combatants: &[CombatantContract],
+) -> HashMap<String, Item> {
+    let db_access: GenericEntity<'_> = GenericEntity::new::<ItemEntity>(db);
+    let mut items: HashMap<String, Item> = HashMap::new();
+
+    for id in combatants.iter().flat_map(|c| c.equipment.iter()) {
+        if items.contains_key(id) {
+            continue;
+        }
+
+        if let Ok(record) =
+            CrudApiScaffold::accessible_record::<ItemEntity, ItemRecord>(&db_access, id, user).await
+        {
+            items.insert(id.clone(), Item::from(&record));
+        }
+    }
+
+    items
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/items",
+    tag = "items",
+    responses(
+        (status = 200, description = "All items the user can access", body = [ItemContract]),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/")]
+pub async fn get_all(db: &State<Storage>, user: AuthenticatedUser) -> Json<Vec<ItemContract>> {
+    let all_items: Vec<ItemRecord> =
+        CrudApiScaffold::get_all::<ItemEntity, ItemRecord>(db, &user).await;
+
+    Json(Vec::from_iter(
+        all_items.iter().map(|record: &ItemRecord| ItemContract::from(record)),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/items/{id}",
+    tag = "items",
+    params(("id" = String, Path, description = "The record id")),
+    responses(
+        (status = 200, description = "The item, its version in the ETag header", body = ItemContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No item with this id the user can access")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/<id>")]
+pub async fn get_by_id(db: &State<Storage>, id: &str, user: AuthenticatedUser) -> ApiResponse {
+    return CrudApiScaffold::get_by_id::<ItemEntity, ItemRecord, ItemContract>(
+        db,
+        id,
+        &user,
+        |record: ItemRecord| ItemContract::from(&record),
+    )
+    .await;
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/items/{id}",
+    tag = "items",
+    params(
+        ("id" = String, Path, description = "The record id"),
+        ("If-Match" = Option<String>, Header, description = "The expected version, as returned in the ETag header")
+    ),
+    responses(
+        (status = 200, description = "The deleted item", body = ItemContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No item with this id the user can access"),
+        (status = 412, description = "The item was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
+#[delete("/<id>")]
+pub async fn delete(
+    db: &State<Storage>,
+    id: &str,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    return CrudApiScaffold::delete::<ItemEntity, ItemRecord, ItemContract>(
+        db,
+        id,
+        if_match,
+        &user,
+        |record: ItemRecord| ItemContract::from(&record),
+    )
+    .await;
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/items",
+    tag = "items",
+    request_body = ItemContract,
+    responses(
+        (status = 200, description = "The created item, its version in the ETag header", body = ItemContract),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
+#[post("/", format = "json", data = "<item_post_data>")]
+pub async fn create_new(
+    item_post_data: Json<ItemContract>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    let entity: ItemEntity = ItemEntity::from(item_post_data);
+    return CrudApiScaffold::create_new(db, entity, &user, |record: ItemRecord| {
+        ItemContract::from(&record)
+    })
+    .await;
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/items/{id}",
+    tag = "items",
+    params(
+        ("id" = String, Path, description = "The record id"),
+        ("If-Match" = Option<String>, Header, description = "The expected version, as returned in the ETag header")
+    ),
+    request_body = ItemContract,
+    responses(
+        (status = 200, description = "The updated item, its version in the ETag header", body = ItemContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No item with this id the user can access"),
+        (status = 412, description = "The item was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
+#[put("/<id>", format = "json", data = "<post_data>")]
+pub async fn update(
+    id: &str,
+    post_data: Json<ItemContract>,
+    db: &State<Storage>,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    let entity: ItemEntity = ItemEntity::from(post_data);
+    return CrudApiScaffold::update(db, id, entity, if_match, &user, |record: ItemRecord| {
+        ItemContract::from(&record)
+    })
+    .await;
+}
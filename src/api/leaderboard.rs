@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use rocket::{http::Status, State};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    engine::rating::{rating_changes, INITIAL_RATING},
+    storage::{
+        battle_results::{BattleResultEntity, BattleResultRecord},
+        combatants::{CombatantEntity, CombatantRecord},
+        GenericEntity, Record, Storage,
+    },
+};
+
+use super::{auth::AuthenticatedUser, ApiResponse, CrudApiScaffold};
+
+#[derive(Serialize, ToSchema)]
+pub struct LeaderboardEntryContract {
+    pub rank: u32,
+    pub combatant: String,
+    pub name: String,
+    pub rating: i32,
+    /// The rated battles, only battles against other stored combatants are rated
+    pub battles: u32,
+    pub wins: u32,
+}
+
+/// The results the leaderboard is built from
+struct LeaderboardFilter {
+    battlefield: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl LeaderboardFilter {
+    fn is_set(&self) -> bool {
+        self.battlefield.is_some() || self.since.is_some() || self.until.is_some()
+    }
+
+    fn matches(&self, result: &BattleResultRecord) -> bool {
+        self.battlefield
+            .as_ref()
+            .map(|b| result.battlefield.as_ref() == Some(b))
+            .unwrap_or(true)
+            && self.since.map(|s| result.fought_at >= s).unwrap_or(true)
+            && self.until.map(|u| result.fought_at < u).unwrap_or(true)
+    }
+}
+
+fn parse_time(value: Option<&str>) -> Result<Option<DateTime<Utc>>, ApiResponse> {
+    match value {
+        Some(v) => DateTime::parse_from_rfc3339(v)
+            .map(|t| Some(t.with_timezone(&Utc)))
+            .map_err(|e| ApiResponse {
+                json: e.to_string(),
+                status: Status::BadRequest,
+                etag: None,
+            }),
+        None => Ok(None),
+    }
+}
+
+/// Ranks the combatants by rating, only combatants with at least one rated battle are listed
+///
+/// Without a filter the stored ratings are used, otherwise the ratings are replayed from the matching results.
+fn rank(
+    combatants: &[CombatantRecord],
+    mut results: Vec<BattleResultRecord>,
+    filter: &LeaderboardFilter,
+) -> Vec<LeaderboardEntryContract> {
+    let mut ratings: HashMap<String, f64> = HashMap::new();
+    let mut battles: HashMap<String, (u32, u32)> = HashMap::new();
+
+    results.retain(|r| filter.matches(r));
+    results.sort_by_key(|r| r.fought_at);
+
+    for result in results.iter() {
+        for participant in result.participants.iter() {
+            let (count, wins) = battles.entry(participant.combatant.clone()).or_default();
+            *count += 1;
+            if participant.place == 1 {
+                *wins += 1;
+            }
+        }
+
+        let before: Vec<(f64, u32)> = result
+            .participants
+            .iter()
+            .map(|p| (*ratings.get(&p.combatant).unwrap_or(&INITIAL_RATING), p.place))
+            .collect();
+
+        for (participant, change) in result.participants.iter().zip(rating_changes(&before)) {
+            *ratings.entry(participant.combatant.clone()).or_insert(INITIAL_RATING) += change;
+        }
+    }
+
+    let mut entries: Vec<LeaderboardEntryContract> = combatants
+        .iter()
+        .filter_map(|record| {
+            let id = record.get_id();
+            let (count, wins) = *battles.get(&id)?;
+            let rating = match filter.is_set() {
+                true => *ratings.get(&id).unwrap_or(&INITIAL_RATING),
+                false => record.rating,
+            };
+
+            Some(LeaderboardEntryContract {
+                rank: 0,
+                combatant: id,
+                name: record.name.clone(),
+                rating: rating.round() as i32,
+                battles: count,
+                wins,
+            })
+        })
+        .collect();
+
+    entries.sort_by(|a, b| b.rating.cmp(&a.rating).then(a.name.cmp(&b.name)));
+    for (index, entry) in entries.iter_mut().enumerate() {
+        entry.rank = index as u32 + 1;
+    }
+
+    entries
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/leaderboard",
+    tag = "leaderboard",
+    params(
+        ("battlefield" = Option<String>, Query, description = "Only battles fought on the stored battlefield with this id"),
+        ("since" = Option<String>, Query, description = "Only battles fought at or after this time (RFC 3339)"),
+        ("until" = Option<String>, Query, description = "Only battles fought before this time (RFC 3339)")
+    ),
+    responses(
+        (status = 200, description = "The combatants the user can access, ordered by rating", body = [LeaderboardEntryContract]),
+        (status = 400, description = "A time is not given in RFC 3339"),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/?<battlefield>&<since>&<until>")]
+pub async fn get_leaderboard(
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+    battlefield: Option<&str>,
+    since: Option<&str>,
+    until: Option<&str>,
+) -> ApiResponse {
+    let filter = LeaderboardFilter {
+        battlefield: battlefield.map(String::from),
+        since: match parse_time(since) {
+            Ok(t) => t,
+            Err(response) => return response,
+        },
+        until: match parse_time(until) {
+            Ok(t) => t,
+            Err(response) => return response,
+        },
+    };
+
+    let combatants: Vec<CombatantRecord> =
+        CrudApiScaffold::get_all::<CombatantEntity, CombatantRecord>(db, &user).await;
+    // The ratings depend on the battles of all users
+    let results: Vec<BattleResultRecord> = GenericEntity::new::<BattleResultEntity>(db.inner())
+        .get_all::<BattleResultEntity, BattleResultRecord>()
+        .await
+        .unwrap_or_default();
+
+    ApiResponse {
+        json: serde_json::to_string(&rank(&combatants, results, &filter)).unwrap(),
+        status: Status::Ok,
+        etag: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use crate::api::test_client::{api_key, battle_of, client, create, create_combatant, get_json, post_json, ADMIN_KEY};
+
+    #[test]
+    fn leaderboard_ranks_rated_combatants() {
+        let client = client();
+        let first = create_combatant(&client, ADMIN_KEY);
+        let second = create_combatant(&client, ADMIN_KEY);
+        create_combatant(&client, ADMIN_KEY);
+
+        post_json(&client, "/battle", ADMIN_KEY, battle_of(&first, &second));
+
+        let leaderboard: Vec<serde_json::Value> = get_json(&client, "/leaderboard", ADMIN_KEY);
+        let future: Vec<serde_json::Value> = get_json(&client, "/leaderboard?since=2999-01-01T00:00:00Z", ADMIN_KEY);
+        let invalid = client
+            .get("/leaderboard?since=yesterday")
+            .header(api_key(ADMIN_KEY))
+            .dispatch();
+
+        assert_eq!(2, leaderboard.len());
+        assert_eq!(1, leaderboard[0]["rank"]);
+        assert_eq!(1516, leaderboard[0]["rating"]);
+        assert_eq!(1484, leaderboard[1]["rating"]);
+        assert!(future.is_empty());
+        assert_eq!(Status::BadRequest, invalid.status());
+    }
+
+    #[test]
+    fn inflated_stats_do_not_change_rating() {
+        let client = client();
+        let weak = create(
+            &client,
+            "/combatants",
+            ADMIN_KEY,
+            r#"{"name": "weak", "hp": 1, "dmg": 1, "id": null, "avatar": null}"#,
+        );
+        let strong = create(
+            &client,
+            "/combatants",
+            ADMIN_KEY,
+            r#"{"name": "strong", "hp": 100, "dmg": 50, "id": null, "avatar": null}"#,
+        );
+        let battle = format!(
+            r#"{{"map": {{"height": 8, "width": 8}}, "combatants": [
+                {{"name": "weak", "hp": 60000, "dmg": 60000, "id": "{}"}},
+                {{"name": "strong", "hp": 100, "dmg": 50, "id": "{}"}}]}}"#,
+            weak, strong
+        );
+
+        post_json(&client, "/battle", ADMIN_KEY, battle);
+
+        let weak: serde_json::Value = get_json(&client, format!("/combatants/{}", weak), ADMIN_KEY);
+        assert_eq!(1, weak["hp"]);
+        assert_eq!(0, weak["progression"]["wins"]);
+        assert!(weak["progression"]["rating"].as_i64().unwrap() < 1500);
+    }
+}
@@ -0,0 +1,284 @@
+use rocket::{http::Status, response::content::RawHtml};
+use utoipa::{
+    openapi::{
+        path::PathItem,
+        security::{ApiKey, ApiKeyValue, SecurityScheme},
+    },
+    Modify, OpenApi,
+};
+
+use super::{
+    battle::{
+        AbilityTargetContract, BattleActionAbilityContract, BattleActionAttackContract,
+        BattleActionContract, BattleActionEffectAppliedContract, BattleActionHealContract,
+        BattleActionReviveContract,
+        BattleActionEffectTickedContract, BattleActionMoveContract,
+        BattleBapContract, BattleResultContract, CreateBattleContract, MapContract, PoiContract,
+        PointContract, TopologyContract,
+    },
+    ability::{AbilityContract, AbilityKindContract},
+    battle_log::{
+        BattleLogCombatantContract, BattleLogContract, BattleLogEventContract, BattleLogHeaderContract,
+        BattleLogMapContract, BattleLogMovementContract, BattleLogReviveContract, BattleLogRulesContract,
+        BattleLogTargetContract,
+    },
+    battlefield::BattleFieldContract,
+    bulk::{ImportReportContract, ImportRowContract, ImportRowStatusContract},
+    combatant::{CombatantContract, ProgressionContract},
+    health::HealthContract,
+    item::{ItemContract, ItemKindContract},
+    leaderboard::LeaderboardEntryContract,
+    replay::BattleStateContract,
+    status_effect::{StatusEffectContract, StatusEffectKindContract},
+    tournament::{
+        MatchContract, StandingContract, TournamentContract, TournamentFormatContract,
+        TournamentStatusContract,
+    },
+    user::{CreatedUserContract, UserContract},
+    ApiResponse,
+};
+
+// The documentation UI, it loads the specification from /openapi.json
+const DOCS_PAGE: &str = r#"<!doctype html>
+<html>
+  <head>
+    <meta charset="utf-8">
+    <title>ribs API</title>
+    <script type="module" src="https://unpkg.com/rapidoc@9.3.4/dist/rapidoc-min.js"></script>
+  </head>
+  <body>
+    <rapi-doc spec-url="/openapi.json" render-style="read" allow-server-selection="false"></rapi-doc>
+  </body>
+</html>
+"#;
+
+/// The OpenAPI specification of all routes mounted by the application
+///
+/// Every new route has to be added to the paths, otherwise the drift test fails.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "ribs", description = "A simple battle system with a simple REST API"),
+    paths(
+        super::index,
+        super::health::live,
+        super::health::ready,
+        super::battlefield::get_all,
+        super::battlefield::create_new,
+        super::battlefield::update,
+        super::battlefield::delete,
+        super::battlefield::get_by_id,
+        super::battlefield::export,
+        super::battlefield::import,
+        super::combatant::get_all,
+        super::combatant::create_new,
+        super::combatant::update,
+        super::combatant::delete,
+        super::combatant::get_by_id,
+        super::combatant::equip,
+        super::combatant::unequip,
+        super::combatant::export,
+        super::combatant::import,
+        super::item::get_all,
+        super::item::create_new,
+        super::item::update,
+        super::item::delete,
+        super::item::get_by_id,
+        super::tournament::get_all,
+        super::tournament::create_new,
+        super::tournament::delete,
+        super::tournament::get_by_id,
+        super::tournament::register,
+        super::tournament::run,
+        super::tournament::standings,
+        super::leaderboard::get_leaderboard,
+        super::battle::start_new_battle,
+        super::v2::battle::start_new_battle,
+        super::replay::get_state,
+        super::battle_log::get_log,
+        super::battle_log::import_log,
+        super::render::render_text,
+        super::render::render_svg,
+        super::render::render_gif,
+        super::user::get_all,
+        super::user::me,
+        super::user::create_new,
+        super::user::delete
+    ),
+    components(schemas(
+        HealthContract,
+        CombatantContract,
+        ProgressionContract,
+        ItemContract,
+        ItemKindContract,
+        TournamentContract,
+        TournamentFormatContract,
+        TournamentStatusContract,
+        MatchContract,
+        StandingContract,
+        LeaderboardEntryContract,
+        BattleFieldContract,
+        ImportReportContract,
+        ImportRowContract,
+        ImportRowStatusContract,
+        CreateBattleContract,
+        BattleBapContract,
+        TopologyContract,
+        BattleResultContract,
+        BattleStateContract,
+        BattleLogContract,
+        BattleLogHeaderContract,
+        BattleLogRulesContract,
+        BattleLogReviveContract,
+        BattleLogMapContract,
+        BattleLogCombatantContract,
+        BattleLogMovementContract,
+        BattleLogTargetContract,
+        BattleLogEventContract,
+        MapContract,
+        PoiContract,
+        PointContract,
+        BattleActionContract,
+        BattleActionMoveContract,
+        BattleActionAttackContract,
+        BattleActionEffectAppliedContract,
+        BattleActionEffectTickedContract,
+        StatusEffectContract,
+        StatusEffectKindContract,
+        BattleActionAbilityContract,
+        BattleActionHealContract,
+        BattleActionReviveContract,
+        AbilityTargetContract,
+        AbilityContract,
+        AbilityKindContract,
+        UserContract,
+        CreatedUserContract,
+        super::v2::battle::BattleResultContract,
+        super::v2::battle::BattleActionContract
+    )),
+    modifiers(&ApiKeyAuth, &UnchangedInV2)
+)]
+pub struct ApiDoc;
+
+/// Registers the X-Api-Key header referenced by the secured routes
+struct ApiKeyAuth;
+
+impl Modify for ApiKeyAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+            );
+        }
+    }
+}
+
+/// Documents the routes of the first version that are served unchanged by the second one
+struct UnchangedInV2;
+
+impl Modify for UnchangedInV2 {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let unchanged: Vec<(String, PathItem)> = openapi
+            .paths
+            .paths
+            .iter()
+            .filter_map(|(path, item)| {
+                path.strip_prefix("/v1/")
+                    .map(|p| (format!("/v2/{}", p), item.clone()))
+            })
+            .filter(|(path, _)| !openapi.paths.paths.contains_key(path))
+            .collect();
+
+        openapi.paths.paths.extend(unchanged);
+    }
+}
+
+#[get("/openapi.json")]
+pub fn specification() -> ApiResponse {
+    match ApiDoc::openapi().to_json() {
+        Ok(json) => ApiResponse {
+            json,
+            status: Status::Ok,
+            etag: None,
+        },
+        Err(_) => ApiResponse::empty(Status::InternalServerError),
+    }
+}
+
+#[get("/docs")]
+pub fn docs() -> RawHtml<&'static str> {
+    RawHtml(DOCS_PAGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rocket::http::Status;
+
+    use super::*;
+    use crate::api::test_client::client;
+
+    // The routes serving the documentation itself are not part of the specification
+    const UNDOCUMENTED: [&str; 2] = ["/openapi.json", "/docs"];
+
+    /// Converts a Rocket path (/combatants/<id>) into an OpenAPI path (/combatants/{id})
+    fn openapi_path(rocket_path: &str) -> String {
+        let path = rocket_path
+            .split('/')
+            .map(|segment| match segment.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+                Some(name) => format!("{{{}}}", name.trim_end_matches("..")),
+                None => segment.to_owned(),
+            })
+            .collect::<Vec<String>>()
+            .join("/");
+
+        match path.len() > 1 {
+            true => path.trim_end_matches('/').to_owned(),
+            false => path,
+        }
+    }
+
+    #[test]
+    fn openapi_path_with_parameter() {
+        assert_eq!("/combatants/{id}", openapi_path("/combatants/<id>"));
+        assert_eq!("/combatants", openapi_path("/combatants/"));
+        assert_eq!("/", openapi_path("/"));
+    }
+
+    #[test]
+    fn specification_matches_routes() {
+        let rocket = crate::mount_api(rocket::build());
+        let mounted: BTreeSet<String> = rocket
+            .routes()
+            .map(|route| (route.method.as_str().to_lowercase(), route.uri.path().to_string()))
+            .filter(|(_, path)| !UNDOCUMENTED.contains(&path.as_str()))
+            .map(|(method, path)| format!("{} {}", method, openapi_path(&path)))
+            .collect();
+
+        let specification = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let documented: BTreeSet<String> = specification["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, operations)| {
+                operations
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .map(move |method| format!("{} {}", method, path))
+            })
+            .collect();
+
+        assert_eq!(mounted, documented);
+    }
+
+    #[test]
+    fn openapi_specification() {
+        let client = client();
+
+        let response = client.get("/openapi.json").dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+    }
+}
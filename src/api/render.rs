@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use rocket::{
+    http::{ContentType, Status},
+    State,
+};
+
+use crate::{
+    engine::replay::Replay,
+    render::{
+        gif::GifAnimation,
+        svg::SvgRenderer,
+        text::{TextRenderer, TextStyle},
+    },
+    storage::Storage,
+};
+
+use super::{
+    auth::AuthenticatedUser,
+    replay::{replayed_state, stored_battle},
+    ApiResponse,
+};
+
+// The time every round is shown in animations if not given, in milliseconds
+const DEFAULT_DELAY: u64 = 500;
+
+// Animations are never faster or slower than this, in milliseconds
+const MIN_DELAY: u64 = 20;
+const MAX_DELAY: u64 = 10_000;
+
+#[utoipa::path(
+    get,
+    path = "/v1/battle/{id}/render",
+    tag = "battle",
+    params(
+        ("id" = String, Path, description = "The id of the battle, as returned when it was fought"),
+        ("round" = Option<u32>, Query, description = "The round to show the end of, the last round if not set"),
+        ("ansi" = Option<bool>, Query, description = "Colors the combatants and their hit points for terminals")
+    ),
+    responses(
+        (status = 200, description = "The map as a grid of characters, followed by the hit points of every combatant",
+            body = String, content_type = "text/plain"),
+        (status = 400, description = "The battle did not last that many rounds"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "Battle not found"),
+        (status = 422, description = "The stored actions do not replay, the reason is given")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/<id>/render?<round>&<ansi>")]
+pub async fn render_text(
+    id: &str,
+    round: Option<u32>,
+    ansi: Option<bool>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> Result<(ContentType, String), ApiResponse> {
+    let (_, state) = replayed_state(db.inner(), id, round, &user).await?;
+    let style = match ansi {
+        Some(true) => TextStyle::Ansi,
+        _ => TextStyle::Plain,
+    };
+
+    Ok((
+        ContentType::Plain,
+        TextRenderer::new(style).frame(state.round_number, &state.map, &state.combatants),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/battle/{id}/render.svg",
+    tag = "battle",
+    params(
+        ("id" = String, Path, description = "The id of the battle, as returned when it was fought"),
+        ("round" = Option<u32>, Query, description = "The round to show the end of, the last round if not set")
+    ),
+    responses(
+        (status = 200, description = "The map and the hit points of every combatant, avatars are drawn as images",
+            body = String, content_type = "image/svg+xml"),
+        (status = 400, description = "The battle did not last that many rounds"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "Battle not found"),
+        (status = 422, description = "The stored actions do not replay, the reason is given")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/<id>/render.svg?<round>")]
+pub async fn render_svg(
+    id: &str,
+    round: Option<u32>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> Result<(ContentType, String), ApiResponse> {
+    let (_, state) = replayed_state(db.inner(), id, round, &user).await?;
+
+    Ok((
+        ContentType::SVG,
+        SvgRenderer.frame(state.round_number, &state.map, &state.combatants),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/battle/{id}/render.gif",
+    tag = "battle",
+    params(
+        ("id" = String, Path, description = "The id of the battle, as returned when it was fought"),
+        ("delay" = Option<u64>, Query, description = "Milliseconds every round is shown, 500 if not set")
+    ),
+    responses(
+        (status = 200, description = "An animation with one frame per round, from the start to the end of the battle",
+            body = Vec<u8>, content_type = "image/gif"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "Battle not found"),
+        (status = 422, description = "The stored actions do not replay, the reason is given")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/<id>/render.gif?<delay>")]
+pub async fn render_gif(
+    id: &str,
+    delay: Option<u64>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> Result<(ContentType, Vec<u8>), ApiResponse> {
+    let (record, initial, actions) = stored_battle(db.inner(), id, &user).await?;
+    let delay = Duration::from_millis(delay.unwrap_or(DEFAULT_DELAY).clamp(MIN_DELAY, MAX_DELAY));
+
+    let mut animation = GifAnimation::new(&initial.map, delay).map_err(failed)?;
+    let mut drawn: Result<(), String> = Ok(());
+    Replay::new(initial).visit_rounds(&actions, record.rounds, |state| {
+        if drawn.is_ok() {
+            drawn = animation.add_frame(&state.map, &state.combatants);
+        }
+    })?;
+    drawn.map_err(failed)?;
+
+    Ok((ContentType::GIF, animation.finish().map_err(failed)?))
+}
+
+fn failed(message: String) -> ApiResponse {
+    error!("The battle could not be drawn: {}", message);
+    ApiResponse::empty(Status::InternalServerError)
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::ContentType;
+
+    use crate::api::test_client::{api_key, client, post_json, ADMIN_KEY, BATTLE};
+
+    #[test]
+    fn render_stored_battle() {
+        let client = client();
+        let result: serde_json::Value = post_json(&client, "/battle", ADMIN_KEY, BATTLE).into_json().unwrap();
+        let id = result["id"].as_str().unwrap();
+
+        let response = client
+            .get(format!("/battle/{}/render?round=0", id))
+            .header(api_key(ADMIN_KEY))
+            .dispatch();
+        assert_eq!(Some(ContentType::Plain), response.content_type());
+
+        let frame = response.into_string().unwrap();
+        let lines: Vec<&str> = frame.lines().collect();
+        assert_eq!("Round 0", lines[0]);
+        assert_eq!(2, lines[1..9].iter().map(|l| l.matches(['A', 'B']).count()).sum::<usize>());
+        assert_eq!("A first   ##########  10/10", lines[10]);
+        assert_eq!("B second  ##########  10/10", lines[11]);
+
+        let svg = client
+            .get(format!("/battle/{}/render.svg", id))
+            .header(api_key(ADMIN_KEY))
+            .dispatch();
+        assert_eq!(Some(ContentType::SVG), svg.content_type());
+        assert!(svg
+            .into_string()
+            .unwrap()
+            .contains(&format!(">Round {}</text>", result["round_number"])));
+
+        let gif = client
+            .get(format!("/battle/{}/render.gif?delay=100", id))
+            .header(api_key(ADMIN_KEY))
+            .dispatch();
+        assert_eq!(Some(ContentType::GIF), gif.content_type());
+        assert!(gif.into_bytes().unwrap().starts_with(b"GIF89a"));
+    }
+}
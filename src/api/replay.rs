@@ -0,0 +1,558 @@
+use chrono::Utc;
+use rocket::{http::Status, State};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    engine::{
+        ability::{Ability, AbilityKind},
+        battle_actions::{
+            AbilityTarget, BattleAbilityAction, BattleAction, BattleAttackAction, BattleHealAction,
+        },
+        battle_engine::BattleRoundState,
+        battle_result::BattleResult,
+        battle_rules::{BattleRules, ReviveRules},
+        combatant::Combatant,
+        item::{Item, ItemKind},
+        map::Map,
+        movement::MovementResult,
+        replay::{Replay, ReplayError},
+        status_effect::{StatusEffect, StatusEffectKind, StatusEffectTick},
+    },
+    storage::{
+        battles::*,
+        combatants::{AbilityEntity, AbilityKindEntity, StatusEffectEntity, StatusEffectKindEntity},
+        err::Error as StorageError,
+        items::{ItemEntity, ItemKindEntity},
+        GenericEntity, Record, Storage,
+    },
+    types::{point::Point, topology::Topology},
+};
+
+use super::{
+    ability::{AbilityContract, AbilityKindContract},
+    auth::AuthenticatedUser,
+    battle::MapContract,
+    combatant::CombatantContract,
+    status_effect::{StatusEffectContract, StatusEffectKindContract},
+    ApiResponse, CrudApiScaffold,
+};
+
+/// The state of a stored battle at the end of a round
+#[derive(Serialize, ToSchema)]
+pub struct BattleStateContract {
+    pub id: String,
+    /// Round 0 is the state before the first round
+    pub round: u32,
+    pub combatants: Vec<CombatantContract>,
+    pub map: MapContract,
+}
+
+impl From<Point> for PointEntity {
+    fn from(value: Point) -> Self {
+        PointEntity { x: value.x, y: value.y }
+    }
+}
+
+impl From<PointEntity> for Point {
+    fn from(value: PointEntity) -> Self {
+        Point::new(value.x, value.y)
+    }
+}
+
+impl From<Topology> for TopologyEntity {
+    fn from(value: Topology) -> Self {
+        match value {
+            Topology::Square4 => TopologyEntity::Square4,
+            Topology::Square8 => TopologyEntity::Square8,
+            Topology::Hex => TopologyEntity::Hex,
+        }
+    }
+}
+
+impl From<TopologyEntity> for Topology {
+    fn from(value: TopologyEntity) -> Self {
+        match value {
+            TopologyEntity::Square4 => Topology::Square4,
+            TopologyEntity::Square8 => Topology::Square8,
+            TopologyEntity::Hex => Topology::Hex,
+        }
+    }
+}
+
+impl From<&BattleRules> for BattleRulesEntity {
+    fn from(value: &BattleRules) -> Self {
+        BattleRulesEntity {
+            max_rounds: value.max_rounds,
+            max_combatant_move: value.max_combatant_move as u32,
+            revive: value.revive.map(|r| ReviveRulesEntity {
+                after_rounds: r.after_rounds,
+                hp_percent: r.hp_percent,
+                max_revives: r.max_revives,
+            }),
+        }
+    }
+}
+
+impl From<&BattleRecord> for BattleRules {
+    /// The rules the stored battle was played by, with its topology and seed
+    fn from(value: &BattleRecord) -> Self {
+        let rules = value.rules.map(|r| BattleRules {
+            max_rounds: r.max_rounds,
+            max_combatant_move: r.max_combatant_move as usize,
+            revive: r.revive.map(|revive| ReviveRules {
+                after_rounds: revive.after_rounds,
+                hp_percent: revive.hp_percent,
+                max_revives: revive.max_revives,
+            }),
+            ..Default::default()
+        });
+
+        BattleRules {
+            topology: Topology::from(value.topology),
+            seed: Some(value.seed),
+            ..rules.unwrap_or_default()
+        }
+    }
+}
+
+impl From<&MovementResult> for MovementEntity {
+    fn from(value: &MovementResult) -> Self {
+        MovementEntity {
+            start: PointEntity::from(value.start),
+            goal: PointEntity::from(value.goal),
+            steps: value.steps.iter().map(|p| PointEntity::from(*p)).collect(),
+        }
+    }
+}
+
+impl From<&MovementEntity> for MovementResult {
+    fn from(value: &MovementEntity) -> Self {
+        let steps: Vec<Point> = value.steps.iter().map(|p| Point::from(*p)).collect();
+
+        MovementResult {
+            start: Point::from(value.start),
+            goal: Point::from(value.goal),
+            last_position: steps.last().copied().unwrap_or(Point::from(value.start)),
+            steps,
+        }
+    }
+}
+
+fn effect_entity(effect: &StatusEffect) -> StatusEffectEntity {
+    StatusEffectEntity::from(&StatusEffectContract::from(effect))
+}
+
+fn effect(entity: &StatusEffectEntity) -> StatusEffect {
+    StatusEffect::from(&StatusEffectContract::from(entity))
+}
+
+fn effect_kind_entity(kind: StatusEffectKind) -> StatusEffectKindEntity {
+    StatusEffectKindEntity::from(StatusEffectKindContract::from(kind))
+}
+
+fn ability_kind_entity(kind: AbilityKind) -> AbilityKindEntity {
+    AbilityKindEntity::from(AbilityKindContract::from(kind))
+}
+
+impl From<&Combatant> for FighterEntity {
+    fn from(value: &Combatant) -> Self {
+        FighterEntity {
+            name: value.name.clone(),
+            combatant: value.id.clone(),
+            avatar: value.avatar.clone(),
+            team: value.team.clone(),
+            hp: value.hp,
+            max_hp: value.max_hp,
+            dmg: value.dmg,
+            armor: value.armor,
+            attack_range: value.attack_range,
+            speed: value.speed,
+            effects: value.effects.iter().map(effect_entity).collect(),
+            on_hit: value.on_hit.iter().map(effect_entity).collect(),
+            abilities: value
+                .abilities
+                .iter()
+                .map(|a| AbilityEntity::from(&AbilityContract::from(a)))
+                .collect(),
+            consumables: value
+                .consumables
+                .iter()
+                .map(|i| ItemEntity {
+                    name: i.name.clone(),
+                    kind: ItemKindEntity::Consumable,
+                    damage: i.damage,
+                    armor: i.armor,
+                    range: i.range,
+                    speed: i.speed,
+                    heal: i.heal,
+                })
+                .collect(),
+            // Set from the map by the battle
+            location: PointEntity { x: 0, y: 0 },
+        }
+    }
+}
+
+impl From<&FighterEntity> for Combatant {
+    fn from(value: &FighterEntity) -> Self {
+        Combatant {
+            name: value.name.clone(),
+            id: value.combatant.clone(),
+            avatar: value.avatar.clone(),
+            team: value.team.clone(),
+            hp: value.hp,
+            max_hp: value.max_hp,
+            dmg: value.dmg,
+            armor: value.armor,
+            attack_range: value.attack_range,
+            speed: value.speed,
+            effects: value.effects.iter().map(effect).collect(),
+            on_hit: value.on_hit.iter().map(effect).collect(),
+            abilities: value
+                .abilities
+                .iter()
+                .map(|a| Ability::from(&AbilityContract::from(a)))
+                .collect(),
+            consumables: value
+                .consumables
+                .iter()
+                .map(|i| Item {
+                    name: i.name.clone(),
+                    kind: ItemKind::from(i.kind),
+                    damage: i.damage,
+                    armor: i.armor,
+                    range: i.range,
+                    speed: i.speed,
+                    heal: i.heal,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&BattleAction> for BattleActionEntity {
+    fn from(value: &BattleAction) -> Self {
+        match value {
+            BattleAction::Move(round, combatant, movement) => BattleActionEntity::Move {
+                round: *round,
+                combatant: combatant.clone(),
+                movement: MovementEntity::from(movement),
+            },
+            BattleAction::Attack(round, attack) => BattleActionEntity::Attack {
+                round: *round,
+                attacker: attack.assailant.name.clone(),
+                attacked: attack.victim.name.clone(),
+                damage: attack.damage,
+                remaining_hp: attack.victim.hp,
+            },
+            BattleAction::EffectApplied(round, combatant, applied) => BattleActionEntity::EffectApplied {
+                round: *round,
+                combatant: combatant.clone(),
+                effect: effect_entity(applied),
+            },
+            BattleAction::EffectTicked(round, combatant, tick) => BattleActionEntity::EffectTicked {
+                round: *round,
+                combatant: combatant.clone(),
+                kind: effect_kind_entity(tick.kind),
+                amount: tick.amount,
+                remaining_hp: tick.remaining_hp,
+                remaining_turns: tick.remaining_turns,
+            },
+            BattleAction::Ability(round, ability) => BattleActionEntity::Ability {
+                round: *round,
+                combatant: ability.user.clone(),
+                ability: ability.ability.clone(),
+                kind: ability_kind_entity(ability.kind),
+                targets: ability
+                    .targets
+                    .iter()
+                    .map(|t| AbilityTargetEntity {
+                        name: t.name.clone(),
+                        amount: t.amount,
+                        remaining_hp: t.remaining_hp,
+                    })
+                    .collect(),
+                movement: ability.movement.as_ref().map(MovementEntity::from),
+            },
+            BattleAction::Heal(round, heal) => BattleActionEntity::Heal {
+                round: *round,
+                healer: heal.healer.name.clone(),
+                healed: heal.target.name.clone(),
+                amount: heal.amount,
+                remaining_hp: heal.target.hp,
+                item: heal.item.clone(),
+            },
+            BattleAction::Revive(round, combatant, location) => BattleActionEntity::Revive {
+                round: *round,
+                combatant: combatant.name.clone(),
+                location: PointEntity::from(*location),
+                hp: combatant.hp,
+            },
+        }
+    }
+}
+
+/// A combatant as far as the log knows it, only the name and the hit points are logged
+pub fn logged(name: &str, hp: u16) -> Combatant {
+    Combatant {
+        name: name.to_owned(),
+        hp,
+        ..Default::default()
+    }
+}
+
+impl From<&BattleActionEntity> for BattleAction {
+    fn from(value: &BattleActionEntity) -> Self {
+        match value {
+            BattleActionEntity::Move { round, combatant, movement } => {
+                BattleAction::Move(*round, combatant.clone(), MovementResult::from(movement))
+            }
+            BattleActionEntity::Attack { round, attacker, attacked, damage, remaining_hp } => BattleAction::Attack(
+                *round,
+                BattleAttackAction {
+                    assailant: logged(attacker, 0),
+                    victim: logged(attacked, *remaining_hp),
+                    damage: *damage,
+                },
+            ),
+            BattleActionEntity::EffectApplied { round, combatant, effect: applied } => {
+                BattleAction::EffectApplied(*round, combatant.clone(), effect(applied))
+            }
+            BattleActionEntity::EffectTicked { round, combatant, kind, amount, remaining_hp, remaining_turns } => {
+                BattleAction::EffectTicked(
+                    *round,
+                    combatant.clone(),
+                    StatusEffectTick {
+                        kind: StatusEffectKind::from(StatusEffectKindContract::from(*kind)),
+                        amount: *amount,
+                        remaining_hp: *remaining_hp,
+                        remaining_turns: *remaining_turns,
+                    },
+                )
+            }
+            BattleActionEntity::Ability { round, combatant, ability, kind, targets, movement } => BattleAction::Ability(
+                *round,
+                BattleAbilityAction {
+                    user: combatant.clone(),
+                    ability: ability.clone(),
+                    kind: AbilityKind::from(AbilityKindContract::from(*kind)),
+                    targets: targets
+                        .iter()
+                        .map(|t| AbilityTarget {
+                            name: t.name.clone(),
+                            amount: t.amount,
+                            remaining_hp: t.remaining_hp,
+                        })
+                        .collect(),
+                    movement: movement.as_ref().map(MovementResult::from),
+                },
+            ),
+            BattleActionEntity::Heal { round, healer, healed, amount, remaining_hp, item } => BattleAction::Heal(
+                *round,
+                BattleHealAction {
+                    healer: logged(healer, 0),
+                    target: logged(healed, *remaining_hp),
+                    amount: *amount,
+                    item: item.clone(),
+                },
+            ),
+            BattleActionEntity::Revive { round, combatant, location, hp } => {
+                BattleAction::Revive(*round, logged(combatant, *hp), Point::from(*location))
+            }
+        }
+    }
+}
+
+impl From<&BattleResult> for BattleEntity {
+    fn from(value: &BattleResult) -> Self {
+        let map = &value.initial.map;
+
+        BattleEntity {
+            fought_at: Utc::now(),
+            seed: value.seed,
+            rules: Some(BattleRulesEntity::from(&value.rules)),
+            width: map.get_width(),
+            height: map.get_height(),
+            topology: TopologyEntity::from(map.get_topology()),
+            fighters: value
+                .initial
+                .combatants
+                .iter()
+                .map(|c| FighterEntity {
+                    location: PointEntity::from(map.position_for(&c.name).unwrap_or(Point::new(0, 0))),
+                    ..FighterEntity::from(c)
+                })
+                .collect(),
+            actions: value.actions.iter().map(BattleActionEntity::from).collect(),
+            rounds: value.round_number,
+            winner: value.winner.as_ref().map(|w| w.name.clone()),
+        }
+    }
+}
+
+/// The state before the first round, every combatant at its starting location
+///
+/// Fails if a location is outside of the map or taken, or if a name is used twice.
+pub fn initial_state(map: Map, combatants: Vec<(Combatant, Point)>) -> Result<BattleRoundState, ReplayError> {
+    let mut map = map;
+
+    for (combatant, location) in combatants.iter() {
+        map.place(combatant.name.clone(), *location)
+            .map_err(|_| ReplayError::InvalidPlacement(0, combatant.name.clone()))?;
+    }
+
+    Ok(BattleRoundState {
+        combatants: combatants.into_iter().map(|(c, _)| c).collect(),
+        map,
+        actions: vec![],
+        round_number: 0,
+    })
+}
+
+impl TryFrom<&BattleRecord> for BattleRoundState {
+    type Error = ReplayError;
+
+    fn try_from(value: &BattleRecord) -> Result<Self, Self::Error> {
+        initial_state(
+            Map::with_topology(value.width, value.height, Topology::from(value.topology)),
+            value
+                .fighters
+                .iter()
+                .map(|f| (Combatant::from(f), Point::from(f.location)))
+                .collect(),
+        )
+    }
+}
+
+/// Reports the combatants and the map of the stored battle at the end of the round
+pub fn state_response(id: String, round: u32, combatants: &[Combatant], map: Map, status: Status) -> ApiResponse {
+    let contract = BattleStateContract {
+        id,
+        round,
+        combatants: combatants.iter().map(CombatantContract::from).collect(),
+        map: MapContract::from(map),
+    };
+
+    match serde_json::to_string(&contract) {
+        Ok(json) => ApiResponse {
+            json,
+            status,
+            etag: None,
+        },
+        Err(_) => ApiResponse::empty(Status::InternalServerError),
+    }
+}
+
+/// Stores the battle so it can be replayed, returns the id it was stored under
+///
+/// A battle that can not be stored is still reported, it just can not be replayed.
+pub async fn store_battle(db: &Storage, user: &AuthenticatedUser, result: &BattleResult) -> Option<String> {
+    let stored: Result<BattleRecord, StorageError> = GenericEntity::new::<BattleEntity>(db)
+        .create_new(BattleEntity::from(result), user.id.clone())
+        .await;
+
+    match stored {
+        Ok(record) => Some(record.get_id()),
+        Err(e) => {
+            warn!("The battle could not be stored: {}", e);
+            None
+        }
+    }
+}
+
+/// Replays the stored battle up to the end of the round
+/// The stored battle of the user, with the state before the first round and the actions of all rounds
+pub async fn stored_battle(
+    db: &Storage,
+    id: &str,
+    user: &AuthenticatedUser,
+) -> Result<(BattleRecord, BattleRoundState, Vec<BattleAction>), ApiResponse> {
+    let db_access: GenericEntity<'_> = GenericEntity::new::<BattleEntity>(db);
+    let record: BattleRecord = CrudApiScaffold::accessible_record::<BattleEntity, BattleRecord>(&db_access, id, user)
+        .await
+        .map_err(ApiResponse::from)?;
+
+    let initial = BattleRoundState::try_from(&record)?;
+    let actions: Vec<BattleAction> = record.actions.iter().map(BattleAction::from).collect();
+
+    Ok((record, initial, actions))
+}
+
+/// The stored battle of the user replayed to the end of the round, the last round if not given
+pub async fn replayed_state(
+    db: &Storage,
+    id: &str,
+    round: Option<u32>,
+    user: &AuthenticatedUser,
+) -> Result<(BattleRecord, BattleRoundState), ApiResponse> {
+    let (record, initial, actions) = stored_battle(db, id, user).await?;
+
+    let round = round.unwrap_or(record.rounds);
+    if round > record.rounds {
+        return Err(ApiResponse::empty(Status::BadRequest));
+    }
+
+    let state = Replay::new(initial).state_at(&actions, round)?;
+
+    Ok((record, state))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/battle/{id}/state",
+    tag = "battle",
+    params(
+        ("id" = String, Path, description = "The id of the battle, as returned when it was fought"),
+        ("round" = Option<u32>, Query, description = "The round to show the end of, the last round if not set")
+    ),
+    responses(
+        (status = 200, description = "The combatants and the map at the end of the round", body = BattleStateContract),
+        (status = 400, description = "The battle did not last that many rounds"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "Battle not found"),
+        (status = 422, description = "The stored actions do not replay, the reason is given")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/<id>/state?<round>")]
+pub async fn get_state(id: &str, round: Option<u32>, db: &State<Storage>, user: AuthenticatedUser) -> ApiResponse {
+    match replayed_state(db.inner(), id, round, &user).await {
+        Ok((record, state)) => state_response(record.get_id(), state.round_number, &state.combatants, state.map, Status::Ok),
+        Err(response) => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use crate::api::test_client::{api_key, client, create_user, get_json, post_json, BATTLE};
+
+    #[test]
+    fn replay_stored_battle() {
+        let client = client();
+        let user_key = create_user(&client);
+        let result: serde_json::Value = post_json(&client, "/battle", &user_key, BATTLE).into_json().unwrap();
+        let id = result["id"].as_str().unwrap();
+
+        let start: serde_json::Value = get_json(&client, format!("/battle/{}/state?round=0", id), &user_key);
+        let end: serde_json::Value = get_json(&client, format!("/v2/battle/{}/state", id), &user_key);
+        let beyond = client
+            .get(format!("/battle/{}/state?round={}", id, result["round_number"].as_u64().unwrap() + 1))
+            .header(api_key(&user_key))
+            .dispatch();
+        let other_user = client
+            .get(format!("/battle/{}/state", id))
+            .header(api_key(&create_user(&client)))
+            .dispatch();
+
+        assert!(start["combatants"].as_array().unwrap().iter().all(|c| c["hp"] == 10));
+        assert_eq!(result["round_number"], end["round"]);
+        assert_eq!(result["combatants"], end["combatants"]);
+        assert_eq!(result["map"]["pois"].as_array().unwrap().len(), end["map"]["pois"].as_array().unwrap().len());
+        assert_eq!(Status::BadRequest, beyond.status());
+        assert_eq!(Status::NotFound, other_user.status());
+    }
+}
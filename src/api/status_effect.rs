@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    engine::status_effect::{StatusEffect, StatusEffectKind},
+    storage::combatants::{StatusEffectEntity, StatusEffectKindEntity},
+};
+
+#[derive(Deserialize, Serialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusEffectKindContract {
+    Poison,
+    Bleed,
+    Stun,
+    Slow,
+    Regeneration,
+    Shield,
+}
+
+/// A timed effect, potency is the damage or healing per turn, the steps lost when slowed or the damage absorbed by a shield
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct StatusEffectContract {
+    pub kind: StatusEffectKindContract,
+    pub turns: u32,
+    #[serde(default)]
+    pub potency: u16,
+}
+
+impl From<StatusEffectKind> for StatusEffectKindContract {
+    fn from(value: StatusEffectKind) -> Self {
+        match value {
+            StatusEffectKind::Poison => StatusEffectKindContract::Poison,
+            StatusEffectKind::Bleed => StatusEffectKindContract::Bleed,
+            StatusEffectKind::Stun => StatusEffectKindContract::Stun,
+            StatusEffectKind::Slow => StatusEffectKindContract::Slow,
+            StatusEffectKind::Regeneration => StatusEffectKindContract::Regeneration,
+            StatusEffectKind::Shield => StatusEffectKindContract::Shield,
+        }
+    }
+}
+
+impl From<StatusEffectKindContract> for StatusEffectKind {
+    fn from(value: StatusEffectKindContract) -> Self {
+        match value {
+            StatusEffectKindContract::Poison => StatusEffectKind::Poison,
+            StatusEffectKindContract::Bleed => StatusEffectKind::Bleed,
+            StatusEffectKindContract::Stun => StatusEffectKind::Stun,
+            StatusEffectKindContract::Slow => StatusEffectKind::Slow,
+            StatusEffectKindContract::Regeneration => StatusEffectKind::Regeneration,
+            StatusEffectKindContract::Shield => StatusEffectKind::Shield,
+        }
+    }
+}
+
+impl From<StatusEffectKindEntity> for StatusEffectKindContract {
+    fn from(value: StatusEffectKindEntity) -> Self {
+        match value {
+            StatusEffectKindEntity::Poison => StatusEffectKindContract::Poison,
+            StatusEffectKindEntity::Bleed => StatusEffectKindContract::Bleed,
+            StatusEffectKindEntity::Stun => StatusEffectKindContract::Stun,
+            StatusEffectKindEntity::Slow => StatusEffectKindContract::Slow,
+            StatusEffectKindEntity::Regeneration => StatusEffectKindContract::Regeneration,
+            StatusEffectKindEntity::Shield => StatusEffectKindContract::Shield,
+        }
+    }
+}
+
+impl From<StatusEffectKindContract> for StatusEffectKindEntity {
+    fn from(value: StatusEffectKindContract) -> Self {
+        match value {
+            StatusEffectKindContract::Poison => StatusEffectKindEntity::Poison,
+            StatusEffectKindContract::Bleed => StatusEffectKindEntity::Bleed,
+            StatusEffectKindContract::Stun => StatusEffectKindEntity::Stun,
+            StatusEffectKindContract::Slow => StatusEffectKindEntity::Slow,
+            StatusEffectKindContract::Regeneration => StatusEffectKindEntity::Regeneration,
+            StatusEffectKindContract::Shield => StatusEffectKindEntity::Shield,
+        }
+    }
+}
+
+impl From<&StatusEffect> for StatusEffectContract {
+    fn from(value: &StatusEffect) -> Self {
+        StatusEffectContract {
+            kind: StatusEffectKindContract::from(value.kind),
+            turns: value.remaining_turns,
+            potency: value.potency,
+        }
+    }
+}
+
+impl From<&StatusEffectContract> for StatusEffect {
+    fn from(value: &StatusEffectContract) -> Self {
+        StatusEffect::new(StatusEffectKind::from(value.kind), value.turns, value.potency)
+    }
+}
+
+impl From<&StatusEffectEntity> for StatusEffectContract {
+    fn from(value: &StatusEffectEntity) -> Self {
+        StatusEffectContract {
+            kind: StatusEffectKindContract::from(value.kind),
+            turns: value.turns,
+            potency: value.potency,
+        }
+    }
+}
+
+impl From<&StatusEffectContract> for StatusEffectEntity {
+    fn from(value: &StatusEffectContract) -> Self {
+        StatusEffectEntity {
+            kind: StatusEffectKindEntity::from(value.kind),
+            turns: value.turns,
+            potency: value.potency,
+        }
+    }
+}
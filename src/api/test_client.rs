@@ -0,0 +1,88 @@
+//! A client for the tests of the API modules, serving all routes from the in-memory storage
+
+use rocket::http::{ContentType, Header};
+use rocket::local::blocking::{Client, LocalResponse};
+
+use crate::api::auth::AuthMiddleware;
+use crate::config::AppConfig;
+use crate::mount_api;
+use crate::storage::{memory::MemoryRepository, Storage};
+
+pub const ADMIN_KEY: &str = "test-admin-api-key";
+
+pub const COMBATANT: &str = r#"{"name": "test", "hp": 10, "dmg": 2, "id": null, "avatar": null}"#;
+
+pub const BATTLE: &str = r#"{
+    "map": {"height": 8, "width": 8},
+    "combatants": [
+        {"name": "first", "hp": 10, "dmg": 5, "id": null, "avatar": null},
+        {"name": "second", "hp": 10, "dmg": 5, "id": null, "avatar": null}
+    ]
+}"#;
+
+pub fn client() -> Client {
+    let mut config = AppConfig::default();
+    config.auth.admin_api_key = Some(String::from(ADMIN_KEY));
+
+    let rocket = mount_api(rocket::build())
+        .manage(Storage::Memory(MemoryRepository::default()))
+        .manage(config)
+        .attach(AuthMiddleware);
+
+    Client::tracked(rocket).unwrap()
+}
+
+pub fn api_key(key: &str) -> Header<'static> {
+    Header::new("X-Api-Key", key.to_owned())
+}
+
+/// Posts the JSON body with the API key
+pub fn post_json<'c>(client: &'c Client, uri: impl Into<String>, key: &str, body: impl AsRef<[u8]>) -> LocalResponse<'c> {
+    client
+        .post(uri.into())
+        .header(ContentType::JSON)
+        .header(api_key(key))
+        .body(body)
+        .dispatch()
+}
+
+/// Gets the resource with the API key as JSON
+pub fn get_json<T: serde::de::DeserializeOwned + Send + 'static>(client: &Client, uri: impl Into<String>, key: &str) -> T {
+    client.get(uri.into()).header(api_key(key)).dispatch().into_json().unwrap()
+}
+
+/// Creates the resource and returns its id
+pub fn create(client: &Client, uri: &str, key: &str, body: &str) -> String {
+    let created: serde_json::Value = post_json(client, uri, key, body).into_json().unwrap();
+
+    created["id"].as_str().unwrap().to_owned()
+}
+
+pub fn create_combatant(client: &Client, key: &str) -> String {
+    create(client, "/combatants", key, COMBATANT)
+}
+
+pub fn create_item(client: &Client, body: &str) -> String {
+    create(client, "/items", ADMIN_KEY, body)
+}
+
+/// Creates a user without admin rights and returns their API key
+pub fn create_user(client: &Client) -> String {
+    let created: serde_json::Value = post_json(
+        client,
+        "/users",
+        ADMIN_KEY,
+        r#"{"name": "user", "is_admin": false, "id": null}"#,
+    )
+    .into_json()
+    .unwrap();
+
+    created["api_key"].as_str().unwrap().to_owned()
+}
+
+/// The battle with the two stored combatants taking the places of the posted ones
+pub fn battle_of(first: &str, second: &str) -> String {
+    BATTLE
+        .replacen(r#""id": null"#, &format!(r#""id": "{}""#, first), 1)
+        .replacen(r#""id": null"#, &format!(r#""id": "{}""#, second), 1)
+}
@@ -0,0 +1,651 @@
+use std::collections::HashMap;
+
+use rocket::{http::Status, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    config::AppConfig,
+    engine::{
+        battle_engine::BattleEngine,
+        battle_result::BattleResult,
+        battle_rules::BattleRules,
+        battlefield::BattlefieldData,
+        combatant::Combatant,
+        err::Error,
+        progression::{BattleOutcome, ProgressionRules},
+        tournament::{Standing, Tournament, TournamentFormat, TournamentMatch},
+    },
+    storage::{
+        battlefields::{BattleFieldEntity, BattleFieldRecord},
+        combatants::{CombatantEntity, CombatantRecord},
+        err::Error as StorageError,
+        tournaments::*,
+        GenericEntity, Record, Storage,
+    },
+};
+
+use super::{
+    auth::AuthenticatedUser,
+    combatant::{name_apart, record_battle, CombatantContract},
+    etag::IfMatch,
+    item::equipped_items,
+    ApiResponse, CrudApiScaffold,
+};
+
+#[derive(Deserialize, Serialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentFormatContract {
+    SingleElimination,
+    DoubleElimination,
+    RoundRobin,
+    Swiss,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TournamentStatusContract {
+    #[default]
+    Registration,
+    Finished,
+}
+
+/// A single match, the combatants are given by their id
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct MatchContract {
+    pub round: u32,
+    pub first: String,
+    /// Not set if the first combatant has a bye
+    pub second: Option<String>,
+    /// Not set if the battle ended without a winner
+    pub winner: Option<String>,
+    pub losers_bracket: bool,
+}
+
+/// Status and matches are set by the server, combatants can be registered until the tournament is run
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct TournamentContract {
+    pub id: Option<String>,
+    pub name: String,
+    pub format: TournamentFormatContract,
+    /// The number of rounds of a swiss tournament, enough to leave a single combatant without a loss if not set
+    #[serde(default)]
+    pub rounds: Option<u32>,
+    /// The id of the battlefield all matches are played on
+    pub battlefield: String,
+    /// The ids of the registered combatants in the order of their seed
+    #[serde(default)]
+    pub combatants: Vec<String>,
+    #[serde(default)]
+    pub status: TournamentStatusContract,
+    #[serde(default)]
+    pub matches: Vec<MatchContract>,
+}
+
+/// The results of a single combatant, byes count as wins
+#[derive(Serialize, ToSchema)]
+pub struct StandingContract {
+    pub combatant: String,
+    pub matches: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl From<TournamentFormatEntity> for TournamentFormatContract {
+    fn from(value: TournamentFormatEntity) -> Self {
+        match value {
+            TournamentFormatEntity::SingleElimination => TournamentFormatContract::SingleElimination,
+            TournamentFormatEntity::DoubleElimination => TournamentFormatContract::DoubleElimination,
+            TournamentFormatEntity::RoundRobin => TournamentFormatContract::RoundRobin,
+            TournamentFormatEntity::Swiss => TournamentFormatContract::Swiss,
+        }
+    }
+}
+
+impl From<TournamentFormatContract> for TournamentFormatEntity {
+    fn from(value: TournamentFormatContract) -> Self {
+        match value {
+            TournamentFormatContract::SingleElimination => TournamentFormatEntity::SingleElimination,
+            TournamentFormatContract::DoubleElimination => TournamentFormatEntity::DoubleElimination,
+            TournamentFormatContract::RoundRobin => TournamentFormatEntity::RoundRobin,
+            TournamentFormatContract::Swiss => TournamentFormatEntity::Swiss,
+        }
+    }
+}
+
+impl From<TournamentStatusEntity> for TournamentStatusContract {
+    fn from(value: TournamentStatusEntity) -> Self {
+        match value {
+            TournamentStatusEntity::Registration => TournamentStatusContract::Registration,
+            TournamentStatusEntity::Finished => TournamentStatusContract::Finished,
+        }
+    }
+}
+
+impl From<&MatchEntity> for MatchContract {
+    fn from(value: &MatchEntity) -> Self {
+        MatchContract {
+            round: value.round,
+            first: value.first.clone(),
+            second: value.second.clone(),
+            winner: value.winner.clone(),
+            losers_bracket: value.losers_bracket,
+        }
+    }
+}
+
+impl From<&TournamentRecord> for TournamentContract {
+    fn from(value: &TournamentRecord) -> Self {
+        TournamentContract {
+            id: Some(value.get_id()),
+            name: value.name.clone(),
+            format: TournamentFormatContract::from(value.format),
+            rounds: value.rounds,
+            battlefield: value.battlefield.clone(),
+            combatants: value.combatants.to_vec(),
+            status: TournamentStatusContract::from(value.status),
+            matches: value.matches.iter().map(MatchContract::from).collect(),
+        }
+    }
+}
+
+impl From<Json<TournamentContract>> for TournamentEntity {
+    fn from(value: Json<TournamentContract>) -> Self {
+        let mut combatants: Vec<String> = vec![];
+        for id in value.combatants.iter() {
+            if !combatants.contains(id) {
+                combatants.push(id.clone());
+            }
+        }
+
+        TournamentEntity {
+            name: value.name.clone(),
+            format: TournamentFormatEntity::from(value.format),
+            rounds: value.rounds,
+            battlefield: value.battlefield.clone(),
+            combatants,
+            status: TournamentStatusEntity::Registration,
+            matches: vec![],
+        }
+    }
+}
+
+impl TournamentEntity {
+    fn engine_format(&self) -> TournamentFormat {
+        match self.format {
+            TournamentFormatEntity::SingleElimination => TournamentFormat::SingleElimination,
+            TournamentFormatEntity::DoubleElimination => TournamentFormat::DoubleElimination,
+            TournamentFormatEntity::RoundRobin => TournamentFormat::RoundRobin,
+            TournamentFormatEntity::Swiss => TournamentFormat::Swiss(
+                self.rounds
+                    .unwrap_or(Tournament::swiss_rounds(self.combatants.len())),
+            ),
+        }
+    }
+
+    /// The stored matches with the combatants given by their seed
+    fn engine_matches(&self) -> Vec<TournamentMatch> {
+        let seed = |id: &String| self.combatants.iter().position(|c| c == id);
+
+        self.matches
+            .iter()
+            .filter_map(|m| {
+                Some(TournamentMatch {
+                    round: m.round,
+                    first: seed(&m.first)?,
+                    second: m.second.as_ref().and_then(seed),
+                    winner: m.winner.as_ref().and_then(seed),
+                    losers_bracket: m.losers_bracket,
+                })
+            })
+            .collect()
+    }
+
+    fn store_matches(&mut self, matches: &[TournamentMatch]) {
+        let id = |seed: usize| self.combatants[seed].clone();
+
+        self.matches = matches
+            .iter()
+            .map(|m| MatchEntity {
+                round: m.round,
+                first: id(m.first),
+                second: m.second.map(id),
+                winner: m.winner.map(id),
+                losers_bracket: m.losers_bracket,
+            })
+            .collect();
+    }
+}
+
+impl StandingContract {
+    fn from(value: &Standing, combatants: &[String]) -> Self {
+        StandingContract {
+            combatant: combatants[value.participant].clone(),
+            matches: value.matches,
+            wins: value.wins,
+            losses: value.losses,
+            draws: value.draws,
+        }
+    }
+}
+
+/// Loads the registered combatants with their equipment, in the order of their seed
+///
+/// Combatants with the same name are told apart by their seed, the battle map needs unique names.
+async fn load_fighters(
+    db: &Storage,
+    user: &AuthenticatedUser,
+    ids: &[String],
+) -> Result<Vec<Combatant>, StorageError> {
+    let db_access: GenericEntity<'_> = GenericEntity::new::<CombatantEntity>(db);
+    let mut contracts: Vec<CombatantContract> = vec![];
+
+    for id in ids.iter() {
+        let record: CombatantRecord =
+            CrudApiScaffold::accessible_record::<CombatantEntity, CombatantRecord>(&db_access, id, user).await?;
+        contracts.push(CombatantContract::from(&record));
+    }
+    name_apart(&mut contracts);
+
+    let items = equipped_items(db, user, &contracts).await;
+    let mut fighters: Vec<Combatant> = vec![];
+
+    for contract in contracts.iter() {
+        let mut fighter = Combatant::from(contract);
+        fighter.equip(
+            contract
+                .equipment
+                .iter()
+                .filter_map(|id| items.get(id).cloned())
+                .collect(),
+        );
+
+        fighters.push(fighter);
+    }
+
+    Ok(fighters)
+}
+
+/// Plays a single match on an empty battlefield of the given size
+fn play_match(
+    fighters: &[Combatant],
+    first: usize,
+    second: usize,
+    battlefield: &BattleFieldEntity,
+    rules: BattleRules,
+) -> Result<BattleResult, Error> {
+    let data = BattlefieldData {
+        battlefield_height: battlefield.height,
+        battlefield_width: battlefield.width,
+        combatants: vec![fighters[first].clone(), fighters[second].clone()],
+    };
+
+    BattleEngine::new(data, rules)?.start_battle()
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tournaments",
+    tag = "tournaments",
+    responses(
+        (status = 200, description = "All tournaments the user can access", body = [TournamentContract]),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/")]
+pub async fn get_all(db: &State<Storage>, user: AuthenticatedUser) -> Json<Vec<TournamentContract>> {
+    let all_tournaments: Vec<TournamentRecord> =
+        CrudApiScaffold::get_all::<TournamentEntity, TournamentRecord>(db, &user).await;
+
+    Json(Vec::from_iter(
+        all_tournaments
+            .iter()
+            .map(|record: &TournamentRecord| TournamentContract::from(record)),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tournaments/{id}",
+    tag = "tournaments",
+    params(("id" = String, Path, description = "The record id")),
+    responses(
+        (status = 200, description = "The tournament with its bracket, its version in the ETag header", body = TournamentContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No tournament with this id the user can access")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/<id>")]
+pub async fn get_by_id(db: &State<Storage>, id: &str, user: AuthenticatedUser) -> ApiResponse {
+    return CrudApiScaffold::get_by_id::<TournamentEntity, TournamentRecord, TournamentContract>(
+        db,
+        id,
+        &user,
+        |record: TournamentRecord| TournamentContract::from(&record),
+    )
+    .await;
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/tournaments/{id}",
+    tag = "tournaments",
+    params(
+        ("id" = String, Path, description = "The record id"),
+        ("If-Match" = Option<String>, Header, description = "The expected version, as returned in the ETag header")
+    ),
+    responses(
+        (status = 200, description = "The deleted tournament", body = TournamentContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No tournament with this id the user can access"),
+        (status = 412, description = "The tournament was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
+#[delete("/<id>")]
+pub async fn delete(
+    db: &State<Storage>,
+    id: &str,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    return CrudApiScaffold::delete::<TournamentEntity, TournamentRecord, TournamentContract>(
+        db,
+        id,
+        if_match,
+        &user,
+        |record: TournamentRecord| TournamentContract::from(&record),
+    )
+    .await;
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/tournaments",
+    tag = "tournaments",
+    request_body = TournamentContract,
+    responses(
+        (status = 200, description = "The created tournament, its version in the ETag header", body = TournamentContract),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
+#[post("/", format = "json", data = "<tournament_post_data>")]
+pub async fn create_new(
+    tournament_post_data: Json<TournamentContract>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    let entity: TournamentEntity = TournamentEntity::from(tournament_post_data);
+    return CrudApiScaffold::create_new(db, entity, &user, |record: TournamentRecord| {
+        TournamentContract::from(&record)
+    })
+    .await;
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/tournaments/{id}/combatants/{combatant_id}",
+    tag = "tournaments",
+    params(
+        ("id" = String, Path, description = "The record id of the tournament"),
+        ("combatant_id" = String, Path, description = "The record id of the combatant"),
+        ("If-Match" = Option<String>, Header, description = "The expected version of the tournament, as returned in the ETag header")
+    ),
+    responses(
+        (status = 200, description = "The tournament with the combatant registered", body = TournamentContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No tournament or combatant with this id the user can access"),
+        (status = 409, description = "The tournament was already run"),
+        (status = 412, description = "The tournament was changed in the meantime, the current version is in the ETag header"),
+        (status = 500, description = "A match failed, the tournament was not run")
+    ),
+    security(("api_key" = []))
+)]
+#[put("/<id>/combatants/<combatant_id>")]
+pub async fn register(
+    id: &str,
+    combatant_id: &str,
+    db: &State<Storage>,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    let tournaments: GenericEntity<'_> = GenericEntity::new::<TournamentEntity>(db.inner());
+    let combatants: GenericEntity<'_> = GenericEntity::new::<CombatantEntity>(db.inner());
+
+    let mut entity: TournamentEntity = match CrudApiScaffold::accessible_record::<
+        TournamentEntity,
+        TournamentRecord,
+    >(&tournaments, id, &user)
+    .await
+    {
+        Ok(record) => record.get_entity(),
+        Err(e) => return ApiResponse::from(e),
+    };
+    if entity.status == TournamentStatusEntity::Finished {
+        return ApiResponse::empty(Status::Conflict);
+    }
+    if let Err(e) =
+        CrudApiScaffold::accessible_record::<CombatantEntity, CombatantRecord>(&combatants, combatant_id, &user).await
+    {
+        return ApiResponse::from(e);
+    }
+
+    if !entity.combatants.iter().any(|c| c == combatant_id) {
+        entity.combatants.push(combatant_id.to_owned());
+    }
+
+    return CrudApiScaffold::update(db, id, entity, if_match, &user, |record: TournamentRecord| {
+        TournamentContract::from(&record)
+    })
+    .await;
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/tournaments/{id}/run",
+    tag = "tournaments",
+    params(
+        ("id" = String, Path, description = "The record id"),
+        ("If-Match" = Option<String>, Header, description = "The expected version, as returned in the ETag header")
+    ),
+    responses(
+        (status = 200, description = "The finished tournament with all matches", body = TournamentContract),
+        (status = 400, description = "Less than two combatants are registered, a combatant or the battlefield can not be accessed, or a match can not be played on the battlefield"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No tournament with this id the user can access"),
+        (status = 409, description = "The tournament was already run"),
+        (status = 412, description = "The tournament was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
+#[post("/<id>/run")]
+pub async fn run(
+    id: &str,
+    db: &State<Storage>,
+    config: &State<AppConfig>,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    let tournaments: GenericEntity<'_> = GenericEntity::new::<TournamentEntity>(db.inner());
+    let battlefields: GenericEntity<'_> = GenericEntity::new::<BattleFieldEntity>(db.inner());
+
+    let mut entity: TournamentEntity = match CrudApiScaffold::accessible_record::<
+        TournamentEntity,
+        TournamentRecord,
+    >(&tournaments, id, &user)
+    .await
+    {
+        Ok(record) => record.get_entity(),
+        Err(e) => return ApiResponse::from(e),
+    };
+    if entity.status == TournamentStatusEntity::Finished {
+        return ApiResponse::empty(Status::Conflict);
+    }
+    if entity.combatants.len() < 2 {
+        return ApiResponse::empty(Status::BadRequest);
+    }
+
+    let battlefield: BattleFieldEntity = match CrudApiScaffold::accessible_record::<
+        BattleFieldEntity,
+        BattleFieldRecord,
+    >(&battlefields, &entity.battlefield, &user)
+    .await
+    {
+        Ok(record) => record.get_entity(),
+        Err(_) => return ApiResponse::empty(Status::BadRequest),
+    };
+    let fighters: Vec<Combatant> = match load_fighters(db.inner(), &user, &entity.combatants).await {
+        Ok(f) => f,
+        Err(_) => return ApiResponse::empty(Status::BadRequest),
+    };
+
+    let rules = BattleRules::from(&config.battle);
+    let mut played: Vec<(usize, usize, HashMap<String, BattleOutcome>)> = vec![];
+    // A match that could not be played is no draw, the tournament is not run at all then
+    let mut failure: Option<Error> = None;
+    let matches = Tournament::new(entity.engine_format(), fighters.len()).run(|first, second| {
+        if failure.is_some() {
+            return None;
+        }
+        let result = match play_match(&fighters, first, second, &battlefield, rules) {
+            Ok(r) => r,
+            Err(e) => {
+                failure = Some(e);
+                return None;
+            }
+        };
+        played.push((first, second, result.outcomes()));
+        let winner = result.winner?;
+
+        [first, second]
+            .into_iter()
+            .find(|seed| fighters[*seed].name == winner.name)
+    });
+
+    if let Some(e) = failure {
+        return ApiResponse::from(e);
+    }
+
+    let combatants = entity.combatants.to_vec();
+    let battlefield_id = entity.battlefield.clone();
+    entity.store_matches(&matches);
+    entity.status = TournamentStatusEntity::Finished;
+
+    let response = CrudApiScaffold::update(db, id, entity, if_match, &user, |record: TournamentRecord| {
+        TournamentContract::from(&record)
+    })
+    .await;
+
+    // The matches only count once the tournament is stored as finished
+    if response.status == Status::Ok {
+        let progression = ProgressionRules::from(&config.progression);
+
+        for (first, second, outcomes) in played.iter() {
+            let pair: Vec<(String, String)> = [*first, *second]
+                .iter()
+                .map(|seed| (combatants[*seed].clone(), fighters[*seed].name.clone()))
+                .collect();
+
+            record_battle(db.inner(), &user, &progression, &pair, outcomes, Some(battlefield_id.clone())).await;
+        }
+    }
+
+    response
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tournaments/{id}/standings",
+    tag = "tournaments",
+    params(("id" = String, Path, description = "The record id")),
+    responses(
+        (status = 200, description = "The combatants ordered by wins, then losses, then seed", body = [StandingContract]),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 404, description = "No tournament with this id the user can access")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/<id>/standings")]
+pub async fn standings(db: &State<Storage>, id: &str, user: AuthenticatedUser) -> ApiResponse {
+    return CrudApiScaffold::get_by_id::<TournamentEntity, TournamentRecord, Vec<StandingContract>>(
+        db,
+        id,
+        &user,
+        |record: TournamentRecord| {
+            let entity = record.get_entity();
+
+            Tournament::standings(entity.combatants.len(), &entity.engine_matches())
+                .iter()
+                .map(|s| StandingContract::from(s, &entity.combatants))
+                .collect()
+        },
+    )
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+    use rocket::local::blocking::Client;
+
+    use crate::api::test_client::{api_key, client, create, create_combatant, get_json, post_json, ADMIN_KEY};
+
+    /// Creates a round robin tournament of the combatants on an empty battlefield of the given size
+    fn create_tournament(client: &Client, size: u16, combatants: &[String]) -> String {
+        let battlefield = create(
+            client,
+            "/battlefields",
+            ADMIN_KEY,
+            &format!(r#"{{"height": {0}, "width": {0}, "id": null, "combatants": []}}"#, size),
+        );
+        let tournament = serde_json::json!({
+            "id": null,
+            "name": "league",
+            "format": "round_robin",
+            "battlefield": battlefield,
+            "combatants": combatants
+        });
+
+        create(client, "/tournaments", ADMIN_KEY, &tournament.to_string())
+    }
+
+    #[test]
+    fn round_robin_tournament() {
+        let client = client();
+        let combatants: Vec<String> = (0..3).map(|_| create_combatant(&client, ADMIN_KEY)).collect();
+        let id = create_tournament(&client, 8, &combatants[..2]);
+
+        client
+            .put(format!("/tournaments/{}/combatants/{}", id, combatants[2]))
+            .header(api_key(ADMIN_KEY))
+            .dispatch();
+        let finished: serde_json::Value = post_json(&client, format!("/tournaments/{}/run", id), ADMIN_KEY, "")
+            .into_json()
+            .unwrap();
+        let again = post_json(&client, format!("/tournaments/{}/run", id), ADMIN_KEY, "");
+        let standings: Vec<serde_json::Value> = get_json(&client, format!("/tournaments/{}/standings", id), ADMIN_KEY);
+
+        assert_eq!("finished", finished["status"]);
+        assert_eq!(3, finished["matches"].as_array().unwrap().len());
+        assert_eq!(Status::Conflict, again.status());
+        assert_eq!(3, standings.len());
+        assert!(standings.iter().all(|s| s["matches"] == 2));
+    }
+
+    #[test]
+    fn tournament_with_unplayable_match_is_not_run() {
+        let client = client();
+        let combatants: Vec<String> = (0..2).map(|_| create_combatant(&client, ADMIN_KEY)).collect();
+        let id = create_tournament(&client, 1, &combatants);
+
+        let run = post_json(&client, format!("/tournaments/{}/run", id), ADMIN_KEY, "");
+        let stored: serde_json::Value = get_json(&client, format!("/tournaments/{}", id), ADMIN_KEY);
+
+        assert_eq!(Status::BadRequest, run.status());
+        assert_ne!("finished", stored["status"]);
+    }
+}
@@ -0,0 +1,185 @@
+use rocket::{http::Status, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::storage::{
+    users::{UserEntity, UserRecord},
+    Record, Storage,
+};
+
+use super::{
+    auth::{generate_api_key, hash_api_key, AuthenticatedUser},
+    etag::IfMatch,
+    ApiResponse, CrudApiScaffold,
+};
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct UserContract {
+    pub id: Option<String>,
+    pub name: String,
+    pub is_admin: bool,
+}
+
+/// Returned only once when the user is created, the API key can not be retrieved afterwards
+#[derive(Serialize, ToSchema)]
+pub struct CreatedUserContract {
+    pub id: String,
+    pub name: String,
+    pub is_admin: bool,
+    pub api_key: String,
+}
+
+impl From<&UserRecord> for UserContract {
+    fn from(value: &UserRecord) -> Self {
+        UserContract {
+            id: Some(value.get_id()),
+            name: value.name.clone(),
+            is_admin: value.is_admin,
+        }
+    }
+}
+
+impl From<&AuthenticatedUser> for UserContract {
+    fn from(value: &AuthenticatedUser) -> Self {
+        UserContract {
+            id: value.id.clone(),
+            name: value.name.clone(),
+            is_admin: value.is_admin,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/users",
+    tag = "users",
+    responses(
+        (status = 200, description = "All users", body = [UserContract]),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 403, description = "The user is not an admin")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/")]
+pub async fn get_all(db: &State<Storage>, user: AuthenticatedUser) -> ApiResponse {
+    if !user.is_admin {
+        return ApiResponse::empty(Status::Forbidden);
+    }
+
+    let all_users: Vec<UserRecord> =
+        CrudApiScaffold::get_all::<UserEntity, UserRecord>(db, &user).await;
+
+    ApiResponse {
+        json: serde_json::to_string(&Vec::from_iter(
+            all_users.iter().map(|record: &UserRecord| UserContract::from(record)),
+        ))
+        .unwrap(),
+        status: Status::Ok,
+        etag: None,
+    }
+}
+
+/// Returns the user the API key belongs to
+#[utoipa::path(
+    get,
+    path = "/v1/users/me",
+    tag = "users",
+    responses(
+        (status = 200, description = "The user the API key belongs to", body = UserContract),
+        (status = 401, description = "API key missing or unknown")
+    ),
+    security(("api_key" = []))
+)]
+#[get("/me")]
+pub fn me(user: AuthenticatedUser) -> Json<UserContract> {
+    Json(UserContract::from(&user))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/users",
+    tag = "users",
+    request_body = UserContract,
+    responses(
+        (status = 200, description = "The created user with its API key", body = CreatedUserContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 403, description = "The user is not an admin")
+    ),
+    security(("api_key" = []))
+)]
+#[post("/", format = "json", data = "<post_data>")]
+pub async fn create_new(
+    post_data: Json<UserContract>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    if !user.is_admin {
+        return ApiResponse::empty(Status::Forbidden);
+    }
+
+    let api_key = generate_api_key();
+    let entity = UserEntity {
+        name: post_data.name.clone(),
+        api_key_hash: hash_api_key(&api_key),
+        is_admin: post_data.is_admin,
+    };
+
+    CrudApiScaffold::create_new(db, entity, &user, |record: UserRecord| CreatedUserContract {
+        id: record.get_id(),
+        name: record.name.clone(),
+        is_admin: record.is_admin,
+        api_key: api_key.clone(),
+    })
+    .await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/users/{id}",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "The record id"),
+        ("If-Match" = Option<String>, Header, description = "The expected version, as returned in the ETag header")
+    ),
+    responses(
+        (status = 200, description = "The deleted user", body = UserContract),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 403, description = "The user is not an admin"),
+        (status = 404, description = "No user with this id"),
+        (status = 412, description = "The user was changed in the meantime, the current version is in the ETag header")
+    ),
+    security(("api_key" = []))
+)]
+#[delete("/<id>")]
+pub async fn delete(
+    id: &str,
+    db: &State<Storage>,
+    if_match: IfMatch,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    if !user.is_admin {
+        return ApiResponse::empty(Status::Forbidden);
+    }
+
+    CrudApiScaffold::delete(db, id, if_match, &user, |record: UserRecord| {
+        UserContract::from(&record)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+
+    use crate::api::test_client::{api_key, client, create_user};
+
+    #[test]
+    fn users_only_for_admins() {
+        let client = client();
+        let user_key = create_user(&client);
+
+        let response = client.get("/users").header(api_key(&user_key)).dispatch();
+
+        assert_eq!(Status::Forbidden, response.status());
+    }
+}
@@ -0,0 +1,5 @@
+//! The contracts that changed in the second version of the API
+//!
+//! Everything not found here is served unchanged from the first version.
+
+pub mod battle;
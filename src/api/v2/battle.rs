@@ -0,0 +1,216 @@
+use rocket::{http::Status, serde::json::Json, State};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    api::{
+        auth::AuthenticatedUser,
+        ability::AbilityKindContract,
+        battle::{
+            run_battle, AbilityTargetContract, CreateBattleContract, MapContract, PointContract,
+        },
+        combatant::CombatantContract,
+        status_effect::{StatusEffectContract, StatusEffectKindContract},
+        ApiResponse,
+    },
+    config::AppConfig,
+    engine::{battle_actions::BattleAction, battle_result::BattleResult},
+    storage::Storage,
+};
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::BattleResultContract)]
+pub struct BattleResultContract {
+    /// The id to replay the battle with, not set if the battle could not be stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The ids of the stored combatants whose progression could not be stored, the battle does not count for them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unrecorded: Vec<String>,
+    /// Fighting the same combatants with this seed leads to the same battle
+    pub seed: u64,
+    pub combatants: Vec<CombatantContract>,
+    pub map: MapContract,
+    #[schema(value_type = Vec<v2::BattleActionContract>)]
+    pub actions: Vec<BattleActionContract>,
+    pub round_number: u32,
+    pub winner: Option<CombatantContract>,
+}
+
+/// A single action of the battle, the kind of action is given in the type field
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::BattleActionContract)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BattleActionContract {
+    Move {
+        round: u32,
+        combatant: String,
+        start: PointContract,
+        end: PointContract,
+        path: Vec<PointContract>,
+    },
+    Attack {
+        round: u32,
+        attacker: String,
+        attacked: String,
+        dmg: u16,
+        remaining_hp: u16,
+        max_hp: u16,
+    },
+    EffectApplied {
+        round: u32,
+        combatant: String,
+        effect: StatusEffectContract,
+    },
+    EffectTicked {
+        round: u32,
+        combatant: String,
+        kind: StatusEffectKindContract,
+        amount: u16,
+        remaining_hp: u16,
+        remaining_turns: u32,
+    },
+    Ability {
+        round: u32,
+        combatant: String,
+        ability: String,
+        kind: AbilityKindContract,
+        targets: Vec<AbilityTargetContract>,
+        path: Vec<PointContract>,
+    },
+    Heal {
+        round: u32,
+        healer: String,
+        healed: String,
+        amount: u16,
+        remaining_hp: u16,
+        max_hp: u16,
+        /// The consumable used, not set for heals by abilities
+        #[serde(skip_serializing_if = "Option::is_none")]
+        item: Option<String>,
+    },
+    Revive {
+        round: u32,
+        combatant: String,
+        location: PointContract,
+        hp: u16,
+        max_hp: u16,
+    },
+}
+
+impl From<&BattleAction> for BattleActionContract {
+    fn from(value: &BattleAction) -> Self {
+        match value {
+            BattleAction::Move(r_num, combatant, results) => BattleActionContract::Move {
+                round: *r_num,
+                combatant: combatant.clone(),
+                start: PointContract::from(results.start),
+                end: PointContract::from(results.last_position),
+                path: results.steps.iter().map(|p| PointContract::from(*p)).collect(),
+            },
+            BattleAction::Attack(r_num, action) => BattleActionContract::Attack {
+                round: *r_num,
+                attacker: action.assailant.name.clone(),
+                attacked: action.victim.name.clone(),
+                dmg: action.damage,
+                remaining_hp: action.victim.hp,
+                max_hp: action.victim.max_hp,
+            },
+            BattleAction::EffectApplied(r_num, combatant, effect) => {
+                BattleActionContract::EffectApplied {
+                    round: *r_num,
+                    combatant: combatant.clone(),
+                    effect: StatusEffectContract::from(effect),
+                }
+            }
+            BattleAction::EffectTicked(r_num, combatant, tick) => BattleActionContract::EffectTicked {
+                round: *r_num,
+                combatant: combatant.clone(),
+                kind: StatusEffectKindContract::from(tick.kind),
+                amount: tick.amount,
+                remaining_hp: tick.remaining_hp,
+                remaining_turns: tick.remaining_turns,
+            },
+            BattleAction::Ability(r_num, action) => BattleActionContract::Ability {
+                round: *r_num,
+                combatant: action.user.clone(),
+                ability: action.ability.clone(),
+                kind: AbilityKindContract::from(action.kind),
+                targets: action.targets.iter().map(AbilityTargetContract::from).collect(),
+                path: PointContract::path_of(&action.movement),
+            },
+            BattleAction::Heal(r_num, action) => BattleActionContract::Heal {
+                round: *r_num,
+                healer: action.healer.name.clone(),
+                healed: action.target.name.clone(),
+                amount: action.amount,
+                remaining_hp: action.target.hp,
+                max_hp: action.target.max_hp,
+                item: action.item.clone(),
+            },
+            BattleAction::Revive(r_num, combatant, location) => BattleActionContract::Revive {
+                round: *r_num,
+                combatant: combatant.name.clone(),
+                location: PointContract::from(*location),
+                hp: combatant.hp,
+                max_hp: combatant.max_hp,
+            },
+        }
+    }
+}
+
+impl From<BattleResult> for BattleResultContract {
+    fn from(value: BattleResult) -> Self {
+        Self {
+            id: value.id.clone(),
+            unrecorded: value.unrecorded.clone(),
+            seed: value.seed,
+            combatants: value
+                .combatants
+                .iter()
+                .map(|c| CombatantContract::from(c))
+                .collect(),
+            map: MapContract::from(value.map),
+            actions: value
+                .actions
+                .iter()
+                .map(|action| BattleActionContract::from(action))
+                .collect(),
+            winner: CombatantContract::from_option(value.winner),
+            round_number: value.round_number,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v2/battle",
+    tag = "battle",
+    request_body = CreateBattleContract,
+    responses(
+        (status = 200, description = "The course and outcome of the battle", body = v2::BattleResultContract),
+        (status = 400, description = "The battle can not be set up"),
+        (status = 401, description = "API key missing or unknown"),
+        (status = 409, description = "Two combatants were placed on the same location")
+    ),
+    security(("api_key" = []))
+)]
+#[post("/", format = "json", data = "<post_data>")]
+pub async fn start_new_battle(
+    post_data: Json<CreateBattleContract>,
+    config: &State<AppConfig>,
+    db: &State<Storage>,
+    user: AuthenticatedUser,
+) -> ApiResponse {
+    match run_battle(post_data, config.inner(), db.inner(), &user).await {
+        Ok(result) => match serde_json::to_string(&BattleResultContract::from(result)) {
+            Ok(json) => ApiResponse {
+                json,
+                status: Status::Ok,
+                etag: None,
+            },
+            Err(_) => ApiResponse::empty(Status::InternalServerError),
+        },
+        Err(response) => response,
+    }
+}
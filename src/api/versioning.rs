@@ -0,0 +1,162 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::uri::Origin,
+    Data, Request, Response,
+};
+
+/// All API versions that are served, the version is the first segment of the path (/v1/combatants)
+pub const API_VERSIONS: [&str; 2] = ["v1", "v2"];
+
+// Used for requests to unversioned paths without a version in the Accept header
+const DEFAULT_API_VERSION: &str = "v1";
+
+// The resources that exist in every API version
+const VERSIONED_RESOURCES: [&str; 7] = [
+    "battlefields",
+    "combatants",
+    "items",
+    "tournaments",
+    "leaderboard",
+    "battle",
+    "users",
+];
+
+/// Reads the API version from a vendor media type in the Accept header
+///
+/// `Accept: application/vnd.ribs.v2+json` selects the second version
+pub fn version_from_accept(accept: &str) -> Option<String> {
+    accept
+        .split(',')
+        .map(|media_type| media_type.split(';').next().unwrap_or("").trim())
+        .find_map(|media_type| {
+            media_type
+                .strip_prefix("application/vnd.ribs.")
+                .and_then(|t| t.strip_suffix("+json"))
+                .map(|version| version.to_lowercase())
+        })
+}
+
+/// Routes requests to unversioned paths to the version selected in the Accept header
+///
+/// `/combatants` is served by `/v1/combatants` unless another version is asked for.
+/// Paths that already contain a version are left as they are, the path takes precedence over the header.
+pub struct ApiVersioning;
+
+#[rocket::async_trait]
+impl Fairing for ApiVersioning {
+    fn info(&self) -> Info {
+        Info {
+            name: "API Versioning",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let path = request.uri().path().to_string();
+        let resource = path.trim_start_matches('/').split('/').next().unwrap_or("");
+
+        if !VERSIONED_RESOURCES.contains(&resource) {
+            return;
+        }
+
+        let version = request
+            .headers()
+            .get_one("Accept")
+            .and_then(version_from_accept)
+            .unwrap_or(String::from(DEFAULT_API_VERSION));
+
+        let versioned_uri = match request.uri().query() {
+            Some(query) => format!("/{}{}?{}", version, path, query),
+            None => format!("/{}{}", version, path),
+        };
+
+        match Origin::parse_owned(versioned_uri) {
+            Ok(uri) => request.set_uri(uri),
+            Err(e) => warn!("could not route {} to API version {}: {}", path, version, e),
+        }
+    }
+
+    /// Tells the client which version answered the request
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let path = request.uri().path().to_string();
+        let version = path.trim_start_matches('/').split('/').next().unwrap_or("");
+
+        if API_VERSIONS.contains(&version) {
+            response.set_raw_header("Api-Version", version.to_owned());
+            response.set_raw_header("Vary", "Accept");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{ContentType, Header, Status};
+
+    use super::*;
+    use crate::api::test_client::{api_key, client, ADMIN_KEY, BATTLE};
+
+    #[test]
+    fn version_from_vendor_media_type() {
+        assert_eq!(
+            Some(String::from("v2")),
+            version_from_accept("application/vnd.ribs.v2+json")
+        );
+    }
+
+    #[test]
+    fn version_from_media_type_list() {
+        assert_eq!(
+            Some(String::from("v1")),
+            version_from_accept("text/html, application/vnd.ribs.v1+json;q=0.9, */*")
+        );
+    }
+
+    #[test]
+    fn version_from_plain_json() {
+        assert_eq!(None, version_from_accept("application/json"));
+    }
+
+    #[test]
+    fn unversioned_path_defaults_to_v1() {
+        let client = client();
+
+        let response = client.get("/combatants").header(api_key(ADMIN_KEY)).dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        assert_eq!(Some("v1"), response.headers().get_one("Api-Version"));
+    }
+
+    #[test]
+    fn battle_v2_selected_by_accept_header() {
+        let client = client();
+
+        let response = client
+            .post("/battle")
+            .header(ContentType::JSON)
+            .header(Header::new("Accept", "application/vnd.ribs.v2+json"))
+            .header(api_key(ADMIN_KEY))
+            .body(BATTLE)
+            .dispatch();
+
+        assert_eq!(Some("v2"), response.headers().get_one("Api-Version"));
+        let result: serde_json::Value = response.into_json().unwrap();
+        assert!(result["actions"][0]["type"].is_string());
+    }
+
+    #[test]
+    fn battle_v1_by_path() {
+        let client = client();
+
+        let response = client
+            .post("/v1/battle")
+            .header(ContentType::JSON)
+            .header(Header::new("Accept", "application/vnd.ribs.v2+json"))
+            .header(api_key(ADMIN_KEY))
+            .body(BATTLE)
+            .dispatch();
+
+        assert_eq!(Some("v1"), response.headers().get_one("Api-Version"));
+        let result: serde_json::Value = response.into_json().unwrap();
+        assert!(result["actions"][0]["type"].is_null());
+    }
+}
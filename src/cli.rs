@@ -0,0 +1,418 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{stdout, IsTerminal},
+    path::{Path, PathBuf},
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+
+use crate::{
+    api::{
+        battle::{play_battle, BattleResultContract, CreateBattleContract},
+        battle_log::{BattleLogContract, LogFormat},
+    },
+    config::{self, AppConfig},
+    engine::{battle_result::BattleResult, battle_rules::BattleRules, replay::Replay},
+    render::text::{TextRenderer, TextStyle},
+};
+
+/// A simple battle system with a simple REST API
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Starts the REST API, the default without a command
+    Serve,
+    /// Draws every round of an exported battle log
+    Replay {
+        /// The log, as exported from /battle/{id}/log
+        log: PathBuf,
+        /// Only draws the end of this round
+        #[arg(long)]
+        round: Option<u32>,
+        /// jsonl or cbor, taken from the file extension if not set
+        #[arg(long)]
+        format: Option<LogFormat>,
+        #[arg(long, value_enum, default_value_t = Color::Auto)]
+        color: Color,
+    },
+    /// Fights the battle of a file without the REST API and its storage
+    Simulate {
+        /// A battle as sent to /battle, the equipment of the combatants is ignored
+        battle: PathBuf,
+        /// The seed of the first battle, every following battle uses the next seed
+        #[arg(long)]
+        seed: Option<u64>,
+        /// How often the battle is fought, more than one run prints how often every combatant won
+        #[arg(long, default_value_t = 1)]
+        runs: u32,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Color {
+    /// Colors when printing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Text,
+}
+
+/// How often every combatant won the simulated battles
+#[derive(Serialize)]
+struct SimulationContract {
+    runs: u32,
+    /// The seed of the first battle, every following battle used the next seed
+    seed: u64,
+    wins: Vec<WinsContract>,
+    /// Battles no one survived
+    draws: u32,
+    /// Battles the engine could not finish, with the reason
+    failures: Vec<String>,
+    average_rounds: f64,
+}
+
+#[derive(Serialize)]
+struct WinsContract {
+    combatant: String,
+    team: Option<String>,
+    wins: u32,
+}
+
+/// Runs a command that does not need the REST API
+pub fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Serve => Ok(()),
+        Command::Replay {
+            log,
+            round,
+            format,
+            color,
+        } => {
+            let style = match color {
+                Color::Always => TextStyle::Ansi,
+                Color::Auto if stdout().is_terminal() => TextStyle::Ansi,
+                _ => TextStyle::Plain,
+            };
+
+            print!("{}", replay(&log, round, format, style)?);
+            Ok(())
+        }
+        Command::Simulate {
+            battle,
+            seed,
+            runs,
+            format,
+        } => {
+            let config = AppConfig::load(&config::figment()).map_err(|problems| problems.join("\n"))?;
+
+            print!("{}", simulate(&battle, BattleRules::from(&config.battle), seed, runs, format)?);
+            Ok(())
+        }
+    }
+}
+
+/// The result of a single battle, or how often every combatant won for more than one run
+fn simulate(path: &Path, rules: BattleRules, seed: Option<u64>, runs: u32, format: OutputFormat) -> Result<String, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut battle: CreateBattleContract =
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let first_seed = seed.or(battle.seed).unwrap_or_else(rand::random);
+    let fight = |battle: &CreateBattleContract| {
+        play_battle(battle, rules, &HashMap::new()).map_err(|response| match response.json.is_empty() {
+            true => format!("the battle could not be fought: {}", response.status),
+            false => format!("the battle could not be fought: {}", response.json),
+        })
+    };
+
+    if runs <= 1 {
+        battle.seed = Some(first_seed);
+        let result = fight(&battle)?;
+
+        return match format {
+            OutputFormat::Json => serde_json::to_string_pretty(&BattleResultContract::from(result))
+                .map(|json| json + "\n")
+                .map_err(|e| e.to_string()),
+            OutputFormat::Text => Ok(summary(&result)),
+        };
+    }
+
+    let mut wins: Vec<WinsContract> = battle
+        .combatants
+        .iter()
+        .map(|c| WinsContract {
+            combatant: c.name.clone(),
+            team: c.team.clone(),
+            wins: 0,
+        })
+        .collect();
+    let (mut draws, mut failures, mut rounds) = (0, vec![], 0u64);
+
+    for run in 0..runs {
+        let seed = first_seed.wrapping_add(run as u64);
+        battle.seed = Some(seed);
+
+        match fight(&battle) {
+            Ok(result) => {
+                rounds += result.round_number as u64;
+                match result.winner.and_then(|w| wins.iter_mut().find(|c| c.combatant == w.name)) {
+                    Some(winner) => winner.wins += 1,
+                    None => draws += 1,
+                }
+            }
+            Err(message) => failures.push(format!("seed {}: {}", seed, message)),
+        }
+    }
+
+    let finished = runs - failures.len() as u32;
+    let simulation = SimulationContract {
+        runs,
+        seed: first_seed,
+        wins,
+        draws,
+        failures,
+        average_rounds: if finished > 0 { rounds as f64 / finished as f64 } else { 0.0 },
+    };
+
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(&simulation)
+            .map(|json| json + "\n")
+            .map_err(|e| e.to_string()),
+        OutputFormat::Text => Ok(statistics(&simulation)),
+    }
+}
+
+/// The map at the end of the battle and who won
+fn summary(result: &BattleResult) -> String {
+    let frame = TextRenderer::default().frame(result.round_number, &result.map, &result.combatants);
+    let outcome = match &result.winner {
+        Some(winner) => format!("{} won after {} rounds", winner.name, result.round_number),
+        None => format!("No one survived {} rounds", result.round_number),
+    };
+
+    format!("{}\n{}\nSeed {}\n", frame, outcome, result.seed)
+}
+
+fn statistics(simulation: &SimulationContract) -> String {
+    let name_width = simulation.wins.iter().map(|w| w.combatant.chars().count()).max().unwrap_or(0);
+    let mut lines: Vec<String> = vec![format!(
+        "{} battles from seed {}, {:.1} rounds on average",
+        simulation.runs, simulation.seed, simulation.average_rounds
+    )];
+
+    for wins in simulation.wins.iter() {
+        lines.push(format!(
+            "{:<width$}  {:>5} wins  {:>5.1}%",
+            wins.combatant,
+            wins.wins,
+            wins.wins as f64 * 100.0 / simulation.runs as f64,
+            width = name_width
+        ));
+    }
+    lines.push(format!("{} draws", simulation.draws));
+    for failure in simulation.failures.iter() {
+        lines.push(format!("failed with {}", failure));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// The frames of the given round, or of every round from the start to the end of the battle
+fn replay(path: &Path, round: Option<u32>, format: Option<LogFormat>, style: TextStyle) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let format = format.unwrap_or(match path.extension().and_then(|e| e.to_str()) {
+        Some("cbor") => LogFormat::Cbor,
+        _ => LogFormat::JsonLines,
+    });
+
+    let log = BattleLogContract::decode(&bytes, format)?;
+    let (initial, _, actions) = log.to_battle().map_err(|response| response.json)?;
+
+    let renderer = TextRenderer::new(style);
+    let frames: Vec<String> = match round {
+        Some(r) if r > log.header.rounds => {
+            return Err(format!("the battle lasted {} rounds only", log.header.rounds));
+        }
+        Some(r) => {
+            let state = Replay::new(initial).state_at(&actions, r).map_err(|e| e.to_string())?;
+            vec![renderer.frame(r, &state.map, &state.combatants)]
+        }
+        None => {
+            let mut frames: Vec<String> = vec![];
+            Replay::new(initial)
+                .visit_rounds(&actions, log.header.rounds, |s| {
+                    frames.push(renderer.frame(s.round_number, &s.map, &s.combatants))
+                })
+                .map_err(|e| e.to_string())?;
+            frames
+        }
+    };
+
+    Ok(frames.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::api::battle_log::{
+        BattleLogCombatantContract, BattleLogEventContract, BattleLogHeaderContract, BattleLogMapContract,
+        BattleLogMovementContract, BattleLogRulesContract, LOG_FORMAT, LOG_VERSION,
+    };
+    use crate::api::battle::{PointContract, TopologyContract};
+    use crate::engine::battle_rules::BattleRules;
+    use crate::types::point::Point;
+
+    fn point(x: u16) -> PointContract {
+        PointContract::from(Point::new(x, 0))
+    }
+
+    fn write_log(name: &str, format: LogFormat) -> PathBuf {
+        let combatant = |name: &str, x: u16| BattleLogCombatantContract {
+            name: String::from(name),
+            id: None,
+            avatar: None,
+            team: None,
+            hp: 10,
+            max_hp: 10,
+            dmg: 5,
+            armor: 0,
+            attack_range: 1,
+            speed: 0,
+            effects: vec![],
+            on_hit: vec![],
+            abilities: vec![],
+            consumables: vec![],
+            location: point(x),
+        };
+        let log = BattleLogContract {
+            header: BattleLogHeaderContract {
+                format: String::from(LOG_FORMAT),
+                version: LOG_VERSION,
+                id: None,
+                fought_at: None,
+                seed: 0,
+                rules: BattleLogRulesContract::from(&BattleRules::default()),
+                map: BattleLogMapContract {
+                    width: 4,
+                    height: 1,
+                    topology: TopologyContract::Square4,
+                },
+                combatants: vec![combatant("first", 0), combatant("second", 3)],
+                rounds: 2,
+                winner: None,
+            },
+            events: vec![
+                BattleLogEventContract::Move {
+                    round: 1,
+                    combatant: String::from("first"),
+                    movement: BattleLogMovementContract {
+                        start: point(0),
+                        goal: point(3),
+                        path: vec![point(1), point(2)],
+                    },
+                },
+                BattleLogEventContract::Attack {
+                    round: 2,
+                    attacker: String::from("first"),
+                    attacked: String::from("second"),
+                    damage: 5,
+                    remaining_hp: 5,
+                },
+            ],
+        };
+
+        let path = std::env::temp_dir().join(format!("ribs-cli-{}-{}", std::process::id(), name));
+        fs::write(&path, log.encode(format).unwrap()).unwrap();
+
+        path
+    }
+
+    const BATTLE: &str = r#"{
+        "map": {"height": 8, "width": 8},
+        "combatants": [
+            {"name": "first", "hp": 10, "dmg": 5, "id": null, "avatar": null},
+            {"name": "second", "hp": 10, "dmg": 3, "id": null, "avatar": null}
+        ]
+    }"#;
+
+    fn write_battle(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ribs-cli-{}-{}", std::process::id(), name));
+        fs::write(&path, BATTLE).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn simulate_same_seed_same_battle() {
+        let path = write_battle("seeded.json");
+
+        let first = simulate(&path, BattleRules::default(), Some(42), 1, OutputFormat::Json).unwrap();
+        let second = simulate(&path, BattleRules::default(), Some(42), 1, OutputFormat::Json).unwrap();
+        let text = simulate(&path, BattleRules::default(), Some(42), 1, OutputFormat::Text).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let result: serde_json::Value = serde_json::from_str(&first).unwrap();
+        assert_eq!(42, result["seed"]);
+        assert_eq!(first, second);
+        assert!(text.starts_with(&format!("Round {}\n", result["round_number"])));
+        assert!(text.ends_with("Seed 42\n"));
+    }
+
+    #[test]
+    fn simulate_many_runs() {
+        let path = write_battle("runs.json");
+
+        let json = simulate(&path, BattleRules::default(), Some(7), 20, OutputFormat::Json).unwrap();
+        let text = simulate(&path, BattleRules::default(), Some(7), 20, OutputFormat::Text).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let simulation: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let wins: u64 = simulation["wins"].as_array().unwrap().iter().map(|w| w["wins"].as_u64().unwrap()).sum();
+        let failures = simulation["failures"].as_array().unwrap().len() as u64;
+        assert_eq!(7, simulation["seed"]);
+        assert_eq!(20, wins + simulation["draws"].as_u64().unwrap() + failures);
+        assert!(text.starts_with("20 battles from seed 7, "));
+        assert!(text.contains("\nfirst  "));
+    }
+
+    #[test]
+    fn replay_every_round() {
+        let path = write_log("every.jsonl", LogFormat::JsonLines);
+
+        let frames = replay(&path, None, None, TextStyle::Plain).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert!(frames.starts_with("Round 0\nA . . B\n"));
+        assert!(frames.contains("Round 1\n. . A B\n"));
+        assert!(frames.contains("Round 2\n. . A B\n\nA first   ##########  10/10\nB second  #####.....   5/10\n"));
+    }
+
+    #[test]
+    fn replay_single_round_of_cbor() {
+        let path = write_log("single.cbor", LogFormat::Cbor);
+
+        let frame = replay(&path, Some(1), None, TextStyle::Plain).unwrap();
+        let beyond = replay(&path, Some(3), None, TextStyle::Plain);
+        fs::remove_file(path).unwrap();
+
+        assert!(frame.starts_with("Round 1\n. . A B\n"));
+        assert!(!frame.contains("Round 0"));
+        assert!(beyond.is_err());
+    }
+}
@@ -0,0 +1,435 @@
+use rocket::figment::{
+    providers::{Env, Format, Serialized, Toml},
+    Figment, Profile,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::{
+        battle_rules::{BattleRules, ReviveRules},
+        progression::ProgressionRules,
+    },
+    types::topology::Topology,
+};
+
+// The environment variables supported before the configuration files existed, and where they end up
+const LEGACY_ENV: [(&str, &str); 8] = [
+    ("db_address", "database.address"),
+    ("db_username", "database.user"),
+    ("db_password", "database.pass"),
+    ("db_namespace", "database.namespace"),
+    ("db_name", "database.database"),
+    ("db_engine", "database.engine"),
+    ("db_connect_retries", "database.connect_retries"),
+    ("db_health_interval", "database.health_interval"),
+];
+
+const LOG_LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+
+// Shorter API keys are too easy to guess
+const MIN_API_KEY_LENGTH: usize = 16;
+
+/// The storage backends the application can run on
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageEngine {
+    SurrealDb,
+    Memory,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DbConfig {
+    pub engine: StorageEngine,
+    pub namespace: String,
+    pub database: String,
+    pub user: String,
+    pub pass: String,
+    pub address: String,
+    /// How many times the connection is retried at application start
+    pub connect_retries: u32,
+    /// The number of seconds between two connection health checks
+    pub health_interval: u64,
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            engine: StorageEngine::SurrealDb,
+            namespace: String::from("development"),
+            database: String::from("ribs"),
+            user: String::from("root"),
+            pass: String::from("root"),
+            address: String::from("127.0.0.1:8000"),
+            connect_retries: 10,
+            health_interval: 5,
+        }
+    }
+}
+
+/// How the locations of battle maps are connected
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TopologyConfig {
+    #[default]
+    Square4,
+    Square8,
+    Hex,
+}
+
+impl From<TopologyConfig> for Topology {
+    fn from(value: TopologyConfig) -> Self {
+        match value {
+            TopologyConfig::Square4 => Topology::Square4,
+            TopologyConfig::Square8 => Topology::Square8,
+            TopologyConfig::Hex => Topology::Hex,
+        }
+    }
+}
+
+/// The default rules for battles started through the API
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BattleConfig {
+    pub max_rounds: u32,
+    pub max_combatant_move: usize,
+    /// Dead combatants stay dead if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revive: Option<ReviveConfig>,
+    /// Used by battles that do not choose a topology themselves
+    #[serde(default)]
+    pub topology: TopologyConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ReviveConfig {
+    pub after_rounds: u32,
+    pub hp_percent: u8,
+    pub max_revives: u32,
+}
+
+impl Default for BattleConfig {
+    fn default() -> Self {
+        let rules = BattleRules::default();
+        Self {
+            max_rounds: rules.max_rounds,
+            max_combatant_move: rules.max_combatant_move,
+            revive: None,
+            topology: TopologyConfig::default(),
+        }
+    }
+}
+
+impl From<&BattleConfig> for BattleRules {
+    fn from(value: &BattleConfig) -> Self {
+        BattleRules {
+            max_rounds: value.max_rounds,
+            max_combatant_move: value.max_combatant_move,
+            revive: value.revive.as_ref().map(|r| ReviveRules {
+                after_rounds: r.after_rounds,
+                hp_percent: r.hp_percent,
+                max_revives: r.max_revives,
+            }),
+            topology: Topology::from(value.topology),
+            seed: None,
+        }
+    }
+}
+
+/// The experience stored combatants gain from battles and what each level is worth
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProgressionConfig {
+    pub xp_per_kill: u32,
+    pub xp_for_survival: u32,
+    pub xp_for_win: u32,
+    pub xp_per_level: u32,
+    pub hp_per_level: u16,
+    pub dmg_per_level: u16,
+}
+
+impl Default for ProgressionConfig {
+    fn default() -> Self {
+        let rules = ProgressionRules::default();
+        Self {
+            xp_per_kill: rules.xp_per_kill,
+            xp_for_survival: rules.xp_for_survival,
+            xp_for_win: rules.xp_for_win,
+            xp_per_level: rules.xp_per_level,
+            hp_per_level: rules.hp_per_level,
+            dmg_per_level: rules.dmg_per_level,
+        }
+    }
+}
+
+impl From<&ProgressionConfig> for ProgressionRules {
+    fn from(value: &ProgressionConfig) -> Self {
+        ProgressionRules {
+            xp_per_kill: value.xp_per_kill,
+            xp_for_survival: value.xp_for_survival,
+            xp_for_win: value.xp_for_win,
+            xp_per_level: value.xp_per_level,
+            hp_per_level: value.hp_per_level,
+            dmg_per_level: value.dmg_per_level,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LoggingConfig {
+    /// The max log level, overridden by the RUST_LOG environment variable
+    pub level: String,
+    /// Either stdout or stderr
+    pub target: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: String::from("info"),
+            target: String::from("stdout"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AuthConfig {
+    /// If disabled every request is treated as coming from an admin
+    pub enabled: bool,
+    /// The API key of the admin user created at startup, no admin is created if not set
+    pub admin_api_key: Option<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            admin_api_key: None,
+        }
+    }
+}
+
+/// All application settings that are not handled by Rocket itself
+///
+/// The server settings (address, port, ...) are read by Rocket from the same sources
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct AppConfig {
+    pub database: DbConfig,
+    pub battle: BattleConfig,
+    pub progression: ProgressionConfig,
+    pub logging: LoggingConfig,
+    pub auth: AuthConfig,
+}
+
+impl AppConfig {
+    /// Loads and validates the configuration from the given figment
+    pub fn load(figment: &Figment) -> Result<Self, Vec<String>> {
+        let config: AppConfig = figment.extract().map_err(|e| vec![e.to_string()])?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks the configuration values that can not be expressed through the types alone
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems: Vec<String> = vec![];
+
+        if self.database.engine == StorageEngine::SurrealDb && self.database.address.trim().is_empty() {
+            problems.push(String::from("database.address can not be empty"));
+        }
+        if self.database.health_interval == 0 {
+            problems.push(String::from("database.health_interval has to be at least 1 second"));
+        }
+        if self.battle.max_rounds == 0 {
+            problems.push(String::from("battle.max_rounds has to be at least 1"));
+        }
+        if self.battle.max_combatant_move == 0 {
+            problems.push(String::from("battle.max_combatant_move has to be at least 1"));
+        }
+        if let Some(revive) = &self.battle.revive {
+            if revive.hp_percent == 0 || revive.hp_percent > 100 {
+                problems.push(String::from("battle.revive.hp_percent has to be between 1 and 100"));
+            }
+        }
+        if self.progression.xp_per_level == 0 {
+            problems.push(String::from("progression.xp_per_level has to be at least 1"));
+        }
+        if !LOG_LEVELS.contains(&self.logging.level.to_lowercase().as_str()) {
+            problems.push(format!(
+                "logging.level {} is not one of {}",
+                self.logging.level,
+                LOG_LEVELS.join(", ")
+            ));
+        }
+        if let Some(key) = &self.auth.admin_api_key {
+            if key.len() < MIN_API_KEY_LENGTH {
+                problems.push(format!(
+                    "auth.admin_api_key has to be at least {} characters long",
+                    MIN_API_KEY_LENGTH
+                ));
+            }
+        }
+        if !["stdout", "stderr"].contains(&self.logging.target.to_lowercase().as_str()) {
+            problems.push(format!(
+                "logging.target {} is not one of stdout, stderr",
+                self.logging.target
+            ));
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(problems),
+        }
+    }
+}
+
+/// Combines all configuration sources, later ones override earlier ones
+///
+/// 1. The built in defaults
+/// 2. App.toml (or the file given in RIBS_CONFIG)
+/// 3. Rocket.toml (or the file given in ROCKET_CONFIG)
+/// 4. The deprecated db_* environment variables
+/// 5. ROCKET_* and RIBS_* environment variables (nested keys are separated by __, e.g. RIBS_DATABASE__ADDRESS)
+///
+/// Environment variables apply to all profiles, so the db_* ones are only overridden by the RIBS_* ones.
+///
+/// The profile ([debug] or [release]) is selected with ROCKET_PROFILE
+pub fn figment() -> Figment {
+    Figment::from(rocket::Config::default())
+        .merge(Serialized::defaults(AppConfig::default()))
+        .merge(Toml::file(Env::var_or("RIBS_CONFIG", "App.toml")).nested())
+        .merge(Toml::file(Env::var_or("ROCKET_CONFIG", "Rocket.toml")).nested())
+        .merge(Env::raw().filter_map(|key| {
+            LEGACY_ENV
+                .iter()
+                .find(|(env_name, _)| key == *env_name)
+                .map(|(_, path)| (*path).into())
+        }).global())
+        .merge(Env::prefixed("ROCKET_").ignore(&["PROFILE", "CONFIG"]).global())
+        .merge(Env::prefixed("RIBS_").ignore(&["CONFIG"]).split("__").global())
+        .select(Profile::from_env_or(
+            "ROCKET_PROFILE",
+            rocket::Config::DEFAULT_PROFILE,
+        ))
+}
+
+/// A warning for every deprecated db_* environment variable that is set, naming the variable replacing it
+pub fn deprecated_env() -> Vec<String> {
+    std::env::vars()
+        .filter_map(|(key, _)| {
+            LEGACY_ENV
+                .iter()
+                .find(|(env_name, _)| key.to_lowercase() == *env_name)
+                .map(|(env_name, path)| {
+                    format!(
+                        "the environment variable {} is deprecated, use RIBS_{} instead",
+                        env_name,
+                        path.replace('.', "__").to_uppercase()
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn validate_short_admin_api_key() {
+        let mut config = AppConfig::default();
+        config.auth.admin_api_key = Some(String::from("secret"));
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn default_is_valid() {
+        assert!(AppConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)] // figment jails run closures returning figment errors
+    fn shipped_configuration_is_valid() {
+        // The jail changes the working directory, like the environment it is only entered by one test at a time
+        Jail::expect_with(|jail| {
+            jail.create_file("App.toml", include_str!("../App.toml"))?;
+
+            let config = AppConfig::load(&figment());
+
+            assert!(config.is_ok(), "{:?}", config.err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn validate_zero_rounds() {
+        let mut config = AppConfig::default();
+        config.battle.max_rounds = 0;
+
+        let problems = config.validate().unwrap_err();
+
+        assert_eq!(1, problems.len());
+    }
+
+    #[test]
+    fn validate_revive_hp_percent() {
+        let mut config = AppConfig::default();
+        config.battle.revive = Some(ReviveConfig {
+            after_rounds: 2,
+            hp_percent: 120,
+            max_revives: 1,
+        });
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_zero_xp_per_level() {
+        let mut config = AppConfig::default();
+        config.progression.xp_per_level = 0;
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_unknown_log_level() {
+        let mut config = AppConfig::default();
+        config.logging.level = String::from("loud");
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_memory_engine_without_address() {
+        let mut config = AppConfig::default();
+        config.database.engine = StorageEngine::Memory;
+        config.database.address = String::new();
+
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)] // figment jails run closures returning figment errors
+    fn new_environment_variables_override_deprecated_ones() {
+        Jail::expect_with(|jail| {
+            jail.set_env("db_namespace", "legacy");
+            jail.set_env("RIBS_DATABASE__NAMESPACE", "current");
+            jail.set_env("db_name", "legacy");
+
+            // Like a value of the selected profile in App.toml
+            let config = AppConfig::load(
+                &figment()
+                    .merge(Serialized::default("database.database", "file").profile("debug"))
+                    .select("debug"),
+            )
+            .map_err(|problems| problems.join("\n"))?;
+
+            assert_eq!("current", config.database.namespace);
+            assert_eq!("legacy", config.database.database);
+            assert!(deprecated_env().contains(&String::from(
+                "the environment variable db_namespace is deprecated, use RIBS_DATABASE__NAMESPACE instead"
+            )));
+            Ok(())
+        });
+    }
+}
@@ -6,7 +6,15 @@ pub mod combatant;
 pub mod map;
 pub mod battle_actions;
 pub mod battle_result;
+pub mod battle_rules;
 pub mod combatant_turn;
+pub mod status_effect;
+pub mod ability;
+pub mod item;
+pub mod progression;
+pub mod rating;
+pub mod tournament;
+pub mod replay;
 
  // the maximal number of rounds to be played
  pub const MAX_ROUND_NUM: u32 = 1000;
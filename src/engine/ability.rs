@@ -0,0 +1,101 @@
+use super::status_effect::StatusEffect;
+
+/// What an ability does when it is used
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AbilityKind {
+    /// Hits every opponent within range
+    AreaAttack,
+    /// Moves up to range steps towards the closest opponent and hits it
+    Charge,
+    /// Heals the most wounded ally within range, the combatant itself included
+    HealAlly,
+    /// Protects the combatant once an opponent comes within range
+    Shield,
+}
+
+/// A special action a combatant can take instead of moving and attacking
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ability {
+    pub name: String,
+    pub kind: AbilityKind,
+    /// The maximal number of steps between the combatant and its targets
+    pub range: u32,
+    /// The number of turns the ability can not be used after it was used
+    pub cooldown: u32,
+    /// The damage dealt, the hit points healed or the damage absorbed by a shield
+    pub power: u16,
+    /// Applied to every target, a shield applies it to the combatant itself
+    pub effect: Option<StatusEffect>,
+    /// The number of turns until the ability can be used again
+    pub remaining_cooldown: u32,
+}
+
+impl Ability {
+    pub fn new(name: &str, kind: AbilityKind, range: u32, cooldown: u32, power: u16) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind,
+            range,
+            cooldown,
+            power,
+            effect: None,
+            remaining_cooldown: 0,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.remaining_cooldown == 0
+    }
+
+    /// Starts the cooldown after the ability was used
+    ///
+    /// Abilities cool down at the start of every turn of the combatant, the turn the ability was used in does not count.
+    pub fn trigger(&mut self) {
+        self.remaining_cooldown = self.cooldown + 1;
+    }
+
+    /// Brings the ability one turn closer to being ready
+    pub fn cool_down(&mut self) {
+        self.remaining_cooldown = self.remaining_cooldown.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ready_after_cooldown() {
+        let mut test_object = Ability::new("test", AbilityKind::AreaAttack, 2, 2, 3);
+
+        test_object.trigger();
+        assert!(!test_object.is_ready());
+
+        test_object.cool_down();
+        assert!(!test_object.is_ready());
+
+        test_object.cool_down();
+        assert!(!test_object.is_ready());
+
+        test_object.cool_down();
+        assert!(test_object.is_ready());
+    }
+
+    #[test]
+    fn blocked_for_cooldown_turns() {
+        for cooldown in 0..4 {
+            let mut test_object = Ability::new("test", AbilityKind::AreaAttack, 2, cooldown, 3);
+            test_object.trigger();
+
+            // every turn starts with cooling down
+            let blocked_turns = (0..10)
+                .take_while(|_| {
+                    test_object.cool_down();
+                    !test_object.is_ready()
+                })
+                .count();
+
+            assert_eq!(cooldown as usize, blocked_turns);
+        }
+    }
+}
@@ -1,9 +1,39 @@
-use super::{movement::MovementResult, combatant::Combatant};
+use crate::types::point::Point;
+
+use super::{
+    ability::AbilityKind,
+    combatant::Combatant,
+    movement::MovementResult,
+    status_effect::{StatusEffect, StatusEffectTick},
+};
 
 #[derive(Debug, Clone)]
 pub enum BattleAction{
     Move(u32,String, MovementResult),
-    Attack(u32, BattleAttackAction)
+    Attack(u32, BattleAttackAction),
+    /// An effect was put on the named combatant
+    EffectApplied(u32, String, StatusEffect),
+    /// An effect on the named combatant took place at the start of its turn
+    EffectTicked(u32, String, StatusEffectTick),
+    Ability(u32, BattleAbilityAction),
+    Heal(u32, BattleHealAction),
+    /// A dead combatant returned to the battle at the given location
+    Revive(u32, Combatant, Point),
+}
+
+impl BattleAction {
+    /// The round the action took place in
+    pub fn round(&self) -> u32 {
+        match self {
+            BattleAction::Move(r, _, _)
+            | BattleAction::Attack(r, _)
+            | BattleAction::EffectApplied(r, _, _)
+            | BattleAction::EffectTicked(r, _, _)
+            | BattleAction::Ability(r, _)
+            | BattleAction::Heal(r, _)
+            | BattleAction::Revive(r, _, _) => *r,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -11,4 +41,33 @@ pub struct BattleAttackAction{
     pub assailant: Combatant,
     pub victim: Combatant,
     pub damage: u16
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone)]
+pub struct BattleHealAction{
+    pub healer: Combatant,
+    pub target: Combatant,
+    /// The hit points gained
+    pub amount: u16,
+    /// The consumable used, not set for heals by abilities
+    pub item: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BattleAbilityAction{
+    pub user: String,
+    pub ability: String,
+    pub kind: AbilityKind,
+    pub targets: Vec<AbilityTarget>,
+    /// Only set for abilities that move the user
+    pub movement: Option<MovementResult>,
+}
+
+/// A combatant affected by an ability
+#[derive(Debug, Clone)]
+pub struct AbilityTarget{
+    pub name: String,
+    /// The damage dealt or hit points healed
+    pub amount: u16,
+    pub remaining_hp: u16,
+}
@@ -1,24 +1,34 @@
+use rand::{rngs::StdRng, SeedableRng};
+
 use super::{
     battle_actions::BattleAction,
     battlefield::BattlefieldData,
     combatant::Combatant,
     err::Error,
     map::Map,
-    battle_result::BattleResult, MAX_ROUND_NUM, combatant_turn::{CombatantTurn, CombatantTurnResult},
+    battle_result::BattleResult, battle_rules::{BattleRules, ReviveRules}, combatant_turn::CombatantTurn,
 };
 
 pub struct BattleEngine {
     map: Map,
     combatants: Vec<Combatant>,
     round_counter: u32,
+    rules: BattleRules,
+    seed: u64,
+    /// Every random decision of the battle is drawn from this generator
+    rng: StdRng,
 }
 
-#[derive(Debug, Clone)]
-pub struct BattleRound {
+/// A single round played on the state of the battle
+#[derive(Debug)]
+pub struct BattleRound<'a> {
     round_number: u32,
-    state: BattleRoundState,
+    state: &'a mut BattleRoundState,
+    rules: BattleRules,
+    rng: &'a mut StdRng,
 }
 
+/// The state of the battle, changed in place by every turn while the actions are only ever appended
 #[derive(Debug, Clone)]
 pub struct BattleRoundState {
     pub combatants: Vec<Combatant>,
@@ -28,13 +38,13 @@ pub struct BattleRoundState {
 }
 
 impl BattleRoundState {
+    /// Determines if at least two living combatants are still opponents
     pub fn min_two_alive(&self) -> bool {
-        self.combatants
+        let alive: Vec<&Combatant> = self.combatants.iter().filter(|c| c.is_alive()).collect();
+
+        alive
             .iter()
-            .filter(|c| c.is_alive())
-            .collect::<Vec<&Combatant>>()
-            .len()
-            >= 2
+            .any(|c| alive.iter().any(|other| c.is_hostile_to(other)))
     }
 
     pub fn alive_combatants(&self) -> Vec<Combatant> {
@@ -48,120 +58,159 @@ impl BattleRoundState {
     pub fn get_combatant(&self, name: &str) -> Option<&Combatant> {
         self.combatants.iter().find(|c| c.name == name)
     }
+
+    /// Removes dead combatants from the map and remembers when they were found dead
+    pub fn bury_dead(&mut self, round_number: u32) {
+        for dead in self.combatants.iter_mut().filter(|c| !c.is_alive()) {
+            self.map.remove_poi(&dead.name);
+
+            if dead.died_in_round.is_none() {
+                dead.died_in_round = Some(round_number);
+            }
+        }
+    }
+
+    /// Places the dead combatants that waited long enough back on the map, as far as there is room for them
+    pub fn revive_dead(&mut self, round_number: u32, rules: &ReviveRules, rng: &mut StdRng) -> Result<(), Error> {
+        for dead in self.combatants.iter_mut().filter(|c| !c.is_alive()) {
+            let may_return = match dead.died_in_round {
+                Some(died) => died + rules.after_rounds <= round_number,
+                None => false,
+            };
+
+            if !may_return || dead.revived >= rules.max_revives {
+                continue;
+            }
+
+            match self.map.place_randomly_with(dead.name.clone(), rng) {
+                Ok(_) => dead.revive(rules.hp_percent),
+                // there is no room on a full map, the combatant returns in a later round instead
+                Err(Error::MapFull) => continue,
+                Err(e) => return Err(e),
+            }
+
+            if let Some(location) = self.map.position_for(&dead.name) {
+                self.actions
+                    .push(BattleAction::Revive(round_number, dead.clone(), location));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl BattleEngine {
 
-    pub fn new(battlefield_data: BattlefieldData) -> Result<Self, Error> {
+    pub fn new(battlefield_data: BattlefieldData, rules: BattleRules) -> Result<Self, Error> {
+        let seed: u64 = rules.seed.unwrap_or_else(rand::random);
         let mut instance = BattleEngine { 
-            map: Map::new(
+            map: Map::with_topology(
                 battlefield_data.battlefield_width,
                 battlefield_data.battlefield_height,
+                rules.topology,
             ),
-            combatants: battlefield_data.combatants.to_vec(),
+            combatants: battlefield_data
+                .combatants
+                .iter()
+                .cloned()
+                .map(|mut c| {
+                    c.max_hp = c.max_hp.max(c.hp);
+                    c
+                })
+                .collect(),
             round_counter: u32::MIN,
+            rules,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
         };
 
         for com in battlefield_data.combatants.clone() {
-            instance.map.place_randomly(com.name)?;
+            instance.map.place_randomly_with(com.name, &mut instance.rng)?;
         }
 
         return Ok(instance);
     }
 
     pub fn start_battle(&mut self) -> Result<BattleResult, Error> {
-        let mut current_battle_round_state: BattleRoundState = BattleRoundState {
+        let mut state: BattleRoundState = BattleRoundState {
             combatants: self.combatants.to_vec(),
             map: self.map.clone(),
             actions: vec![],
             round_number: self.round_counter
         };
 
-        while current_battle_round_state.min_two_alive()
-            && self.round_counter <= MAX_ROUND_NUM
+        let initial = state.clone();
+
+        while state.min_two_alive()
+            && self.round_counter <= self.rules.max_rounds
         {
             self.round_counter = self.round_counter + 1;
 
-            let result: BattleRoundState =
-                BattleRound::new(self.round_counter, current_battle_round_state).do_battle()?;
-            current_battle_round_state = result.clone();
+            BattleRound::new(self.round_counter, &mut state, self.rules, &mut self.rng).do_battle()?;
         }
 
-        let battle_result = BattleResult::new(current_battle_round_state).analyze_results();
+        let battle_result = BattleResult::new(initial, state, self.rules, self.seed).analyze_results();
 
         return Ok(battle_result);
     }
 }
 
-impl BattleRound {
-    pub fn new(round_number: u32, initial_state: BattleRoundState) -> Self {
+impl<'a> BattleRound<'a> {
+    pub fn new(round_number: u32, state: &'a mut BattleRoundState, rules: BattleRules, rng: &'a mut StdRng) -> Self {
         BattleRound {
             round_number: round_number,
-            state: initial_state,
+            state,
+            rules,
+            rng,
         }
     }
 
-    pub fn do_battle(&self) -> Result<BattleRoundState, Error> {
-        let mut tmp_state = self.state.clone();
+    pub fn do_battle(self) -> Result<(), Error> {
+        // Only combatants alive at the start of the round act, revived combatants act from the next round on
+        let acting: Vec<usize> = (0..self.state.combatants.len())
+            .filter(|i| self.state.combatants[*i].is_alive())
+            .collect();
 
-        // remove dead combatants from the map!
-        for dead in self.state.dead_combatants() {
-            tmp_state.map = tmp_state.map.remove_poi(&dead.name);
-        }
+        self.state.round_number = self.round_number;
+        self.state.bury_dead(self.round_number);
 
-        for combatant in self.state.alive_combatants() {
-
-            let active_combatant = tmp_state.get_combatant(&combatant.name);
-
-            match active_combatant {
-                Some(ac) => {
-
-                    if !ac.is_alive(){
-                        continue;
-                    }
-
-                    let opponents = tmp_state
-                    .combatants
-                    .iter()
-                    .filter(|c| *c != ac)
-                    .cloned()
-                    .collect();
-    
-                let combatant_turn: CombatantTurnResult = CombatantTurn::new(
-                    ac.clone(),
-                    opponents,
-                    tmp_state.map.clone(),
-                    tmp_state.actions.to_vec(),
-                    self.round_number,
-                )
-                .execute()?;
-    
-                tmp_state = BattleRoundState::from(combatant_turn);
-                },
-                None => continue,
-            }
+        if let Some(revive) = self.rules.revive {
+            self.state.revive_dead(self.round_number, &revive, self.rng)?;
         }
 
-        return Ok(tmp_state);
-    }
-}
+        for index in acting {
+            if !self.state.combatants[index].is_alive() {
+                continue;
+            }
 
-impl From<CombatantTurnResult> for BattleRoundState {
-    fn from(value: CombatantTurnResult) -> Self {
-        let mut all_combatants = value.opponents.to_vec();
-        all_combatants.push(value.active_combatant);
-        BattleRoundState {
-            combatants: all_combatants,
-            map: value.map.clone(),
-            actions: value.actions.to_vec(),
-            round_number: value.round_number
+            // The active combatant is taken out while the others are lent to its turn, the order is restored afterwards
+            let mut active = self.state.combatants.swap_remove(index);
+            let result = CombatantTurn::new(
+                &mut active,
+                &mut self.state.combatants,
+                &mut self.state.map,
+                &mut self.state.actions,
+                self.round_number,
+                self.rules,
+                self.rng,
+            )
+            .execute();
+
+            self.state.combatants.push(active);
+            let last = self.state.combatants.len() - 1;
+            self.state.combatants.swap(index, last);
+
+            result?;
         }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::engine::ability::{Ability, AbilityKind};
 
     #[test]
     fn do_battle() {
@@ -173,19 +222,22 @@ mod tests {
                     dmg: 2,
                     hp: 15,
                     name: "test1".to_owned(),
+                    ..Default::default()
                 },
                 Combatant {
                     dmg: 4,
                     hp: 10,
                     name: "test2".to_owned(),
+                    ..Default::default()
                 },
                 Combatant {
                     dmg: 2,
                     hp: 15,
                     name: "test3".to_owned(),
+                    ..Default::default()
                 },
             ],
-        });
+        }, BattleRules::default());
 
         let results = engine.unwrap().start_battle();
 
@@ -194,14 +246,180 @@ mod tests {
         let r = results.unwrap();
 
         let foo = r.actions.iter().any(|a| match a {
-            BattleAction::Move(_, _, _) => false,
             BattleAction::Attack(_r, attack) => attack.victim.hp == 0,
+            _ => false,
         });
 
         assert!(foo);
 
         assert_eq!(3, r.combatants.len());
     }
+
+    #[test]
+    fn same_seed_same_battle() {
+        let battle = || {
+            let combatants = (0..6)
+                .map(|i| Combatant {
+                    name: format!("test{}", i),
+                    hp: 10,
+                    dmg: 1 + i % 3,
+                    ..Default::default()
+                })
+                .collect();
+            let rules = BattleRules { seed: Some(42), ..Default::default() };
+
+            BattleEngine::new(BattlefieldData { battlefield_height: 20, battlefield_width: 20, combatants }, rules)
+                .unwrap()
+                .start_battle()
+        };
+
+        let (first, second) = (battle(), battle());
+
+        match (first, second) {
+            (Ok(first), Ok(second)) => {
+                assert_eq!(42, first.seed);
+                assert_eq!(first.actions.len(), second.actions.len());
+                let hp = |r: &BattleResult| r.combatants.iter().map(|c| c.hp).collect::<Vec<u16>>();
+                assert_eq!(hp(&first), hp(&second));
+                assert_eq!(first.map.get_pois().len(), second.map.get_pois().len());
+                for (name, location) in first.initial.map.get_pois().iter().map(|(p, n)| (n, p)) {
+                    assert_eq!(Some(*location), second.initial.map.position_for(name));
+                }
+            }
+            (first, second) => assert_eq!(first.is_ok(), second.is_ok()),
+        }
+    }
+
+    #[test]
+    fn crowded_battle_with_teams_and_charge() {
+        for seed in 0..50 {
+            let combatants = (0..10)
+                .map(|i| Combatant {
+                    name: format!("test{}", i),
+                    hp: 20,
+                    dmg: 2,
+                    team: Some(format!("team{}", i % 2)),
+                    abilities: vec![Ability::new("Charge", AbilityKind::Charge, 6, 2, 3)],
+                    ..Default::default()
+                })
+                .collect();
+            let rules = BattleRules { seed: Some(seed), ..Default::default() };
+
+            let result = BattleEngine::new(BattlefieldData { battlefield_height: 8, battlefield_width: 8, combatants }, rules)
+                .unwrap()
+                .start_battle();
+
+            assert!(result.is_ok(), "seed {}: {:?}", seed, result.err());
+        }
+    }
+
+    fn dead_combatant(name: &str) -> Combatant {
+        Combatant {
+            name: name.to_owned(),
+            hp: 0,
+            max_hp: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn revive_dead_after_rounds() {
+        let rules = ReviveRules {
+            after_rounds: 2,
+            hp_percent: 50,
+            max_revives: 1,
+        };
+        let mut state = BattleRoundState {
+            combatants: vec![dead_combatant("test1")],
+            map: Map::new(10, 10),
+            actions: vec![],
+            round_number: 0,
+        };
+
+        state.bury_dead(1);
+        state.revive_dead(2, &rules, &mut StdRng::seed_from_u64(0)).unwrap();
+        assert!(!state.combatants[0].is_alive());
+
+        state.revive_dead(3, &rules, &mut StdRng::seed_from_u64(0)).unwrap();
+        assert_eq!(5, state.combatants[0].hp);
+        assert!(state.map.position_for("test1").is_some());
+        assert!(matches!(state.actions[0], BattleAction::Revive(3, _, _)));
+    }
+
+    #[test]
+    fn revive_dead_postponed_on_full_map() {
+        let rules = ReviveRules {
+            after_rounds: 0,
+            hp_percent: 50,
+            max_revives: 1,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut state = BattleRoundState {
+            combatants: vec![dead_combatant("test1")],
+            map: Map::new(1, 1),
+            actions: vec![],
+            round_number: 0,
+        };
+        state.map.place_randomly_with("test2".to_owned(), &mut rng).unwrap();
+
+        state.bury_dead(1);
+        state.revive_dead(1, &rules, &mut rng).unwrap();
+        assert!(!state.combatants[0].is_alive());
+        assert!(state.actions.is_empty());
+
+        state.map.remove_poi("test2");
+        state.revive_dead(2, &rules, &mut rng).unwrap();
+        assert!(state.combatants[0].is_alive());
+        assert!(matches!(state.actions[0], BattleAction::Revive(2, _, _)));
+    }
+
+    #[test]
+    fn revive_dead_only_max_revives() {
+        let rules = ReviveRules {
+            after_rounds: 0,
+            hp_percent: 50,
+            max_revives: 1,
+        };
+        let mut state = BattleRoundState {
+            combatants: vec![Combatant {
+                revived: 1,
+                ..dead_combatant("test1")
+            }],
+            map: Map::new(10, 10),
+            actions: vec![],
+            round_number: 0,
+        };
+
+        state.bury_dead(1);
+        state.revive_dead(1, &rules, &mut StdRng::seed_from_u64(0)).unwrap();
+
+        assert!(!state.combatants[0].is_alive());
+    }
+
+    #[test]
+    fn min_two_alive_only_allies() {
+        let state = BattleRoundState {
+            combatants: vec![
+                Combatant {
+                    name: "test1".to_owned(),
+                    hp: 10,
+                    team: Some("blue".to_owned()),
+                    ..Default::default()
+                },
+                Combatant {
+                    name: "test2".to_owned(),
+                    hp: 10,
+                    team: Some("blue".to_owned()),
+                    ..Default::default()
+                },
+            ],
+            map: Map::new(10, 10),
+            actions: vec![],
+            round_number: 0,
+        };
+
+        assert!(!state.min_two_alive());
+    }
 }
 
 
@@ -215,17 +433,20 @@ mod battle_round_tests {
         let combatant1 = Combatant {
             name: String::from("Combatant1"),
             dmg: 2,
-            hp: 10
+            hp: 10,
+            ..Default::default()
         };
         let combatant2 = Combatant{
             name: String::from("Combatant2"),
             dmg: 2,
-            hp: 10
+            hp: 10,
+            ..Default::default()
         };
         let combatant3 = Combatant{
             name: String::from("Combatant3"),
             dmg: 2,
-            hp: 10
+            hp: 10,
+            ..Default::default()
         };
 
         let mut map = Map::new(10, 10);
@@ -241,10 +462,11 @@ mod battle_round_tests {
             round_number: 1
         };
 
-        let test_object = BattleRound::new(2, initial_state);
+        let mut state = initial_state;
 
-        let test_result = test_object.do_battle().unwrap();
+        BattleRound::new(2, &mut state, BattleRules::default(), &mut StdRng::seed_from_u64(0)).do_battle().unwrap();
 
-        assert_ne!(0, test_result.actions.len(), "The number of actions can not be 0")
+        assert_ne!(0, state.actions.len(), "The number of actions can not be 0");
+        assert_eq!("Combatant1", state.combatants[0].name, "The order of the combatants has to be kept");
     }
 }
\ No newline at end of file
@@ -1,12 +1,22 @@
 use super::{
     battle_actions::BattleAction,
     battle_engine::BattleRoundState,
+    battle_rules::BattleRules,
     combatant::Combatant,
     map::Map,
 };
 
 #[derive(Debug, Clone)]
 pub struct BattleResult {
+    /// The state before the first round, the actions replay from it
+    pub initial: BattleRoundState,
+    /// The id the battle was stored under, not set for battles that were not stored
+    pub id: Option<String>,
+    /// The ids of the stored combatants whose progression could not be stored
+    pub unrecorded: Vec<String>,
+    pub rules: BattleRules,
+    /// Playing the initial state with the rules and this seed again leads to the same battle
+    pub seed: u64,
     pub combatants: Vec<Combatant>,
     pub map: Map,
     pub actions: Vec<BattleAction>,
@@ -16,8 +26,13 @@ pub struct BattleResult {
 }
 
 impl BattleResult {
-    pub fn new(state: BattleRoundState) -> Self {
+    pub fn new(initial: BattleRoundState, state: BattleRoundState, rules: BattleRules, seed: u64) -> Self {
         Self {
+            initial,
+            id: None,
+            unrecorded: vec![],
+            rules,
+            seed,
             combatants: state.combatants,
             map: state.map,
             actions: state.actions,
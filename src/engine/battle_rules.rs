@@ -0,0 +1,41 @@
+use crate::types::topology::Topology;
+
+use super::{MAX_COMBATANT_MOVE, MAX_ROUND_NUM};
+
+/// The rules a single battle is played by
+#[derive(Debug, Clone, Copy)]
+pub struct BattleRules {
+    /// The maximal number of rounds played before the battle ends in a draw
+    pub max_rounds: u32,
+    /// The number of steps each combatant can take in a single round
+    pub max_combatant_move: usize,
+    /// Dead combatants stay dead if not set
+    pub revive: Option<ReviveRules>,
+    /// How the locations of the map are connected
+    pub topology: Topology,
+    /// Battles with the same seed, combatants and rules play out the same, a random seed is drawn if not set
+    pub seed: Option<u64>,
+}
+
+/// When and how dead combatants return to the battle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReviveRules {
+    /// The number of rounds a combatant stays dead
+    pub after_rounds: u32,
+    /// The share of the maximal hit points a revived combatant starts with
+    pub hp_percent: u8,
+    /// How often a single combatant can be revived
+    pub max_revives: u32,
+}
+
+impl Default for BattleRules {
+    fn default() -> Self {
+        Self {
+            max_rounds: MAX_ROUND_NUM,
+            max_combatant_move: MAX_COMBATANT_MOVE,
+            revive: None,
+            topology: Topology::default(),
+            seed: None,
+        }
+    }
+}
@@ -1,10 +1,12 @@
 
+use crate::types::point::Coordinate;
+
 use super::combatant::Combatant;
 
 #[derive(Debug)]
 pub struct BattlefieldData {
-    pub battlefield_height: u8,
-    pub battlefield_width: u8,
+    pub battlefield_height: Coordinate,
+    pub battlefield_width: Coordinate,
     pub combatants: Vec<Combatant>,
 }
 
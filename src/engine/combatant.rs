@@ -1,12 +1,39 @@
+use super::{
+    ability::Ability,
+    item::{Item, ItemKind},
+    status_effect::{StatusEffect, StatusEffectKind, StatusEffectTick},
+};
+
 /// Represents a combatant in the battle.
 /// No alliances are represented and not positional data on the map is kept by this structure
-#[derive(Clone, Debug, PartialOrd, Eq, Ord)]
+#[derive(Clone, Debug, Default, PartialOrd, Eq, Ord)]
 pub struct Combatant{
     pub name: String,
     pub hp: u16,
+    /// Healing never raises the hit points above this value
+    pub max_hp: u16,
     pub dmg: u16,
     pub id: Option<String>,
     pub avatar: Option<String>,
+    /// The effects currently active on the combatant
+    pub effects: Vec<StatusEffect>,
+    /// The effects applied to every opponent the combatant hits
+    pub on_hit: Vec<StatusEffect>,
+    pub abilities: Vec<Ability>,
+    /// Combatants of the same team are allies and do not attack each other
+    pub team: Option<String>,
+    /// The round the combatant was found dead in, cleared when revived
+    pub died_in_round: Option<u32>,
+    /// How often the combatant was revived
+    pub revived: u32,
+    /// Subtracted from the damage of every hit taken
+    pub armor: u16,
+    /// The number of steps attacks reach, adjacent opponents only if 0
+    pub attack_range: u32,
+    /// Added to the steps the combatant can move each turn
+    pub speed: i8,
+    /// Used one at a time once the combatant is below half of its maximal hit points
+    pub consumables: Vec<Item>,
 }
 
 impl Combatant {
@@ -25,7 +52,170 @@ impl Combatant {
         else {
             self.hp = sub_result.0;
         }
-    }   
+    }
+
+    /// Applies the damage of a hit, reduced by the armor and the active shields, and returns the damage dealt
+    pub fn take_hit(&mut self, damage: u16) -> u16 {
+        let absorbed: u16 = self
+            .effects
+            .iter()
+            .filter(|e| e.kind == StatusEffectKind::Shield)
+            .fold(self.armor, |sum, e| sum.saturating_add(e.potency));
+        let dealt = damage.saturating_sub(absorbed).min(self.hp);
+
+        self.apply_damage(dealt);
+
+        dealt
+    }
+
+    /// Adds the stats of the items to the base stats, consumables are kept to be used later
+    ///
+    /// A combatant carries a single weapon and a single armor, of several the last one is used.
+    pub fn equip(&mut self, items: Vec<Item>) {
+        let last_of = |kind: ItemKind| items.iter().rposition(|item| item.kind == kind);
+        let worn = [last_of(ItemKind::Weapon), last_of(ItemKind::Armor)];
+
+        for (i, item) in items.into_iter().enumerate() {
+            match item.kind {
+                ItemKind::Consumable => self.consumables.push(item),
+                ItemKind::Weapon | ItemKind::Armor if worn.contains(&Some(i)) => {
+                    self.dmg = self.dmg.saturating_add(item.damage);
+                    self.armor = self.armor.saturating_add(item.armor);
+                    self.attack_range = self.attack_range.max(item.range);
+                    self.speed = self.speed.saturating_add(item.speed);
+                }
+                ItemKind::Weapon | ItemKind::Armor => {}
+            }
+        }
+    }
+
+    /// The number of steps attacks reach
+    pub fn reach(&self) -> u32 {
+        self.attack_range.max(1)
+    }
+
+    /// The number of steps the combatant can move with the given base movement
+    pub fn movement(&self, base: usize) -> usize {
+        match self.speed >= 0 {
+            true => base.saturating_add(self.speed as usize),
+            false => base.saturating_sub(self.speed.unsigned_abs() as usize),
+        }
+    }
+
+    /// Uses the first consumable once the combatant is below half of its maximal hit points
+    ///
+    /// Returns the consumable and the hit points gained
+    pub fn use_consumable(&mut self) -> Option<(Item, u16)> {
+        if !self.is_alive() || self.hp_ratio() >= 0.5 || self.consumables.is_empty() {
+            return None;
+        }
+
+        let item = self.consumables.remove(0);
+        let gained = self.heal(item.heal);
+
+        Some((item, gained))
+    }
+
+    /// Determines if the other combatant is an opponent
+    pub fn is_hostile_to(&self, other: &Combatant) -> bool {
+        if self == other {
+            return false;
+        }
+
+        match (&self.team, &other.team) {
+            (Some(own), Some(others)) => own != others,
+            _ => true,
+        }
+    }
+
+    /// Brings all abilities one turn closer to being ready
+    pub fn cool_down_abilities(&mut self) {
+        for ability in self.abilities.iter_mut() {
+            ability.cool_down();
+        }
+    }
+
+    /// Heals the combatant by the given amount, up to its maximal hit points, and returns the hit points gained
+    ///
+    /// Dead combatants can not be healed, they have to be revived.
+    pub fn heal(&mut self, amount: u16) -> u16 {
+        if !self.is_alive() {
+            return 0;
+        }
+
+        let hp_before = self.hp;
+        self.hp = self.hp.saturating_add(amount).min(self.max_hp.max(hp_before));
+
+        self.hp - hp_before
+    }
+
+    /// Determines if the combatant lost hit points
+    pub fn is_wounded(&self) -> bool {
+        self.is_alive() && self.hp < self.max_hp
+    }
+
+    /// The share of the maximal hit points left, used to find the most wounded combatant
+    pub fn hp_ratio(&self) -> f64 {
+        match self.max_hp {
+            0 => 1.0,
+            max => self.hp as f64 / max as f64,
+        }
+    }
+
+    /// Brings a dead combatant back with the given share of its maximal hit points, but at least 1
+    pub fn revive(&mut self, hp_percent: u8) {
+        let hp = (self.max_hp as u32 * hp_percent.min(100) as u32 / 100) as u16;
+
+        self.hp = hp.max(1);
+        self.effects.clear();
+        self.died_in_round = None;
+        self.revived += 1;
+    }
+
+    /// Adds the effect, an already active effect of the same kind is replaced unless it stacks
+    pub fn apply_effect(&mut self, effect: StatusEffect) {
+        if !effect.stacks() {
+            self.effects.retain(|e| e.kind != effect.kind);
+        }
+
+        self.effects.push(effect);
+    }
+
+    /// Lets every active effect take place once and removes the expired ones
+    pub fn tick_effects(&mut self) -> Vec<StatusEffectTick> {
+        let mut ticks: Vec<StatusEffectTick> = vec![];
+        let mut effects = self.effects.to_vec();
+
+        for effect in effects.iter_mut() {
+            let amount = match effect.kind {
+                StatusEffectKind::Poison | StatusEffectKind::Bleed => {
+                    let damage = effect.potency.min(self.hp);
+                    self.apply_damage(effect.potency);
+                    damage
+                }
+                StatusEffectKind::Regeneration => self.heal(effect.potency),
+                StatusEffectKind::Slow => effect.potency,
+                StatusEffectKind::Stun | StatusEffectKind::Shield => 0,
+            };
+
+            effect.remaining_turns = effect.remaining_turns.saturating_sub(1);
+
+            ticks.push(StatusEffectTick {
+                kind: effect.kind,
+                amount,
+                remaining_hp: self.hp,
+                remaining_turns: effect.remaining_turns,
+            });
+
+            if !self.is_alive() {
+                break;
+            }
+        }
+
+        self.effects = effects.into_iter().filter(|e| !e.is_expired()).collect();
+
+        ticks
+    }
 }
 
 impl PartialEq for Combatant {
@@ -44,8 +234,7 @@ mod tests {
             name: "test".to_owned(),
             dmg: 2,
             hp: 0,
-            avatar: None,
-            id: None,
+            ..Default::default()
         };
 
         assert!(!test_object.is_alive());
@@ -57,8 +246,7 @@ mod tests {
             name: "test".to_owned(),
             dmg: 2,
             hp: u16::MIN,
-            avatar: None,
-            id: None,
+            ..Default::default()
         };
 
         assert!(!test_object.is_alive());
@@ -70,8 +258,7 @@ mod tests {
             name: "test".to_owned(),
             dmg: 2,
             hp: 15,
-            avatar: None,
-            id: None,
+            ..Default::default()
         };
 
         test_object.apply_damage(5);
@@ -85,8 +272,7 @@ mod tests {
             name: "test".to_owned(),
             dmg: 2,
             hp: 15,
-            avatar: None,
-            id: None,
+            ..Default::default()
         };
 
         test_object.apply_damage(15);
@@ -103,8 +289,7 @@ mod tests {
             name: "test".to_owned(),
             dmg: 2,
             hp: 15,
-            avatar: None,
-            id: None,
+            ..Default::default()
         };
 
         test_object.apply_damage(u16::MAX);
@@ -121,8 +306,7 @@ mod tests {
             name: "test".to_owned(),
             dmg: 2,
             hp: 15,
-            avatar: None,
-            id: None,
+            ..Default::default()
         };
 
         assert_eq!(c1, c1);
@@ -134,20 +318,286 @@ mod tests {
             name: "c1".to_owned(),
             dmg: 5,
             hp: 10,
-            avatar: None,
-            id: None,
+            ..Default::default()
         };
 
         let c2 = Combatant{
             name: "c1".to_owned(),
             dmg: 2,
             hp: 20,
-            avatar: None,
-            id: None,
+            ..Default::default()
         };
 
         assert_eq!(c1, c2);
     }
 
+    #[test]
+    fn apply_effect_refreshes_poison(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 15,
+            ..Default::default()
+        };
+
+        test_object.apply_effect(StatusEffect::new(StatusEffectKind::Poison, 1, 2));
+        test_object.apply_effect(StatusEffect::new(StatusEffectKind::Poison, 3, 2));
+
+        assert_eq!(vec![StatusEffect::new(StatusEffectKind::Poison, 3, 2)], test_object.effects);
+    }
+
+    #[test]
+    fn apply_effect_stacks_bleed(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 15,
+            ..Default::default()
+        };
+
+        test_object.apply_effect(StatusEffect::new(StatusEffectKind::Bleed, 2, 1));
+        test_object.apply_effect(StatusEffect::new(StatusEffectKind::Bleed, 2, 1));
+
+        assert_eq!(2, test_object.effects.len());
+    }
+
+    #[test]
+    fn tick_effects_damage_and_heal(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 15,
+            max_hp: 15,
+            ..Default::default()
+        };
+        test_object.apply_effect(StatusEffect::new(StatusEffectKind::Poison, 2, 5));
+        test_object.apply_effect(StatusEffect::new(StatusEffectKind::Regeneration, 2, 2));
+
+        let ticks = test_object.tick_effects();
+
+        assert_eq!(2, ticks.len());
+        assert_eq!(12, test_object.hp);
+        assert_eq!(2, test_object.effects.len());
+    }
+
+    #[test]
+    fn tick_effects_removes_expired(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 15,
+            ..Default::default()
+        };
+        test_object.apply_effect(StatusEffect::new(StatusEffectKind::Stun, 1, 0));
+
+        let ticks = test_object.tick_effects();
+
+        assert_eq!(StatusEffectKind::Stun, ticks[0].kind);
+        assert!(test_object.effects.is_empty());
+    }
+
+    #[test]
+    fn tick_effects_stops_when_dead(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 3,
+            ..Default::default()
+        };
+        test_object.apply_effect(StatusEffect::new(StatusEffectKind::Bleed, 2, 5));
+        test_object.apply_effect(StatusEffect::new(StatusEffectKind::Regeneration, 2, 5));
+
+        let ticks = test_object.tick_effects();
+
+        assert_eq!(1, ticks.len());
+        assert_eq!(3, ticks[0].amount);
+        assert!(!test_object.is_alive());
+    }
+
+    #[test]
+    fn take_hit_with_shield(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 15,
+            effects: vec![StatusEffect::new(StatusEffectKind::Shield, 2, 3)],
+            ..Default::default()
+        };
+
+        let dealt = test_object.take_hit(5);
+
+        assert_eq!(2, dealt);
+        assert_eq!(13, test_object.hp);
+    }
+
+    #[test]
+    fn take_hit_with_armor(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 15,
+            armor: 2,
+            effects: vec![StatusEffect::new(StatusEffectKind::Shield, 2, 1)],
+            ..Default::default()
+        };
+
+        let dealt = test_object.take_hit(5);
+
+        assert_eq!(2, dealt);
+        assert_eq!(13, test_object.hp);
+    }
+
+    #[test]
+    fn equip_adds_stats(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 15,
+            dmg: 2,
+            ..Default::default()
+        };
+        let bow = Item { damage: 3, range: 4, speed: 1, ..Item::new("bow", ItemKind::Weapon) };
+        let plate = Item { armor: 5, speed: -2, ..Item::new("plate", ItemKind::Armor) };
+        let potion = Item { heal: 10, ..Item::new("potion", ItemKind::Consumable) };
+
+        test_object.equip(vec![bow, plate, potion]);
+
+        assert_eq!(5, test_object.dmg);
+        assert_eq!(5, test_object.armor);
+        assert_eq!(4, test_object.reach());
+        assert_eq!(2, test_object.movement(3));
+        assert_eq!(1, test_object.consumables.len());
+    }
+
+    #[test]
+    fn equip_one_weapon_and_one_armor(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 15,
+            dmg: 2,
+            ..Default::default()
+        };
+        let sword = Item { damage: 3, ..Item::new("sword", ItemKind::Weapon) };
+        let axe = Item { damage: 5, ..Item::new("axe", ItemKind::Weapon) };
+        let plate = Item { armor: 5, ..Item::new("plate", ItemKind::Armor) };
+        let mail = Item { armor: 2, ..Item::new("mail", ItemKind::Armor) };
+        let potion = Item { heal: 10, ..Item::new("potion", ItemKind::Consumable) };
+
+        test_object.equip(vec![sword, plate, potion.clone(), axe, mail, potion]);
+
+        assert_eq!(7, test_object.dmg);
+        assert_eq!(2, test_object.armor);
+        assert_eq!(2, test_object.consumables.len());
+    }
+
+    #[test]
+    fn movement_never_negative(){
+        let test_object = Combatant{
+            name: "test".to_owned(),
+            speed: -5,
+            ..Default::default()
+        };
+
+        assert_eq!(0, test_object.movement(3));
+        assert_eq!(1, test_object.reach());
+    }
+
+    #[test]
+    fn use_consumable_below_half_hp(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 6,
+            max_hp: 10,
+            consumables: vec![Item { heal: 3, ..Item::new("potion", ItemKind::Consumable) }],
+            ..Default::default()
+        };
+
+        assert_eq!(None, test_object.use_consumable());
+
+        test_object.apply_damage(2);
+
+        let (item, gained) = test_object.use_consumable().unwrap();
+
+        assert_eq!("potion", item.name);
+        assert_eq!(3, gained);
+        assert_eq!(7, test_object.hp);
+        assert!(test_object.consumables.is_empty());
+    }
+
+    #[test]
+    fn is_hostile_to(){
+        let c1 = Combatant{
+            name: "c1".to_owned(),
+            team: Some("red".to_owned()),
+            ..Default::default()
+        };
+        let c2 = Combatant{
+            name: "c2".to_owned(),
+            team: Some("red".to_owned()),
+            ..Default::default()
+        };
+        let c3 = Combatant{
+            name: "c3".to_owned(),
+            ..Default::default()
+        };
+
+        assert!(!c1.is_hostile_to(&c2));
+        assert!(!c1.is_hostile_to(&c1));
+        assert!(c1.is_hostile_to(&c3));
+        assert!(c3.is_hostile_to(&c2));
+    }
+
+    #[test]
+    fn heal_up_to_max_hp(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 8,
+            max_hp: 10,
+            ..Default::default()
+        };
+
+        let healed = test_object.heal(5);
+
+        assert_eq!(2, healed);
+        assert_eq!(10, test_object.hp);
+        assert!(!test_object.is_wounded());
+    }
+
+    #[test]
+    fn heal_dead(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 0,
+            max_hp: 10,
+            ..Default::default()
+        };
+
+        assert_eq!(0, test_object.heal(5));
+        assert!(!test_object.is_alive());
+    }
+
+    #[test]
+    fn revive_with_share_of_max_hp(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 0,
+            max_hp: 20,
+            died_in_round: Some(3),
+            effects: vec![StatusEffect::new(StatusEffectKind::Poison, 2, 1)],
+            ..Default::default()
+        };
+
+        test_object.revive(50);
 
-}
\ No newline at end of file
+        assert_eq!(10, test_object.hp);
+        assert_eq!(1, test_object.revived);
+        assert_eq!(None, test_object.died_in_round);
+        assert!(test_object.effects.is_empty());
+    }
+
+    #[test]
+    fn revive_at_least_one_hp(){
+        let mut test_object = Combatant{
+            name: "test".to_owned(),
+            hp: 0,
+            max_hp: 3,
+            ..Default::default()
+        };
+
+        test_object.revive(10);
+
+        assert_eq!(1, test_object.hp);
+    }
+}
@@ -1,40 +1,43 @@
-use rand::seq::SliceRandom;
+use rand::{rngs::StdRng, seq::SliceRandom};
 
 use crate::types::point::Point;
 
 use super::{
-    battle_actions::{BattleAction, BattleAttackAction},
+    ability::{Ability, AbilityKind},
+    battle_actions::{
+        AbilityTarget, BattleAbilityAction, BattleAction, BattleAttackAction, BattleHealAction,
+    },
+    battle_rules::BattleRules,
     combatant::Combatant,
     err::Error,
     map::Map,
     movement::{MovementEngine, MovementResult},
-    MAX_COMBATANT_MOVE,
+    status_effect::{StatusEffect, StatusEffectKind, StatusEffectTick},
 };
 
-#[derive(Debug, Clone)]
-pub struct CombatantTurn {
-    active_combatant: Combatant,
-    opponents: Vec<Combatant>,
-    map: Map,
-    actions: Vec<BattleAction>,
+/// The turn of a single combatant, played directly on the state of the battle
+#[derive(Debug)]
+pub struct CombatantTurn<'a> {
+    active_combatant: &'a mut Combatant,
+    /// All other combatants, allies included
+    opponents: &'a mut Vec<Combatant>,
+    map: &'a mut Map,
+    /// The log of the whole battle, the turn only appends to it
+    actions: &'a mut Vec<BattleAction>,
     round_number: u32,
+    rules: BattleRules,
+    rng: &'a mut StdRng,
 }
 
-pub struct CombatantTurnResult {
-    pub active_combatant: Combatant,
-    pub opponents: Vec<Combatant>,
-    pub map: Map,
-    pub actions: Vec<BattleAction>,
-    pub round_number: u32,
-}
-
-impl CombatantTurn {
+impl<'a> CombatantTurn<'a> {
     pub fn new(
-        active: Combatant,
-        opponents: Vec<Combatant>,
-        map: Map,
-        actions: Vec<BattleAction>,
+        active: &'a mut Combatant,
+        opponents: &'a mut Vec<Combatant>,
+        map: &'a mut Map,
+        actions: &'a mut Vec<BattleAction>,
         round_number: u32,
+        rules: BattleRules,
+        rng: &'a mut StdRng,
     ) -> Self {
         Self {
             active_combatant: active,
@@ -42,62 +45,360 @@ impl CombatantTurn {
             map,
             actions,
             round_number,
+            rules,
+            rng,
         }
     }
 
-    pub fn execute(&mut self) -> Result<CombatantTurnResult, Error> {
+    pub fn execute(&mut self) -> Result<(), Error> {
         // If no opponents are present then we can not have an Combatant turn
         if self.opponents.len() == 0 {
             return Err(Error::NoOpponentsPresent);
         }
 
-        let active_combatant_position: Option<Point> =
-            self.map.position_for(&self.active_combatant.name);
+        let ticks = self.tick_effects();
 
-        match active_combatant_position {
-            Some(active_position) => {
-                // Move the active combatant to the closest opponent
-                let movement: MovementResult = MovementEngine::new(
-                    active_position,
-                    self.opponents_locations(),
-                    Some(MAX_COMBATANT_MOVE),
-                )
-                .do_move();
-
-                if movement.has_moved() {
-                    self.actions.push(BattleAction::Move(
-                        self.round_number,
-                        self.active_combatant.name.clone(),
-                        movement.clone(),
-                    ));
-
-                    // update the active combatants position on the map
-                    self.map.move_to(active_position, movement.last_position)?;
-                }
-
-                // Determine if any opponent is in range
-                let mut potential_targets: Vec<String> =
-                    self.map.get_occupied_neighbors(movement.last_position);
-                potential_targets.shuffle(&mut rand::thread_rng());
-                let opponent_id: Option<&String> = potential_targets.first();
-
-                match opponent_id {
-                    Some(id) => {
-                        self.attack(id.clone());
-                        return Ok(CombatantTurnResult::from(self));
+        // Combatants that died from their effects or are stunned do not act
+        if !self.active_combatant.is_alive()
+            || ticks.iter().any(|t| t.kind == StatusEffectKind::Stun)
+        {
+            return Ok(());
+        }
+
+        let slowed_by: usize = ticks
+            .iter()
+            .filter(|t| t.kind == StatusEffectKind::Slow)
+            .map(|t| t.amount as usize)
+            .sum();
+
+        let active_position: Point = match self.map.position_for(&self.active_combatant.name) {
+            Some(p) => p,
+            // The map does not know the position of the active combatant
+            None => return Ok(()),
+        };
+
+        // Nothing left to fight for once only allies remain
+        if self.opponents_locations().is_empty() {
+            return Ok(());
+        }
+
+        self.active_combatant.cool_down_abilities();
+        self.use_consumable();
+
+        // Using an ability takes the whole turn
+        if self.use_ability(active_position, slowed_by)? {
+            return Ok(());
+        }
+
+        let reach: u32 = self.active_combatant.reach();
+
+        // Combatants hold their position while an opponent is within reach
+        let movement: MovementResult = match self.opponents_within(active_position, reach).is_empty() {
+            true => MovementEngine::new(
+                active_position,
+                self.opponents_locations(),
+                Some(self.max_move(slowed_by)),
+                self.map.get_topology(),
+            )
+            .do_move()
+            .until_blocked(|p| self.map.is_occupied(p)),
+            false => MovementResult::already_at_goal(active_position, active_position),
+        };
+
+        if movement.has_moved() {
+            self.actions.push(BattleAction::Move(
+                self.round_number,
+                self.active_combatant.name.clone(),
+                movement.clone(),
+            ));
+
+            // update the active combatants position on the map
+            self.map.move_to(active_position, movement.last_position)?;
+        }
+
+        // Determine if any opponent is in reach
+        let mut potential_targets: Vec<String> = self.opponents_within(movement.last_position, reach);
+        potential_targets.shuffle(self.rng);
+
+        if let Some(id) = potential_targets.first() {
+            self.attack(id.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Uses the first ready ability whose conditions are met, in the order they are declared
+    fn use_ability(&mut self, position: Point, slowed_by: usize) -> Result<bool, Error> {
+        let abilities: Vec<Ability> = self.active_combatant.abilities.to_vec();
+
+        for (index, ability) in abilities.iter().enumerate() {
+            if !ability.is_ready() {
+                continue;
+            }
+
+            let action: Option<BattleAbilityAction> = match ability.kind {
+                AbilityKind::AreaAttack => self.area_attack(ability, position),
+                AbilityKind::Charge => self.charge(ability, position, slowed_by)?,
+                AbilityKind::HealAlly => self.heal_ally(ability, position),
+                AbilityKind::Shield => self.shield(ability, position),
+            };
+
+            if let Some(a) = action {
+                self.active_combatant.abilities[index].trigger();
+                self.actions.push(BattleAction::Ability(self.round_number, a));
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// The steps the active combatant can move this turn
+    fn max_move(&self, slowed_by: usize) -> usize {
+        self.active_combatant
+            .movement(self.rules.max_combatant_move)
+            .saturating_sub(slowed_by)
+    }
+
+    /// Uses a consumable once the active combatant is badly wounded, this does not take up the turn
+    fn use_consumable(&mut self) {
+        if let Some((item, amount)) = self.active_combatant.use_consumable() {
+            self.actions.push(BattleAction::Heal(
+                self.round_number,
+                BattleHealAction {
+                    healer: self.active_combatant.clone(),
+                    target: self.active_combatant.clone(),
+                    amount,
+                    item: Some(item.name),
+                },
+            ));
+        }
+    }
+
+    /// Hits every opponent within range
+    fn area_attack(&mut self, ability: &Ability, position: Point) -> Option<BattleAbilityAction> {
+        let targets: Vec<String> = self.opponents_within(position, ability.range);
+
+        if targets.is_empty() {
+            return None;
+        }
+
+        let hits = targets
+            .iter()
+            .map(|name| self.hit_with_ability(name, ability))
+            .collect();
+
+        Some(self.ability_action(ability, hits, None))
+    }
+
+    /// Rushes towards the closest opponent that can not be reached by a normal move and hits it
+    fn charge(
+        &mut self,
+        ability: &Ability,
+        position: Point,
+        slowed_by: usize,
+    ) -> Result<Option<BattleAbilityAction>, Error> {
+        let reach = (ability.range as usize).saturating_sub(slowed_by);
+        let target: Option<(String, Point)> = self
+            .opponents
+            .iter()
+            .filter(|c| self.is_target(&c.name))
+            .filter_map(|c| self.map.position_for(&c.name).map(|p| (c.name.clone(), p)))
+            .filter(|(_, p)| {
+                let steps = position.steps_to(p, self.map.get_topology()) as usize;
+                steps > self.max_move(0) + 1 && steps <= reach + 1
+            })
+            .min_by_key(|(_, p)| position.steps_to(p, self.map.get_topology()));
+
+        let (target_name, target_position) = match target {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+
+        let movement: MovementResult =
+            MovementEngine::new(position, vec![target_position], Some(reach), self.map.get_topology())
+                .do_move()
+                .until_blocked(|p| self.map.is_occupied(p));
+
+        if movement.has_moved() {
+            self.map.move_to(position, movement.last_position)?;
+        }
+
+        let hits = match movement.last_position.steps_to(&target_position, self.map.get_topology()) {
+            1 => vec![self.hit_with_ability(&target_name, ability)],
+            _ => vec![],
+        };
+
+        Ok(Some(self.ability_action(ability, hits, Some(movement))))
+    }
+
+    /// Heals the most wounded ally within range, the combatant itself included
+    ///
+    /// Not used while no ally is wounded.
+    fn heal_ally(&mut self, ability: &Ability, position: Point) -> Option<BattleAbilityAction> {
+        let in_range: Vec<String> = self.map.occupants_within(position, ability.range);
+        let most_wounded: Option<&Combatant> = self
+            .opponents
+            .iter()
+            .filter(|c| !c.is_hostile_to(&self.active_combatant))
+            .filter(|c| in_range.contains(&c.name))
+            .chain(std::iter::once(&*self.active_combatant))
+            .filter(|c| c.is_wounded())
+            .min_by(|a, b| a.hp_ratio().total_cmp(&b.hp_ratio()));
+
+        let target_name = most_wounded?.name.clone();
+        let effect = ability.effect.clone();
+        let target = self.combatant_mut(&target_name)?;
+
+        let amount = target.heal(ability.power);
+        if let Some(e) = effect.clone() {
+            target.apply_effect(e);
+        }
+        let target_snapshot = target.clone();
+
+        self.actions.push(BattleAction::Heal(
+            self.round_number,
+            BattleHealAction {
+                healer: self.active_combatant.clone(),
+                target: target_snapshot.clone(),
+                amount,
+                item: None,
+            },
+        ));
+        self.log_effect(&target_name, effect);
+
+        let healed = AbilityTarget {
+            name: target_snapshot.name,
+            amount,
+            remaining_hp: target_snapshot.hp,
+        };
+
+        Some(self.ability_action(ability, vec![healed], None))
+    }
+
+    /// Raises a shield once an opponent comes within range
+    fn shield(&mut self, ability: &Ability, position: Point) -> Option<BattleAbilityAction> {
+        if self.opponents_within(position, ability.range).is_empty() {
+            return None;
+        }
+
+        let effect = ability
+            .effect
+            .clone()
+            .unwrap_or(StatusEffect::new(StatusEffectKind::Shield, 1, ability.power));
+        let name = self.active_combatant.name.clone();
+
+        self.active_combatant.apply_effect(effect.clone());
+        self.log_effect(&name, Some(effect));
+
+        Some(self.ability_action(ability, vec![], None))
+    }
+
+    /// Deals the damage of the ability to the opponent and applies its effect if the opponent survives
+    fn hit_with_ability(&mut self, name: &str, ability: &Ability) -> AbilityTarget {
+        let effect = ability.effect.clone();
+        let (target, applied) = match self.opponents.iter_mut().find(|c| c.name == name) {
+            Some(opponent) => {
+                let dealt = opponent.take_hit(ability.power);
+                let applied = match (opponent.is_alive(), effect) {
+                    (true, Some(e)) => {
+                        opponent.apply_effect(e.clone());
+                        Some(e)
                     }
-                    None => Ok(CombatantTurnResult::from(self)),
-                }
+                    _ => None,
+                };
+
+                (
+                    AbilityTarget {
+                        name: opponent.name.clone(),
+                        amount: dealt,
+                        remaining_hp: opponent.hp,
+                    },
+                    applied,
+                )
             }
-            // The map does not know the position of the active combatant
-            None => Ok(CombatantTurnResult::from(self)),
+            None => (
+                AbilityTarget {
+                    name: name.to_owned(),
+                    amount: 0,
+                    remaining_hp: 0,
+                },
+                None,
+            ),
+        };
+
+        self.log_effect(name, applied);
+
+        target
+    }
+
+    fn ability_action(
+        &self,
+        ability: &Ability,
+        targets: Vec<AbilityTarget>,
+        movement: Option<MovementResult>,
+    ) -> BattleAbilityAction {
+        BattleAbilityAction {
+            user: self.active_combatant.name.clone(),
+            ability: ability.name.clone(),
+            kind: ability.kind,
+            targets,
+            movement,
+        }
+    }
+
+    fn log_effect(&mut self, name: &str, effect: Option<StatusEffect>) {
+        if let Some(e) = effect {
+            self.actions.push(BattleAction::EffectApplied(
+                self.round_number,
+                name.to_owned(),
+                e,
+            ));
         }
     }
 
+    fn combatant_mut(&mut self, name: &str) -> Option<&mut Combatant> {
+        match self.active_combatant.name == name {
+            true => Some(&mut *self.active_combatant),
+            false => self.opponents.iter_mut().find(|c| c.name == name),
+        }
+    }
+
+    /// Determines if the named combatant is a living opponent of the active combatant
+    fn is_target(&self, name: &str) -> bool {
+        self.opponents
+            .iter()
+            .any(|c| c.name == name && c.is_alive() && c.is_hostile_to(&self.active_combatant))
+    }
+
+    /// The names of all living opponents within the given number of steps
+    fn opponents_within(&self, position: Point, range: u32) -> Vec<String> {
+        self.map
+            .occupants_within(position, range)
+            .into_iter()
+            .filter(|name| self.is_target(name))
+            .collect()
+    }
+
+    /// Lets the effects of the active combatant take place and logs them
+    fn tick_effects(&mut self) -> Vec<StatusEffectTick> {
+        let ticks = self.active_combatant.tick_effects();
+
+        for tick in ticks.iter() {
+            self.actions.push(BattleAction::EffectTicked(
+                self.round_number,
+                self.active_combatant.name.clone(),
+                tick.clone(),
+            ));
+        }
+
+        ticks
+    }
+
     fn opponents_locations(&self) -> Vec<Point> {
         self.opponents
             .iter()
-            //.filter(|c| c.is_alive())
+            .filter(|c| c.is_hostile_to(&self.active_combatant))
             .map(|c| self.map.position_for(&c.name))
             .filter(|p| p.is_some())
             .map(|p| p.unwrap())
@@ -105,37 +406,32 @@ impl CombatantTurn {
     }
 
     fn attack(&mut self, opponent_id: String) {
-        let cloned = self.opponents.to_vec();
-        self.opponents.clear();
+        let opponent = match self.opponents.iter_mut().find(|c| c.name == opponent_id) {
+            Some(o) => o,
+            None => return,
+        };
+        let dealt = opponent.take_hit(self.active_combatant.dmg);
 
-        for mut opponent in cloned {
-            if opponent.name == opponent_id {
-                opponent.apply_damage(self.active_combatant.dmg);
+        // Add a protocol of who is attacking who and for how much
+        self.actions.push(BattleAction::Attack(
+            self.round_number,
+            BattleAttackAction {
+                assailant: self.active_combatant.clone(),
+                victim: opponent.clone(),
+                damage: dealt,
+            },
+        ));
 
-                // Add a protocol of who is attacking who and for how much
-                self.actions.push(BattleAction::Attack(
+        // Effects only stick to opponents that survived the hit
+        if opponent.is_alive() {
+            for effect in self.active_combatant.on_hit.iter() {
+                opponent.apply_effect(effect.clone());
+                self.actions.push(BattleAction::EffectApplied(
                     self.round_number,
-                    BattleAttackAction {
-                        assailant: self.active_combatant.clone(),
-                        victim: opponent.clone(),
-                        damage: self.active_combatant.dmg,
-                    },
+                    opponent.name.clone(),
+                    effect.clone(),
                 ));
             }
-
-            self.opponents.push(opponent);
-        }
-    }
-}
-
-impl From<&mut CombatantTurn> for CombatantTurnResult {
-    fn from(value: &mut CombatantTurn) -> Self {
-        Self {
-            active_combatant: value.active_combatant.clone(),
-            opponents: value.opponents.clone(),
-            map: value.map.clone(),
-            actions: value.actions.to_vec(),
-            round_number: value.round_number,
         }
     }
 }
@@ -143,6 +439,9 @@ impl From<&mut CombatantTurn> for CombatantTurnResult {
 #[cfg(test)]
 mod combatant_turn_tests {
     use super::*;
+    use rand::SeedableRng;
+    use crate::engine::item::{Item, ItemKind};
+    use crate::types::topology::Topology;
 
     #[test]
     #[allow(unused_must_use)] // alow the test code to ignore return values
@@ -151,16 +450,19 @@ mod combatant_turn_tests {
             name: String::from("Active"),
             dmg: 2,
             hp: 10,
+            ..Default::default()
         };
         let opponent1 = Combatant {
             name: String::from("Opponent1"),
             dmg: 2,
             hp: 10,
+            ..Default::default()
         };
         let opponent2 = Combatant {
             name: String::from("Opponent2"),
             dmg: 2,
             hp: 10,
+            ..Default::default()
         };
 
         let mut map = Map::new(10, 10);
@@ -169,13 +471,7 @@ mod combatant_turn_tests {
         map.place_randomly(opponent1.name.clone());
         map.place_randomly(opponent2.name.clone());
 
-        let mut test_object: CombatantTurn = CombatantTurn::new(
-            active_combattant,
-            vec![opponent1, opponent2],
-            map,
-            vec![],
-            1,
-        );
+        let mut test_object = TurnState::new(active_combattant, vec![opponent1, opponent2], map);
 
         let test_result = test_object.execute().unwrap();
 
@@ -185,4 +481,351 @@ mod combatant_turn_tests {
             "The number of actions can not be 0"
         )
     }
+
+    #[test]
+    fn stunned_combatant_does_nothing() {
+        let active_combattant = Combatant {
+            name: String::from("Active"),
+            dmg: 2,
+            hp: 10,
+            effects: vec![StatusEffect::new(StatusEffectKind::Stun, 1, 0)],
+            ..Default::default()
+        };
+        let opponent = Combatant {
+            name: String::from("Opponent"),
+            dmg: 2,
+            hp: 10,
+            ..Default::default()
+        };
+
+        let mut map = Map::new(10, 10);
+        map.place(active_combattant.name.clone(), Point { x: 0, y: 0 }).unwrap();
+        map.place(opponent.name.clone(), Point { x: 1, y: 0 }).unwrap();
+
+        let mut test_object =
+            TurnState::new(active_combattant, vec![opponent], map);
+
+        let test_result = test_object.execute().unwrap();
+
+        assert_eq!(1, test_result.actions.len());
+        assert!(matches!(test_result.actions[0], BattleAction::EffectTicked(1, _, _)));
+        assert!(test_result.active_combatant.effects.is_empty());
+        assert_eq!(10, test_result.opponents[0].hp);
+    }
+
+    #[test]
+    fn attack_applies_on_hit_effects() {
+        let active_combattant = Combatant {
+            name: String::from("Active"),
+            dmg: 2,
+            hp: 10,
+            on_hit: vec![StatusEffect::new(StatusEffectKind::Poison, 3, 1)],
+            ..Default::default()
+        };
+        let opponent = Combatant {
+            name: String::from("Opponent"),
+            dmg: 2,
+            hp: 10,
+            ..Default::default()
+        };
+
+        let mut map = Map::new(10, 10);
+        map.place(active_combattant.name.clone(), Point { x: 0, y: 0 }).unwrap();
+        map.place(opponent.name.clone(), Point { x: 1, y: 0 }).unwrap();
+
+        let mut test_object =
+            TurnState::new(active_combattant, vec![opponent], map);
+
+        let test_result = test_object.execute().unwrap();
+
+        assert_eq!(8, test_result.opponents[0].hp);
+        assert_eq!(
+            vec![StatusEffect::new(StatusEffectKind::Poison, 3, 1)],
+            test_result.opponents[0].effects
+        );
+        assert!(matches!(test_result.actions.last(), Some(BattleAction::EffectApplied(1, _, _))));
+    }
+
+    /// The state a turn in the first round is played on
+    struct TurnState {
+        active_combatant: Combatant,
+        opponents: Vec<Combatant>,
+        map: Map,
+        actions: Vec<BattleAction>,
+    }
+
+    impl TurnState {
+        fn new(active_combatant: Combatant, opponents: Vec<Combatant>, map: Map) -> Self {
+            Self {
+                active_combatant,
+                opponents,
+                map,
+                actions: vec![],
+            }
+        }
+
+        fn execute(&mut self) -> Result<&Self, Error> {
+            CombatantTurn::new(
+                &mut self.active_combatant,
+                &mut self.opponents,
+                &mut self.map,
+                &mut self.actions,
+                1,
+                BattleRules::default(),
+                &mut StdRng::seed_from_u64(0),
+            )
+            .execute()?;
+
+            Ok(self)
+        }
+    }
+
+    fn turn_on_map(active: Combatant, others: Vec<(Combatant, Point)>, position: Point) -> TurnState {
+        let mut map = Map::new(10, 10);
+        map.place(active.name.clone(), position).unwrap();
+        for (other, p) in others.iter() {
+            map.place(other.name.clone(), *p).unwrap();
+        }
+
+        TurnState::new(active, others.into_iter().map(|(c, _)| c).collect(), map)
+    }
+
+    fn combatant(name: &str, hp: u16) -> Combatant {
+        Combatant {
+            name: String::from(name),
+            dmg: 2,
+            hp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn area_attack_hits_all_in_range() {
+        let active = Combatant {
+            abilities: vec![Ability::new("Whirlwind", AbilityKind::AreaAttack, 2, 3, 4)],
+            ..combatant("Active", 10)
+        };
+
+        let mut test_object = turn_on_map(
+            active,
+            vec![
+                (combatant("Near", 10), Point::new(2, 1)),
+                (combatant("Close", 10), Point::new(1, 3)),
+                (combatant("Far", 10), Point::new(8, 8)),
+            ],
+            Point::new(1, 1),
+        );
+
+        let test_result = test_object.execute().unwrap();
+
+        let hp: Vec<u16> = test_result.opponents.iter().map(|c| c.hp).collect();
+        assert_eq!(vec![6, 6, 10], hp);
+        assert_eq!(4, test_result.active_combatant.abilities[0].remaining_cooldown);
+        assert!(matches!(test_result.actions[0], BattleAction::Ability(1, _)));
+    }
+
+    #[test]
+    fn ability_on_cooldown_is_not_used() {
+        let mut ability = Ability::new("Whirlwind", AbilityKind::AreaAttack, 2, 3, 4);
+        ability.trigger();
+        let active = Combatant {
+            abilities: vec![ability],
+            ..combatant("Active", 10)
+        };
+
+        let mut test_object = turn_on_map(
+            active,
+            vec![(combatant("Near", 10), Point::new(2, 1))],
+            Point::new(1, 1),
+        );
+
+        let test_result = test_object.execute().unwrap();
+
+        assert_eq!(8, test_result.opponents[0].hp);
+        assert_eq!(3, test_result.active_combatant.abilities[0].remaining_cooldown);
+        assert!(matches!(test_result.actions[0], BattleAction::Attack(1, _)));
+    }
+
+    #[test]
+    fn ability_used_again_after_cooldown_turns() {
+        let active = Combatant {
+            abilities: vec![Ability::new("Whirlwind", AbilityKind::AreaAttack, 1, 1, 1)],
+            ..combatant("Active", 10)
+        };
+        let mut test_object = turn_on_map(active, vec![(combatant("Near", 100), Point::new(2, 1))], Point::new(1, 1));
+
+        let used: Vec<bool> = (0..4)
+            .map(|_| {
+                let test_result = test_object.execute().unwrap();
+                matches!(test_result.actions.last(), Some(BattleAction::Ability(_, _)))
+            })
+            .collect();
+
+        assert_eq!(vec![true, false, true, false], used);
+    }
+
+    #[test]
+    fn charge_reaches_distant_opponent() {
+        let active = Combatant {
+            abilities: vec![Ability::new("Charge", AbilityKind::Charge, 6, 3, 5)],
+            ..combatant("Active", 10)
+        };
+
+        let mut test_object = turn_on_map(
+            active,
+            vec![(combatant("Far", 10), Point::new(1, 7))],
+            Point::new(1, 1),
+        );
+
+        let test_result = test_object.execute().unwrap();
+
+        assert_eq!(5, test_result.opponents[0].hp);
+        assert_eq!(Some(Point::new(1, 6)), test_result.map.position_for("Active"));
+    }
+
+    #[test]
+    fn heal_ally_picks_weakest_ally() {
+        let active = Combatant {
+            abilities: vec![Ability::new("Mend", AbilityKind::HealAlly, 3, 3, 5)],
+            team: Some(String::from("blue")),
+            ..combatant("Active", 10)
+        };
+        let ally = Combatant {
+            team: Some(String::from("blue")),
+            max_hp: 10,
+            ..combatant("Ally", 3)
+        };
+        let enemy = Combatant {
+            team: Some(String::from("red")),
+            ..combatant("Enemy", 1)
+        };
+
+        let mut test_object = turn_on_map(
+            active,
+            vec![(ally, Point::new(1, 2)), (enemy, Point::new(8, 8))],
+            Point::new(1, 1),
+        );
+
+        let test_result = test_object.execute().unwrap();
+
+        assert_eq!(8, test_result.opponents[0].hp);
+        assert_eq!(1, test_result.opponents[1].hp);
+        assert!(matches!(test_result.actions[0], BattleAction::Heal(1, _)));
+    }
+
+    #[test]
+    fn heal_ally_not_used_without_wounded() {
+        let active = Combatant {
+            abilities: vec![Ability::new("Mend", AbilityKind::HealAlly, 3, 3, 5)],
+            max_hp: 10,
+            ..combatant("Active", 10)
+        };
+
+        let mut test_object = turn_on_map(
+            active,
+            vec![(combatant("Near", 10), Point::new(2, 1))],
+            Point::new(1, 1),
+        );
+
+        let test_result = test_object.execute().unwrap();
+
+        assert!(test_result.active_combatant.abilities[0].is_ready());
+        assert!(matches!(test_result.actions[0], BattleAction::Attack(1, _)));
+    }
+
+    #[test]
+    fn shield_raised_when_opponent_near() {
+        let active = Combatant {
+            abilities: vec![Ability::new("Guard", AbilityKind::Shield, 1, 3, 2)],
+            ..combatant("Active", 10)
+        };
+
+        let mut test_object = turn_on_map(
+            active,
+            vec![(combatant("Near", 10), Point::new(2, 1))],
+            Point::new(1, 1),
+        );
+
+        let test_result = test_object.execute().unwrap();
+
+        assert_eq!(
+            vec![StatusEffect::new(StatusEffectKind::Shield, 1, 2)],
+            test_result.active_combatant.effects
+        );
+        assert_eq!(10, test_result.opponents[0].hp);
+    }
+
+    #[test]
+    fn allies_are_not_attacked() {
+        let active = Combatant {
+            team: Some(String::from("blue")),
+            ..combatant("Active", 10)
+        };
+        let ally = Combatant {
+            team: Some(String::from("blue")),
+            ..combatant("Ally", 10)
+        };
+
+        let mut test_object = turn_on_map(active, vec![(ally, Point::new(2, 1))], Point::new(1, 1));
+
+        let test_result = test_object.execute().unwrap();
+
+        assert_eq!(10, test_result.opponents[0].hp);
+        assert!(test_result.actions.is_empty());
+    }
+
+    #[test]
+    fn ranged_combatant_attacks_without_moving() {
+        let active = Combatant {
+            attack_range: 3,
+            ..combatant("Active", 10)
+        };
+
+        let mut test_object = turn_on_map(
+            active,
+            vec![(combatant("Target", 10), Point::new(1, 4))],
+            Point::new(1, 1),
+        );
+
+        let test_result = test_object.execute().unwrap();
+
+        assert_eq!(8, test_result.opponents[0].hp);
+        assert_eq!(Some(Point::new(1, 1)), test_result.map.position_for("Active"));
+    }
+
+    #[test]
+    fn diagonal_opponent_attacked_on_hex_map() {
+        let mut map = Map::with_topology(10, 10, Topology::Hex);
+        map.place(String::from("Active"), Point::new(1, 1)).unwrap();
+        map.place(String::from("Target"), Point::new(2, 0)).unwrap();
+
+        let mut test_object = TurnState::new(combatant("Active", 10), vec![combatant("Target", 10)], map);
+
+        let test_result = test_object.execute().unwrap();
+
+        assert_eq!(8, test_result.opponents[0].hp);
+        assert_eq!(Some(Point::new(1, 1)), test_result.map.position_for("Active"));
+    }
+
+    #[test]
+    fn consumable_used_when_badly_wounded() {
+        let active = Combatant {
+            max_hp: 10,
+            consumables: vec![Item { heal: 4, ..Item::new("Potion", ItemKind::Consumable) }],
+            ..combatant("Active", 3)
+        };
+
+        let mut test_object = turn_on_map(
+            active,
+            vec![(combatant("Near", 10), Point::new(2, 1))],
+            Point::new(1, 1),
+        );
+
+        let test_result = test_object.execute().unwrap();
+
+        assert_eq!(7, test_result.active_combatant.hp);
+        assert!(test_result.active_combatant.consumables.is_empty());
+        assert!(matches!(test_result.actions[0], BattleAction::Heal(1, _)));
+        assert!(matches!(test_result.actions[1], BattleAction::Attack(1, _)));
+    }
 }
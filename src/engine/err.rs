@@ -6,5 +6,7 @@ pub enum Error {
     DestinationOccupied(Point, Point),
     DestinationOutOfBounds(Point, Point),
     MapLocationEmpty(Point),
-    NoOpponentsPresent
+    NoOpponentsPresent,
+    /// Every location of the map is occupied
+    MapFull,
 }
\ No newline at end of file
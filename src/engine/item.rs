@@ -0,0 +1,39 @@
+/// The slot an item takes, a combatant carries at most one weapon and one armor
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ItemKind {
+    Weapon,
+    Armor,
+    /// Used up once the combatant is badly wounded
+    Consumable,
+}
+
+/// A piece of equipment adding to the base stats of a combatant
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Item {
+    pub name: String,
+    pub kind: ItemKind,
+    /// Added to the damage of every attack
+    pub damage: u16,
+    /// Subtracted from the damage of every hit taken
+    pub armor: u16,
+    /// The number of steps attacks reach, the largest range of all items counts
+    pub range: u32,
+    /// Added to the steps the combatant can move, heavy items slow it down
+    pub speed: i8,
+    /// Hit points restored when a consumable is used
+    pub heal: u16,
+}
+
+impl Item {
+    pub fn new(name: &str, kind: ItemKind) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind,
+            damage: 0,
+            armor: 0,
+            range: 0,
+            speed: 0,
+            heal: 0,
+        }
+    }
+}
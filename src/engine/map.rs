@@ -1,142 +1,238 @@
 use std::collections::HashMap;
 
-use crate::types::point::Point;
+use rand::{thread_rng, Rng};
+
+use crate::types::{
+    point::{Coordinate, Point},
+    topology::Topology,
+};
 
 use super::err::Error;
 
+// Random locations tried before the map is searched for a free location in order
+const RANDOM_PLACEMENT_ATTEMPTS: u32 = 100;
+
+// The width and height of the chunks range queries look at
+const CHUNK_SIZE: Coordinate = 16;
+
+/// The locations of the combatants, only occupied locations are stored so the size of the map costs nothing
+///
+/// Locations are indexed by id and by chunk, so lookups do not depend on the number of combatants
+/// and range queries only look at the chunks the range overlaps.
 #[derive(Debug, Clone)]
 pub struct Map {
-    width: u8,
-    height: u8,
+    width: Coordinate,
+    height: Coordinate,
+    topology: Topology,
     pois: HashMap<Point, String>,
+    positions: HashMap<String, Point>,
+    chunks: HashMap<(Coordinate, Coordinate), Vec<Point>>,
 }
 
 impl Map {
-    pub fn new(width: u8, height: u8) -> Self {
+    #[allow(dead_code)] // Currently used in unit tests, battles always choose a topology
+    pub fn new(width: Coordinate, height: Coordinate) -> Self {
+        Map::with_topology(width, height, Topology::default())
+    }
+
+    pub fn with_topology(width: Coordinate, height: Coordinate, topology: Topology) -> Self {
         Map {
             width,
             height,
+            topology,
             pois: HashMap::new(),
+            positions: HashMap::new(),
+            chunks: HashMap::new(),
         }
     }
 
-    pub fn get_width(&self) -> u8 {
+    pub fn get_topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn get_width(&self) -> Coordinate {
         self.width
     }
 
-    pub fn get_height(&self) -> u8 {
+    pub fn get_height(&self) -> Coordinate {
         self.height
     }
 
+    /// The occupied locations ordered by the id on them, so the same map is always listed the same way
     pub fn get_pois(&self) -> Vec<(Point, String)> {
-        self.pois
+        let mut pois: Vec<(Point, String)> = self
+            .pois
             .iter()
-            .clone()
             .map(|poi| (poi.0.clone(), poi.1.clone()))
-            .collect()
-    }
+            .collect();
+        pois.sort_by(|a, b| a.1.cmp(&b.1));
 
-    pub fn remove_poi(&self, id: &str) -> Self {
-        let poi_location = self.position_for(id);
-
-        match poi_location {
-            Some(l) => {
-                let mut self_clone = self.clone();
+        pois
+    }
 
-                self_clone.pois.remove(&l);
+    /// Takes the id off the map, returns where it was
+    pub fn remove_poi(&mut self, id: &str) -> Option<Point> {
+        let location = self.position_for(id)?;
+        self.remove(location);
 
-                self_clone
-            },
-            None => self.clone(),
-        }
+        Some(location)
     }
 
     pub fn place_randomly(&mut self, id: String) -> Result<bool, Error> {
-        let position = self.unoccupied_location();
+        self.place_randomly_with(id, &mut thread_rng())
+    }
 
+    /// Places the id on a free location drawn from the given generator
+    pub fn place_randomly_with(&mut self, id: String, rng: &mut impl Rng) -> Result<bool, Error> {
         if self.position_for(&id).is_some() {
             return Err(Error::UserAlreadyOnMap);
         }
 
-        self.pois.insert(position, id);
+        let position = self.unoccupied_location(rng).ok_or(Error::MapFull)?;
+
+        self.insert(id, position);
         Ok(true)
     }
 
-    #[allow(dead_code)] // Currently used in unit tests and for the future feature of manually playing the combatants
+    /// Places the id on the given location, used to set up replayed battles
     pub fn place(&mut self, id: String, location: Point) -> Result<bool, Error> {
+        if self.position_for(&id).is_some() {
+            return Err(Error::UserAlreadyOnMap);
+        }
+
+        let map_bounds: Point = self.map_bounds();
+        if !location.within(&map_bounds) {
+            return Err(Error::DestinationOutOfBounds(location, map_bounds));
+        }
+
         if self.is_occupied(location) {
             return Err(Error::DestinationOccupied(Point::new(0, 0), location));
         }
 
-        self.pois.insert(location.clone(), id.clone());
+        self.insert(id, location);
 
         Ok(true)
     }
 
     pub fn move_to(&mut self, origin: Point, goal: Point) -> Result<bool, Error> {
-        let pois_clone = self.pois.clone();
-        let origin_id: Option<&String> = pois_clone.get(&origin);
-
         // if the origin and goal position is the same then we have already successfully moved to the goal!
         if origin == goal {
             return Ok(true);
         }
 
-        match origin_id {
-            Some(id) => {
-                // if the origin and destination are the same point the the move is successful
-                if origin == goal {
-                    return Ok(true);
-                }
-
-                // check that the destination is inside the map bounds
-                let map_bounds: Point = self.map_bounds();
-                if goal > map_bounds {
-                    return Err(Error::DestinationOutOfBounds(goal, map_bounds));
-                }
+        if !self.is_occupied(origin) {
+            return Err(Error::MapLocationEmpty(origin));
+        }
 
-                // if the destination is occupied then we can not move to the desired location
-                if self.is_occupied(goal) {
-                    return Err(Error::DestinationOccupied(origin, goal));
-                }
+        // check that the destination is inside the map bounds
+        let map_bounds: Point = self.map_bounds();
+        if !goal.within(&map_bounds) {
+            return Err(Error::DestinationOutOfBounds(goal, map_bounds));
+        }
 
-                self.pois.remove(&origin);
-                self.pois.insert(goal, id.clone());
+        // if the destination is occupied then we can not move to the desired location
+        if self.is_occupied(goal) {
+            return Err(Error::DestinationOccupied(origin, goal));
+        }
 
-                Ok(true)
-            }
-            None => Err(Error::MapLocationEmpty(origin)),
+        if let Some(id) = self.remove(origin) {
+            self.insert(id, goal);
         }
+
+        Ok(true)
     }
 
     pub fn get_occupied_neighbors(&self, location: Point) -> Vec<String> {
         // if the provided location is outside of the map bounds then we return an empty array
-        if location > self.map_bounds() {
+        if !location.within(&self.map_bounds()) {
             return vec![];
         }
 
-        let mut occupied_neighbors = vec![];
+        location
+            .neighbors(self.topology, Some(self.map_bounds()))
+            .iter()
+            .filter_map(|neighbor| self.pois.get(neighbor).cloned())
+            .collect()
+    }
+
+    /// The ids at the locations at most the given number of steps away, ordered by location
+    pub fn occupants_within(&self, center: Point, range: u32) -> Vec<String> {
+        let range = range.min(Coordinate::MAX as u32) as Coordinate;
+        // Every location within range lies within this square, whatever the topology
+        let (min_x, min_y) = (center.x.saturating_sub(range), center.y.saturating_sub(range));
+        let (max_x, max_y) = (center.x.saturating_add(range), center.y.saturating_add(range));
+
+        let (first_chunk, last_chunk) = (Map::chunk_of(Point::new(min_x, min_y)), Map::chunk_of(Point::new(max_x, max_y)));
+        let overlapped = (last_chunk.0 - first_chunk.0 + 1) as usize * (last_chunk.1 - first_chunk.1 + 1) as usize;
+
+        // Large ranges look at the occupied chunks instead of every chunk they overlap
+        let chunks: Vec<&Vec<Point>> = match overlapped > self.chunks.len() {
+            true => self
+                .chunks
+                .iter()
+                .filter(|((x, y), _)| (first_chunk.0..=last_chunk.0).contains(x) && (first_chunk.1..=last_chunk.1).contains(y))
+                .map(|(_, locations)| locations)
+                .collect(),
+            false => (first_chunk.0..=last_chunk.0)
+                .flat_map(|x| (first_chunk.1..=last_chunk.1).map(move |y| (x, y)))
+                .filter_map(|chunk| self.chunks.get(&chunk))
+                .collect(),
+        };
+
+        let mut locations: Vec<Point> = chunks
+            .into_iter()
+            .flatten()
+            .filter(|p| center.steps_to(p, self.topology) <= range as u32)
+            .copied()
+            .collect();
+        locations.sort_by_key(|p| (p.x, p.y));
+
+        locations.iter().filter_map(|p| self.pois.get(p).cloned()).collect()
+    }
+
+    fn insert(&mut self, id: String, location: Point) {
+        self.chunks.entry(Map::chunk_of(location)).or_default().push(location);
+        self.positions.insert(id.clone(), location);
+        self.pois.insert(location, id);
+    }
+
+    fn remove(&mut self, location: Point) -> Option<String> {
+        let id = self.pois.remove(&location)?;
+        self.positions.remove(&id);
 
-        for neighbor in location.neighbors(None) {
-            for poi in self.pois.clone() {
-                if neighbor == poi.0 {
-                    occupied_neighbors.push(poi.1.clone())
-                }
+        let chunk = Map::chunk_of(location);
+        if let Some(locations) = self.chunks.get_mut(&chunk) {
+            locations.retain(|p| *p != location);
+            if locations.is_empty() {
+                self.chunks.remove(&chunk);
             }
         }
 
-        return occupied_neighbors;
+        Some(id)
     }
 
-    fn unoccupied_location(&self) -> Point {
-        let mut starting_position: Point = Point::random(Some(Point::new(self.height, self.width)));
+    fn chunk_of(location: Point) -> (Coordinate, Coordinate) {
+        (location.x / CHUNK_SIZE, location.y / CHUNK_SIZE)
+    }
 
-        // ToDo: Potential endless loop id all positions are occupied
-        while self.is_occupied(starting_position) {
-            starting_position = Point::random(Some(Point::new(self.height, self.width)));
+    /// A random free location, none if every location is occupied
+    fn unoccupied_location(&self, rng: &mut impl Rng) -> Option<Point> {
+        let area = self.width as u64 * self.height as u64;
+        if self.pois.len() as u64 >= area {
+            return None;
         }
 
-        return starting_position;
+        for _ in 0..RANDOM_PLACEMENT_ATTEMPTS {
+            let position = Point::random_with(Some(Point::new(self.width, self.height)), rng);
+            if !self.is_occupied(position) {
+                return Some(position);
+            }
+        }
+
+        // Crowded maps are searched in order, there is at least one free location left
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| Point::new(x, y)))
+            .find(|p| !self.is_occupied(*p))
     }
 
     pub fn is_occupied(&self, _location: Point) -> bool {
@@ -144,18 +240,14 @@ impl Map {
     }
 
     pub fn position_for(&self, id: &str) -> Option<Point> {
-        for poi in self.pois.clone() {
-            if poi.1 == id {
-                return Some(poi.0.clone());
-            }
-        }
-        None
+        self.positions.get(id).copied()
     }
 
+    /// The last location on the map
     pub fn map_bounds(&self) -> Point {
         Point {
-            x: self.width,
-            y: self.height,
+            x: self.width.saturating_sub(1),
+            y: self.height.saturating_sub(1),
         }
     }
 }
@@ -178,4 +270,95 @@ mod tests {
 
         assert!(!test_object.is_occupied(Point::new(1, 1)));
     }
+
+    #[test]
+    fn place_checks_bounds_and_ids(){
+        let mut test_object = Map::new(10, 10);
+
+        test_object.place(String::from("value"), Point::new(1, 1)).unwrap();
+
+        assert!(matches!(test_object.place(String::from("value"), Point::new(2, 2)), Err(Error::UserAlreadyOnMap)));
+        assert!(matches!(test_object.place(String::from("other"), Point::new(10, 2)), Err(Error::DestinationOutOfBounds(_, _))));
+        assert!(matches!(test_object.place(String::from("other"), Point::new(1, 1)), Err(Error::DestinationOccupied(_, _))));
+    }
+
+    #[test]
+    fn place_randomly_fills_whole_map(){
+        let mut test_object = Map::new(3, 2);
+
+        for i in 0..6 {
+            test_object.place_randomly(i.to_string()).unwrap();
+        }
+
+        assert!(matches!(test_object.place_randomly(String::from("7")), Err(Error::MapFull)));
+        assert!(test_object.get_pois().iter().all(|(p, _)| p.within(&test_object.map_bounds())));
+        assert_eq!(
+            vec!["0", "1", "2", "3", "4", "5"],
+            test_object.get_pois().iter().map(|(_, id)| id.as_str()).collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn place_randomly_on_large_map(){
+        let mut test_object = Map::new(4000, 3000);
+
+        test_object.place_randomly(String::from("value")).unwrap();
+
+        assert!(test_object.position_for("value").unwrap().within(&Point::new(3999, 2999)));
+    }
+
+    #[test]
+    fn move_to_out_of_bounds(){
+        let mut test_object = Map::new(10, 10);
+        test_object.place(String::from("value"), Point::new(9, 9)).unwrap();
+
+        assert!(matches!(
+            test_object.move_to(Point::new(9, 9), Point::new(9, 10)),
+            Err(Error::DestinationOutOfBounds(_, _))
+        ));
+    }
+
+    #[test]
+    fn indexes_follow_moves(){
+        let mut test_object = Map::new(100, 100);
+        test_object.place(String::from("value"), Point::new(15, 15)).unwrap();
+
+        test_object.move_to(Point::new(15, 15), Point::new(16, 15)).unwrap();
+
+        assert_eq!(Some(Point::new(16, 15)), test_object.position_for("value"));
+        assert_eq!(vec![String::from("value")], test_object.occupants_within(Point::new(20, 15), 4));
+        assert!(test_object.occupants_within(Point::new(12, 15), 3).is_empty());
+
+        assert_eq!(Some(Point::new(16, 15)), test_object.remove_poi("value"));
+        assert_eq!(None, test_object.position_for("value"));
+        assert!(test_object.occupants_within(Point::new(16, 15), 0).is_empty());
+    }
+
+    #[test]
+    fn occupants_within_range_across_chunks(){
+        let mut test_object = Map::with_topology(100, 100, Topology::Square8);
+        test_object.place(String::from("a"), Point::new(10, 10)).unwrap();
+        test_object.place(String::from("b"), Point::new(40, 20)).unwrap();
+        test_object.place(String::from("c"), Point::new(44, 44)).unwrap();
+        test_object.place(String::from("d"), Point::new(31, 39)).unwrap();
+
+        let found = test_object.occupants_within(Point::new(31, 30), 10);
+
+        assert_eq!(vec![String::from("d"), String::from("b")], found);
+        assert_eq!(4, test_object.occupants_within(Point::new(0, 0), u32::MAX).len());
+    }
+
+    #[test]
+    fn occupied_neighbors_follow_topology(){
+        let mut square = Map::new(10, 10);
+        let mut hex = Map::with_topology(10, 10, Topology::Hex);
+
+        for map in [&mut square, &mut hex] {
+            map.place(String::from("diagonal"), Point::new(2, 0)).unwrap();
+            map.place(String::from("beside"), Point::new(2, 1)).unwrap();
+        }
+
+        assert_eq!(vec![String::from("beside")], square.get_occupied_neighbors(Point::new(1, 1)));
+        assert_eq!(2, hex.get_occupied_neighbors(Point::new(1, 1)).len());
+    }
 }
\ No newline at end of file
@@ -1,9 +1,10 @@
-use crate::types::point::Point;
+use crate::types::{point::Point, topology::Topology};
 
 pub struct MovementEngine {
     current_position: Point,
     enemies: Vec<Point>,
     step_limit: Option<usize>,
+    topology: Topology,
 }
 
 #[derive(Debug, Clone)]
@@ -27,34 +28,42 @@ impl MovementResult {
     pub fn has_moved(&self) -> bool {
         self.steps.len() != 0
     }
+
+    /// Ends the movement on the last step before the first occupied location, routes do not avoid other combatants
+    pub fn until_blocked(mut self, is_occupied: impl Fn(Point) -> bool) -> Self {
+        if let Some(blocked) = self.steps.iter().position(|step| is_occupied(*step)) {
+            self.steps.truncate(blocked);
+        }
+        self.last_position = self.steps.last().copied().unwrap_or(self.start);
+
+        self
+    }
 }
 
 impl MovementEngine {
-    pub fn new(current_position: Point, enemies: Vec<Point>, step_limit: Option<usize>) -> Self {
+    pub fn new(current_position: Point, enemies: Vec<Point>, step_limit: Option<usize>, topology: Topology) -> Self {
         MovementEngine {
             current_position,
             enemies,
             step_limit,
+            topology,
         }
     }
 
     pub fn do_move(&self) -> MovementResult {
         // Determine movement target
-        let movement_goal = self.current_position.closest(self.enemies.to_vec());
+        let movement_goal = self.current_position.closest(self.enemies.to_vec(), self.topology);
 
         if self
             .current_position
-            .neighbors(None)
+            .neighbors(self.topology, None)
             .contains(&movement_goal)
             || self.current_position == movement_goal
         {
             return MovementResult::already_at_goal(self.current_position, movement_goal);
         }
 
-        let mut path: Vec<Point> =
-            MovementEngine::find_route(self.current_position.clone(), &movement_goal, vec![])[1..]
-                .to_vec();
-        path.truncate(self.normalized_step_limit());
+        let path: Vec<Point> = self.find_route(&movement_goal, self.normalized_step_limit());
 
         let destination_reached: Option<Point> = path.to_vec().pop();
 
@@ -81,19 +90,24 @@ impl MovementEngine {
         }
     }
 
-    fn find_route(start: Point, goal: &Point, path: Vec<Point>) -> Vec<Point> {
-        let mut new_path = path.to_vec();
-        new_path.push(start.clone());
+    /// The steps towards the goal, without the start and the goal, at most `step_limit` of them
+    fn find_route(&self, goal: &Point, step_limit: usize) -> Vec<Point> {
+        let mut path: Vec<Point> = vec![];
+        let mut position = self.current_position;
+
+        while path.len() < step_limit {
+            let next_step = goal.closest(position.neighbors(self.topology, None), self.topology);
 
-        // When we and the goal have the same coordinates we have reached our goal
-        if &start == goal {
-            // return the path taken without the goals coordinates
-            return path;
-        } else {
-            let next_step = goal.closest(start.neighbors(None));
+            // the goal itself is occupied, the route ends next to it
+            if &next_step == goal {
+                break;
+            }
 
-            return MovementEngine::find_route(next_step, goal, new_path.to_vec());
+            path.push(next_step);
+            position = next_step;
         }
+
+        path
     }
 }
 
@@ -111,6 +125,7 @@ mod tests {
             current_position: player,
             enemies: vec![enemy1, enemy2],
             step_limit: None,
+            topology: Topology::Square4,
         }
         .do_move();
 
@@ -128,6 +143,7 @@ mod tests {
             current_position: player,
             enemies: vec![enemy1],
             step_limit: None,
+            topology: Topology::Square4,
         }
         .do_move();
 
@@ -145,6 +161,7 @@ mod tests {
             current_position: player,
             enemies: vec![enemy1],
             step_limit: Some(3),
+            topology: Topology::Square4,
         }
         .do_move();
 
@@ -163,6 +180,7 @@ mod tests {
             current_position: player,
             enemies: vec![enemy1],
             step_limit: Some(30),
+            topology: Topology::Square4,
         }
         .do_move();
 
@@ -181,6 +199,7 @@ mod tests {
             current_position: player,
             enemies: vec![enemy1],
             step_limit: Some(30),
+            topology: Topology::Square4,
         }
         .do_move();
 
@@ -200,6 +219,7 @@ mod tests {
             current_position: player,
             enemies: vec![enemy1, enemy2],
             step_limit: Some(30),
+            topology: Topology::Square4,
         }
         .do_move();
 
@@ -208,4 +228,76 @@ mod tests {
         assert_eq!(enemy1, result.goal);
         assert_eq!(player, result.last_position);
     }
+
+    #[test]
+    fn do_move_diagonally(){
+        let player = Point::new(3, 3);
+        let enemy1 = Point::new(10, 20);
+
+        let result: MovementResult = MovementEngine::new(player, vec![enemy1], Some(30), Topology::Square8).do_move();
+
+        assert_eq!(Point::new(10, 19), result.last_position);
+        assert_eq!(16, result.steps.len());
+    }
+
+    #[test]
+    fn do_move_on_hex_grid_takes_shortest_path(){
+        let player = Point::new(8, 8);
+
+        for goal in [Point::new(0, 0), Point::new(15, 0), Point::new(0, 15), Point::new(15, 15), Point::new(12, 3)] {
+            let result: MovementResult = MovementEngine::new(player, vec![goal], None, Topology::Hex).do_move();
+
+            // the path ends next to the goal
+            assert_eq!(player.steps_to(&goal, Topology::Hex) as usize - 1, result.steps.len());
+            assert_eq!(1, result.last_position.steps_to(&goal, Topology::Hex));
+        }
+    }
+
+    #[test]
+    fn movement_until_blocked() {
+        let steps = vec![Point::new(1, 0), Point::new(2, 0), Point::new(3, 0)];
+        let blocked_at = |x: u16| move |p: Point| p == Point::new(x, 0);
+
+        let partly = movement(steps.clone()).until_blocked(blocked_at(3));
+        let not_at_all = movement(steps.clone()).until_blocked(blocked_at(1));
+        let free = movement(steps).until_blocked(blocked_at(9));
+
+        assert_eq!(Point::new(2, 0), partly.last_position);
+        assert_eq!(2, partly.steps.len());
+        assert!(!not_at_all.has_moved());
+        assert_eq!(Point::new(0, 0), not_at_all.last_position);
+        assert_eq!(Point::new(3, 0), free.last_position);
+    }
+
+    fn movement(steps: Vec<Point>) -> MovementResult {
+        MovementResult {
+            start: Point::new(0, 0),
+            goal: Point::new(4, 0),
+            last_position: *steps.last().unwrap(),
+            steps,
+        }
+    }
+
+    #[test]
+    fn do_move_across_largest_map() {
+        let player = Point::new(0, 0);
+        let enemy1 = Point::new(65534, 65534);
+
+        let limited: MovementResult = MovementEngine::new(player, vec![enemy1], Some(5), Topology::Square4).do_move();
+        let unlimited: MovementResult = MovementEngine::new(player, vec![enemy1], None, Topology::Square8).do_move();
+
+        assert_eq!(5, limited.steps.len());
+        assert_eq!(65533, unlimited.steps.len());
+        assert_eq!(Point::new(65533, 65533), unlimited.last_position);
+    }
+
+    #[test]
+    fn do_move_hex_neighbor(){
+        let player = Point::new(2, 2);
+        let enemy1 = Point::new(3, 1);
+
+        let result: MovementResult = MovementEngine::new(player, vec![enemy1], Some(30), Topology::Hex).do_move();
+
+        assert!(!result.has_moved());
+    }
 }
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use super::{
+    ability::AbilityKind, battle_actions::BattleAction, battle_result::BattleResult,
+    combatant::Combatant,
+};
+
+/// How a single combatant fared in a battle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BattleOutcome {
+    /// The number of opponents the combatant dealt the final blow to
+    pub kills: u32,
+    pub survived: bool,
+    pub won: bool,
+    /// The winner comes first, then the other survivors, then the dead in the reverse order of their death.
+    /// Combatants that can not be told apart share a place.
+    pub place: u32,
+}
+
+/// The experience awarded after a battle and the stats gained with each level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressionRules {
+    pub xp_per_kill: u32,
+    pub xp_for_survival: u32,
+    pub xp_for_win: u32,
+    /// The experience needed for each level
+    pub xp_per_level: u32,
+    pub hp_per_level: u16,
+    pub dmg_per_level: u16,
+}
+
+impl Default for ProgressionRules {
+    fn default() -> Self {
+        Self {
+            xp_per_kill: 50,
+            xp_for_survival: 20,
+            xp_for_win: 100,
+            xp_per_level: 200,
+            hp_per_level: 5,
+            dmg_per_level: 1,
+        }
+    }
+}
+
+impl ProgressionRules {
+    /// The experience a combatant gains for the outcome of a battle
+    pub fn experience_for(&self, outcome: &BattleOutcome) -> u32 {
+        let mut experience = self.xp_per_kill.saturating_mul(outcome.kills);
+
+        if outcome.survived {
+            experience = experience.saturating_add(self.xp_for_survival);
+        }
+        if outcome.won {
+            experience = experience.saturating_add(self.xp_for_win);
+        }
+
+        experience
+    }
+
+    /// The level reached with the given experience, every combatant starts at level 1
+    pub fn level_for(&self, experience: u32) -> u32 {
+        1 + experience / self.xp_per_level.max(1)
+    }
+}
+
+impl BattleResult {
+    /// How every combatant fared, keyed by the name of the combatant
+    pub fn outcomes(&self) -> HashMap<String, BattleOutcome> {
+        let mut outcomes: HashMap<String, BattleOutcome> = self
+            .combatants
+            .iter()
+            .map(|c| {
+                let outcome = BattleOutcome {
+                    kills: 0,
+                    survived: c.is_alive(),
+                    won: self.winner.as_ref().map(|w| w == c).unwrap_or(false),
+                    place: 1 + self
+                        .combatants
+                        .iter()
+                        .filter(|other| self.placement_key(other) > self.placement_key(c))
+                        .count() as u32,
+                };
+
+                (c.name.clone(), outcome)
+            })
+            .collect();
+
+        for action in self.actions.iter() {
+            let (killer, kills) = match action {
+                BattleAction::Attack(_, attack) if attack.victim.hp == 0 => (&attack.assailant.name, 1),
+                BattleAction::Ability(_, ability)
+                    if matches!(ability.kind, AbilityKind::AreaAttack | AbilityKind::Charge) =>
+                {
+                    let kills = ability
+                        .targets
+                        .iter()
+                        .filter(|t| t.amount > 0 && t.remaining_hp == 0)
+                        .count() as u32;
+
+                    (&ability.user, kills)
+                }
+                _ => continue,
+            };
+
+            if let Some(outcome) = outcomes.get_mut(killer) {
+                outcome.kills += kills;
+            }
+        }
+
+        outcomes
+    }
+
+    // Higher keys are better places, combatants killed in the last round were not buried yet
+    fn placement_key(&self, combatant: &Combatant) -> (bool, bool, u32) {
+        let won = self.winner.as_ref().map(|w| w == combatant).unwrap_or(false);
+        let died = combatant.died_in_round.unwrap_or(self.round_number);
+
+        match combatant.is_alive() {
+            true => (won, true, u32::MAX),
+            false => (won, false, died),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{
+        battle_actions::{AbilityTarget, BattleAbilityAction, BattleAttackAction},
+        battle_engine::BattleRoundState,
+        battle_rules::BattleRules,
+        map::Map,
+    };
+
+    use super::*;
+
+    fn combatant(name: &str, hp: u16) -> Combatant {
+        Combatant {
+            name: name.to_owned(),
+            hp,
+            ..Default::default()
+        }
+    }
+
+    fn initial() -> BattleRoundState {
+        BattleRoundState {
+            combatants: vec![],
+            map: Map::new(4, 4),
+            actions: vec![],
+            round_number: 0,
+        }
+    }
+
+    #[test]
+    fn outcomes_count_kills() {
+        let winner = combatant("winner", 5);
+        let result = BattleResult {
+            initial: initial(),
+            id: None,
+            unrecorded: vec![],
+            rules: BattleRules::default(),
+            seed: 0,
+            combatants: vec![winner.clone(), combatant("first", 0), combatant("second", 0)],
+            map: Map::new(4, 4),
+            actions: vec![
+                BattleAction::Attack(
+                    1,
+                    BattleAttackAction {
+                        assailant: winner.clone(),
+                        victim: combatant("first", 0),
+                        damage: 3,
+                    },
+                ),
+                BattleAction::Ability(
+                    2,
+                    BattleAbilityAction {
+                        user: String::from("winner"),
+                        ability: String::from("Whirlwind"),
+                        kind: AbilityKind::AreaAttack,
+                        targets: vec![AbilityTarget {
+                            name: String::from("second"),
+                            amount: 2,
+                            remaining_hp: 0,
+                        }],
+                        movement: None,
+                    },
+                ),
+            ],
+            round_number: 2,
+            is_analyzed: true,
+            winner: Some(winner),
+        };
+
+        let outcomes = result.outcomes();
+
+        assert_eq!(BattleOutcome { kills: 2, survived: true, won: true, place: 1 }, outcomes["winner"]);
+        assert_eq!(BattleOutcome { place: 2, ..Default::default() }, outcomes["first"]);
+        assert_eq!(2, outcomes["second"].place);
+    }
+
+    #[test]
+    fn outcomes_place_by_death() {
+        let result = BattleResult {
+            initial: initial(),
+            id: None,
+            unrecorded: vec![],
+            rules: BattleRules::default(),
+            seed: 0,
+            combatants: vec![
+                Combatant { died_in_round: Some(2), ..combatant("early", 0) },
+                Combatant { died_in_round: Some(5), ..combatant("late", 0) },
+                combatant("last", 0),
+                combatant("survivor", 3),
+            ],
+            map: Map::new(4, 4),
+            actions: vec![],
+            round_number: 7,
+            is_analyzed: true,
+            winner: None,
+        };
+
+        let outcomes = result.outcomes();
+
+        assert_eq!(1, outcomes["survivor"].place);
+        assert_eq!(2, outcomes["last"].place);
+        assert_eq!(3, outcomes["late"].place);
+        assert_eq!(4, outcomes["early"].place);
+    }
+
+    #[test]
+    fn experience_and_level() {
+        let rules = ProgressionRules::default();
+        let outcome = BattleOutcome { kills: 2, survived: true, won: false, place: 2 };
+
+        assert_eq!(120, rules.experience_for(&outcome));
+        assert_eq!(1, rules.level_for(199));
+        assert_eq!(3, rules.level_for(400));
+    }
+}
@@ -0,0 +1,72 @@
+/// The rating every combatant starts with
+pub const INITIAL_RATING: f64 = 1500.0;
+
+// The maximal rating change of a single battle
+const K_FACTOR: f64 = 32.0;
+
+/// The expected score of a participant with the given rating against the opponent rating
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// The rating changes of a battle between any number of participants, given by their rating and place
+///
+/// Every participant is compared with every other one as if they played a match of their own,
+/// a better place counts as a win and the same place as a draw.
+pub fn rating_changes(participants: &[(f64, u32)]) -> Vec<f64> {
+    if participants.len() < 2 {
+        return vec![0.0; participants.len()];
+    }
+
+    let k = K_FACTOR / (participants.len() - 1) as f64;
+
+    participants
+        .iter()
+        .enumerate()
+        .map(|(i, (rating, place))| {
+            participants
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (opponent, opponent_place))| {
+                    let score = match place.cmp(opponent_place) {
+                        std::cmp::Ordering::Less => 1.0,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Greater => 0.0,
+                    };
+
+                    k * (score - expected_score(*rating, *opponent))
+                })
+                .sum()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn rating_changes_two_equal_participants() {
+        let changes = rating_changes(&[(INITIAL_RATING, 1), (INITIAL_RATING, 2)]);
+
+        assert_relative_eq!(16.0, changes[0]);
+        assert_relative_eq!(-16.0, changes[1]);
+    }
+
+    #[test]
+    fn rating_changes_free_for_all_sum_to_zero() {
+        let changes = rating_changes(&[(1600.0, 2), (1500.0, 1), (1400.0, 3), (1500.0, 3)]);
+
+        assert_relative_eq!(0.0, changes.iter().sum::<f64>(), epsilon = 1e-9);
+        assert!(changes[1] > 0.0);
+        assert!(changes[2] < 0.0);
+    }
+
+    #[test]
+    fn rating_changes_single_participant() {
+        assert_eq!(vec![0.0], rating_changes(&[(INITIAL_RATING, 1)]));
+    }
+}
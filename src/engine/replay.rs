@@ -0,0 +1,587 @@
+use std::fmt::Display;
+
+use crate::types::point::Point;
+
+use super::{
+    ability::{Ability, AbilityKind},
+    battle_actions::{BattleAbilityAction, BattleAction, BattleAttackAction, BattleHealAction},
+    battle_engine::BattleRoundState,
+    combatant::Combatant,
+    movement::MovementResult,
+    status_effect::StatusEffectTick,
+};
+
+/// Why an action log does not fit the state it is replayed on, every error names the round and the combatant
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    /// The combatant is not part of the battle or not on the map
+    UnknownCombatant(u32, String),
+    /// The combatant acted or was targeted while dead
+    DeadCombatant(u32, String),
+    /// The combatant used an ability it does not have
+    UnknownAbility(u32, String),
+    /// The move of the combatant does not start at its location, skips a location or ends on an occupied location
+    InvalidMove(u32, String),
+    /// The combatant hit a target beyond its reach
+    OutOfReach(u32, String),
+    /// The logged hit points, damage or healing of the combatant differ from the replayed ones
+    Mismatch(u32, String),
+    /// An action of an earlier round follows the actions of the given round
+    OutOfOrder(u32, String),
+    /// The combatant is placed outside of the map, on an occupied location or more than once
+    InvalidPlacement(u32, String),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (round, name, reason) = match self {
+            ReplayError::UnknownCombatant(r, n) => (r, n, "is not on the battlefield"),
+            ReplayError::DeadCombatant(r, n) => (r, n, "is dead"),
+            ReplayError::UnknownAbility(r, n) => (r, n, "used an ability it does not have"),
+            ReplayError::InvalidMove(r, n) => (r, n, "made an invalid move"),
+            ReplayError::OutOfReach(r, n) => (r, n, "hit a target out of reach"),
+            ReplayError::Mismatch(r, n) => (r, n, "does not have the logged hit points"),
+            ReplayError::OutOfOrder(r, n) => (r, n, "acted after a later round"),
+            ReplayError::InvalidPlacement(r, n) => (r, n, "is placed outside of the map, on an occupied location or twice"),
+        };
+
+        write!(f, "Round {}: {} {}", round, name, reason)
+    }
+}
+
+/// Reconstructs the state of a battle by applying its logged actions to the state before the first round
+///
+/// Every action is checked against the replayed state, moves have to be made step by step onto free locations
+/// and hits have to be within reach and deal the logged damage. Cooldowns of abilities are not replayed.
+#[derive(Debug)]
+pub struct Replay {
+    state: BattleRoundState,
+}
+
+impl Replay {
+    pub fn new(initial: BattleRoundState) -> Self {
+        Replay { state: initial }
+    }
+
+    /// The state at the end of the given round, applying the logged actions of all rounds up to it
+    pub fn state_at(mut self, actions: &[BattleAction], round: u32) -> Result<BattleRoundState, ReplayError> {
+        self.play(actions, 0, round)?;
+
+        Ok(self.state)
+    }
+
+    /// Replays one round after the other up to the given round, the state at the end of every round is visited
+    ///
+    /// The visit starts with the round the replay is at, round 0 for a replay of the initial state.
+    pub fn visit_rounds(
+        mut self,
+        actions: &[BattleAction],
+        last_round: u32,
+        mut visit: impl FnMut(&BattleRoundState),
+    ) -> Result<BattleRoundState, ReplayError> {
+        let mut index = 0;
+
+        for round in self.state.round_number..=last_round {
+            index = self.play(actions, index, round)?;
+            visit(&self.state);
+        }
+
+        Ok(self.state)
+    }
+
+    /// Applies the actions from the given index on up to the end of the round, returns the index of the first one left
+    fn play(&mut self, actions: &[BattleAction], mut index: usize, round: u32) -> Result<usize, ReplayError> {
+        while index < actions.len() && actions[index].round() <= round {
+            let action = &actions[index];
+            let action_round = action.round();
+
+            if action_round < self.state.round_number {
+                return Err(ReplayError::OutOfOrder(self.state.round_number, actor(action)));
+            }
+            self.enter_round(action_round);
+
+            // All effects of a combatant tick together at the start of its turn
+            let count = match action {
+                BattleAction::EffectTicked(r, name, _) => {
+                    let ticks: Vec<&StatusEffectTick> = actions[index..]
+                        .iter()
+                        .map_while(|a| match a {
+                            BattleAction::EffectTicked(tr, tn, tick) if tr == r && tn == name => Some(tick),
+                            _ => None,
+                        })
+                        .collect();
+
+                    self.tick(*r, name, &ticks)?;
+                    ticks.len()
+                }
+                _ => {
+                    self.apply(action)?;
+                    1
+                }
+            };
+
+            self.state.actions.extend(actions[index..index + count].iter().cloned());
+            index += count;
+        }
+
+        self.enter_round(round);
+
+        Ok(index)
+    }
+
+    /// Buries the combatants that died in the previous round, as the engine does at the start of every round
+    fn enter_round(&mut self, round: u32) {
+        if round > self.state.round_number {
+            self.state.bury_dead(self.state.round_number + 1);
+            self.state.round_number = round;
+        }
+    }
+
+    fn apply(&mut self, action: &BattleAction) -> Result<(), ReplayError> {
+        match action {
+            BattleAction::Move(r, name, movement) => self.move_along(*r, name, movement),
+            BattleAction::Attack(r, attack) => self.attack(*r, attack),
+            BattleAction::EffectApplied(r, name, effect) => {
+                self.living_mut(*r, name)?.apply_effect(effect.clone());
+                Ok(())
+            }
+            BattleAction::EffectTicked(r, name, tick) => self.tick(*r, name, &[tick]),
+            BattleAction::Ability(r, ability) => self.use_ability(*r, ability),
+            BattleAction::Heal(r, heal) => self.heal(*r, heal),
+            BattleAction::Revive(r, revived, location) => self.revive(*r, revived, *location),
+        }
+    }
+
+    fn move_along(&mut self, round: u32, name: &str, movement: &MovementResult) -> Result<(), ReplayError> {
+        let invalid = || ReplayError::InvalidMove(round, name.to_owned());
+        let topology = self.state.map.get_topology();
+
+        self.living(round, name)?;
+        if self.position(round, name)? != movement.start {
+            return Err(invalid());
+        }
+
+        let mut previous = movement.start;
+        for step in movement.steps.iter() {
+            if previous.steps_to(step, topology) != 1 {
+                return Err(invalid());
+            }
+            previous = *step;
+        }
+
+        if previous != movement.last_position {
+            return Err(invalid());
+        }
+
+        self.state
+            .map
+            .move_to(movement.start, movement.last_position)
+            .map(|_| ())
+            .map_err(|_| invalid())
+    }
+
+    fn attack(&mut self, round: u32, attack: &BattleAttackAction) -> Result<(), ReplayError> {
+        let assailant = self.living(round, &attack.assailant.name)?;
+        let (reach, damage) = (assailant.reach(), assailant.dmg);
+
+        self.within_reach(round, &attack.assailant.name, &attack.victim.name, reach)?;
+        self.hit(round, &attack.victim.name, damage, attack.damage, attack.victim.hp)
+    }
+
+    fn use_ability(&mut self, round: u32, action: &BattleAbilityAction) -> Result<(), ReplayError> {
+        let user = self.living_mut(round, &action.user)?;
+        let ability: Ability = match user.abilities.iter_mut().find(|a| a.name == action.ability) {
+            Some(ability) => {
+                ability.trigger();
+                ability.clone()
+            }
+            None => return Err(ReplayError::UnknownAbility(round, action.user.clone())),
+        };
+
+        if let Some(movement) = &action.movement {
+            self.move_along(round, &action.user, movement)?;
+        }
+
+        for target in action.targets.iter() {
+            match ability.kind {
+                AbilityKind::AreaAttack | AbilityKind::Charge => {
+                    let reach = match ability.kind {
+                        AbilityKind::Charge => 1,
+                        _ => ability.range,
+                    };
+
+                    self.within_reach(round, &action.user, &target.name, reach)?;
+                    self.hit(round, &target.name, ability.power, target.amount, target.remaining_hp)?;
+                }
+                // The healing is logged as a heal of its own
+                AbilityKind::HealAlly | AbilityKind::Shield => {
+                    if self.combatant(round, &target.name)?.hp != target.remaining_hp {
+                        return Err(ReplayError::Mismatch(round, target.name.clone()));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn heal(&mut self, round: u32, heal: &BattleHealAction) -> Result<(), ReplayError> {
+        let mismatch = || ReplayError::Mismatch(round, heal.target.name.clone());
+
+        self.living(round, &heal.healer.name)?;
+        let target = self.living_mut(round, &heal.target.name)?;
+
+        let gained = match &heal.item {
+            Some(item) => {
+                let index = target.consumables.iter().position(|i| &i.name == item).ok_or_else(mismatch)?;
+                let item = target.consumables.remove(index);
+                target.heal(item.heal)
+            }
+            None => target.heal(heal.amount),
+        };
+
+        match gained == heal.amount && target.hp == heal.target.hp {
+            true => Ok(()),
+            false => Err(mismatch()),
+        }
+    }
+
+    fn revive(&mut self, round: u32, revived: &Combatant, location: Point) -> Result<(), ReplayError> {
+        let combatant = self.combatant_mut(round, &revived.name)?;
+        if combatant.is_alive() || revived.hp == 0 {
+            return Err(ReplayError::Mismatch(round, revived.name.clone()));
+        }
+
+        combatant.hp = revived.hp;
+        combatant.effects.clear();
+        combatant.died_in_round = None;
+        combatant.revived += 1;
+
+        self.state
+            .map
+            .place(revived.name.clone(), location)
+            .map(|_| ())
+            .map_err(|_| ReplayError::InvalidPlacement(round, revived.name.clone()))
+    }
+
+    fn tick(&mut self, round: u32, name: &str, logged: &[&StatusEffectTick]) -> Result<(), ReplayError> {
+        let ticks = self.living_mut(round, name)?.tick_effects();
+
+        let matches = ticks.len() == logged.len()
+            && ticks.iter().zip(logged.iter()).all(|(tick, logged)| {
+                tick.kind == logged.kind
+                    && tick.amount == logged.amount
+                    && tick.remaining_hp == logged.remaining_hp
+                    && tick.remaining_turns == logged.remaining_turns
+            });
+
+        match matches {
+            true => Ok(()),
+            false => Err(ReplayError::Mismatch(round, name.to_owned())),
+        }
+    }
+
+    /// Deals the damage to the living target and compares the outcome to the logged one
+    fn hit(&mut self, round: u32, name: &str, damage: u16, dealt: u16, remaining_hp: u16) -> Result<(), ReplayError> {
+        let target = self.living_mut(round, name)?;
+
+        match target.take_hit(damage) == dealt && target.hp == remaining_hp {
+            true => Ok(()),
+            false => Err(ReplayError::Mismatch(round, name.to_owned())),
+        }
+    }
+
+    fn within_reach(&self, round: u32, name: &str, target: &str, reach: u32) -> Result<(), ReplayError> {
+        let steps = self
+            .position(round, name)?
+            .steps_to(&self.position(round, target)?, self.state.map.get_topology());
+
+        match steps <= reach {
+            true => Ok(()),
+            false => Err(ReplayError::OutOfReach(round, name.to_owned())),
+        }
+    }
+
+    fn position(&self, round: u32, name: &str) -> Result<Point, ReplayError> {
+        self.state
+            .map
+            .position_for(name)
+            .ok_or_else(|| ReplayError::UnknownCombatant(round, name.to_owned()))
+    }
+
+    fn combatant(&self, round: u32, name: &str) -> Result<&Combatant, ReplayError> {
+        self.state
+            .get_combatant(name)
+            .ok_or_else(|| ReplayError::UnknownCombatant(round, name.to_owned()))
+    }
+
+    fn combatant_mut(&mut self, round: u32, name: &str) -> Result<&mut Combatant, ReplayError> {
+        self.state
+            .combatants
+            .iter_mut()
+            .find(|c| c.name == name)
+            .ok_or_else(|| ReplayError::UnknownCombatant(round, name.to_owned()))
+    }
+
+    fn living(&self, round: u32, name: &str) -> Result<&Combatant, ReplayError> {
+        match self.combatant(round, name)? {
+            c if c.is_alive() => Ok(c),
+            _ => Err(ReplayError::DeadCombatant(round, name.to_owned())),
+        }
+    }
+
+    fn living_mut(&mut self, round: u32, name: &str) -> Result<&mut Combatant, ReplayError> {
+        match self.combatant_mut(round, name)? {
+            c if c.is_alive() => Ok(c),
+            _ => Err(ReplayError::DeadCombatant(round, name.to_owned())),
+        }
+    }
+}
+
+/// The name of the combatant that took the action
+fn actor(action: &BattleAction) -> String {
+    match action {
+        BattleAction::Move(_, name, _) | BattleAction::EffectApplied(_, name, _) | BattleAction::EffectTicked(_, name, _) => {
+            name.clone()
+        }
+        BattleAction::Attack(_, attack) => attack.assailant.name.clone(),
+        BattleAction::Ability(_, ability) => ability.user.clone(),
+        BattleAction::Heal(_, heal) => heal.healer.name.clone(),
+        BattleAction::Revive(_, revived, _) => revived.name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        engine::{
+            battle_engine::BattleEngine, battle_result::BattleResult, battle_rules::BattleRules,
+            battlefield::BattlefieldData, item::{Item, ItemKind}, status_effect::{StatusEffect, StatusEffectKind},
+        },
+        types::topology::Topology,
+    };
+
+    use super::*;
+
+    fn battle() -> BattleResult {
+        let combatants: Vec<Combatant> = (0..8)
+            .map(|i| Combatant {
+                name: format!("combatant {}", i),
+                hp: 30,
+                max_hp: 30,
+                dmg: 3 + i % 3,
+                armor: i % 2,
+                on_hit: match i % 4 {
+                    0 => vec![StatusEffect::new(StatusEffectKind::Poison, 2, 1)],
+                    1 => vec![StatusEffect::new(StatusEffectKind::Slow, 1, 1)],
+                    _ => vec![],
+                },
+                abilities: match i % 3 {
+                    0 => vec![Ability::new("Whirlwind", AbilityKind::AreaAttack, 1, 2, 4)],
+                    1 => vec![Ability::new("Charge", AbilityKind::Charge, 6, 3, 5)],
+                    _ => vec![Ability::new("Mend", AbilityKind::HealAlly, 2, 2, 4)],
+                },
+                team: Some(format!("team {}", i % 2)),
+                consumables: vec![Item { heal: 5, ..Item::new("potion", ItemKind::Consumable) }],
+                ..Default::default()
+            })
+            .collect();
+
+        let rules = BattleRules {
+            topology: Topology::Square8,
+            ..Default::default()
+        };
+
+        // Moves do not avoid occupied locations yet, seeds leading to such a collision are skipped
+        (0..100)
+            .find_map(|seed| {
+                BattleEngine::new(
+                    BattlefieldData {
+                        battlefield_height: 12,
+                        battlefield_width: 12,
+                        combatants: combatants.to_vec(),
+                    },
+                    BattleRules { seed: Some(seed), ..rules },
+                )
+                .unwrap()
+                .start_battle()
+                .ok()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn replay_reaches_final_state() {
+        let result = battle();
+
+        let state = Replay::new(result.initial.clone())
+            .state_at(&result.actions, result.round_number)
+            .unwrap();
+
+        for (replayed, fought) in state.combatants.iter().zip(result.combatants.iter()) {
+            assert_eq!(fought.name, replayed.name);
+            assert_eq!(fought.hp, replayed.hp);
+            assert_eq!(fought.effects, replayed.effects);
+            assert_eq!(result.map.position_for(&fought.name), state.map.position_for(&replayed.name));
+        }
+        assert_eq!(result.actions.len(), state.actions.len());
+    }
+
+    #[test]
+    fn replay_every_round() {
+        let result = battle();
+
+        for round in 0..=result.round_number {
+            let state = Replay::new(result.initial.clone()).state_at(&result.actions, round).unwrap();
+
+            assert_eq!(round, state.round_number);
+            assert!(state.actions.iter().all(|a| a.round() <= round));
+        }
+    }
+
+    #[test]
+    fn visit_every_round() {
+        let result = battle();
+        let mut visited: Vec<(u32, Vec<u16>)> = vec![];
+
+        let state = Replay::new(result.initial.clone())
+            .visit_rounds(&result.actions, result.round_number, |s| {
+                visited.push((s.round_number, s.combatants.iter().map(|c| c.hp).collect()))
+            })
+            .unwrap();
+
+        assert_eq!(result.round_number as usize + 1, visited.len());
+        for (round, hp) in visited {
+            let expected = Replay::new(result.initial.clone()).state_at(&result.actions, round).unwrap();
+            assert_eq!(expected.combatants.iter().map(|c| c.hp).collect::<Vec<u16>>(), hp);
+        }
+        assert_eq!(result.actions.len(), state.actions.len());
+    }
+
+    fn duel() -> BattleRoundState {
+        let mut state = BattleRoundState {
+            combatants: vec![
+                Combatant { name: "a".to_owned(), hp: 10, max_hp: 10, dmg: 3, ..Default::default() },
+                Combatant { name: "b".to_owned(), hp: 10, max_hp: 10, dmg: 3, ..Default::default() },
+            ],
+            map: crate::engine::map::Map::new(5, 5),
+            actions: vec![],
+            round_number: 0,
+        };
+        state.map.place("a".to_owned(), Point::new(0, 0)).unwrap();
+        state.map.place("b".to_owned(), Point::new(3, 0)).unwrap();
+
+        state
+    }
+
+    fn movement(start: Point, steps: Vec<Point>) -> MovementResult {
+        MovementResult {
+            start,
+            goal: *steps.last().unwrap_or(&start),
+            last_position: *steps.last().unwrap_or(&start),
+            steps,
+        }
+    }
+
+    fn attack(state: &BattleRoundState, damage: u16, remaining_hp: u16) -> BattleAction {
+        BattleAction::Attack(
+            1,
+            BattleAttackAction {
+                assailant: state.combatants[0].clone(),
+                victim: Combatant { hp: remaining_hp, ..state.combatants[1].clone() },
+                damage,
+            },
+        )
+    }
+
+    #[test]
+    fn replay_move_and_attack() {
+        let state = duel();
+        let actions = vec![
+            BattleAction::Move(1, "a".to_owned(), movement(Point::new(0, 0), vec![Point::new(1, 0), Point::new(2, 0)])),
+            attack(&state, 3, 7),
+        ];
+
+        let replayed = Replay::new(state).state_at(&actions, 1).unwrap();
+
+        assert_eq!(Some(Point::new(2, 0)), replayed.map.position_for("a"));
+        assert_eq!(7, replayed.combatants[1].hp);
+    }
+
+    #[test]
+    fn replay_rejects_skipped_locations() {
+        let actions = vec![BattleAction::Move(
+            1,
+            "a".to_owned(),
+            movement(Point::new(0, 0), vec![Point::new(2, 0)]),
+        )];
+
+        let replayed = Replay::new(duel()).state_at(&actions, 1);
+
+        assert_eq!(Err(ReplayError::InvalidMove(1, "a".to_owned())), replayed.map(|_| ()));
+    }
+
+    #[test]
+    fn replay_rejects_moves_from_elsewhere() {
+        let actions = vec![BattleAction::Move(
+            1,
+            "a".to_owned(),
+            movement(Point::new(1, 1), vec![Point::new(1, 2)]),
+        )];
+
+        let replayed = Replay::new(duel()).state_at(&actions, 1);
+
+        assert_eq!(Err(ReplayError::InvalidMove(1, "a".to_owned())), replayed.map(|_| ()));
+    }
+
+    #[test]
+    fn replay_rejects_attacks_out_of_reach() {
+        let state = duel();
+        let actions = vec![attack(&state, 3, 7)];
+
+        let replayed = Replay::new(state).state_at(&actions, 1);
+
+        assert_eq!(Err(ReplayError::OutOfReach(1, "a".to_owned())), replayed.map(|_| ()));
+    }
+
+    #[test]
+    fn replay_rejects_tampered_damage() {
+        let state = duel();
+        let actions = vec![
+            BattleAction::Move(1, "a".to_owned(), movement(Point::new(0, 0), vec![Point::new(1, 0), Point::new(2, 0)])),
+            attack(&state, 5, 5),
+        ];
+
+        let replayed = Replay::new(state).state_at(&actions, 1);
+
+        assert_eq!(Err(ReplayError::Mismatch(1, "b".to_owned())), replayed.map(|_| ()));
+    }
+
+    #[test]
+    fn replay_rejects_dead_combatants() {
+        let mut state = duel();
+        state.combatants[0].hp = 0;
+        let actions = vec![BattleAction::Move(
+            2,
+            "a".to_owned(),
+            movement(Point::new(0, 0), vec![Point::new(1, 0)]),
+        )];
+
+        let replayed = Replay::new(state).state_at(&actions, 2);
+
+        assert_eq!(Err(ReplayError::DeadCombatant(2, "a".to_owned())), replayed.map(|_| ()));
+    }
+
+    #[test]
+    fn replay_rejects_revive_outside_of_map() {
+        let mut state = duel();
+        state.combatants[0].hp = 0;
+        state.map.remove_poi("a");
+        let revived = Combatant { hp: 5, ..state.combatants[0].clone() };
+        let actions = vec![BattleAction::Revive(2, revived, Point::new(9, 9))];
+
+        let replayed = Replay::new(state).state_at(&actions, 2);
+
+        assert_eq!(Err(ReplayError::InvalidPlacement(2, "a".to_owned())), replayed.map(|_| ()));
+    }
+}
@@ -0,0 +1,72 @@
+/// The kinds of timed effects a combatant can suffer from or benefit of
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StatusEffectKind {
+    /// Deals damage at the start of each turn, applying it again refreshes the duration
+    Poison,
+    /// Deals damage at the start of each turn, every application stacks
+    Bleed,
+    /// The combatant skips its turns
+    Stun,
+    /// Reduces the number of steps the combatant can take
+    Slow,
+    /// Heals at the start of each turn
+    Regeneration,
+    /// Reduces the damage of every hit taken
+    Shield,
+}
+
+/// A timed effect on a combatant, ticked at the start of each of its turns
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    /// The number of turns the effect is still active
+    pub remaining_turns: u32,
+    /// The damage or healing per turn, the number of steps lost when slowed or the damage absorbed per hit, unused for stun
+    pub potency: u16,
+}
+
+/// The outcome of a single effect at the start of a turn
+#[derive(Clone, Debug)]
+pub struct StatusEffectTick {
+    pub kind: StatusEffectKind,
+    /// The damage dealt, the hit points healed or the steps lost
+    pub amount: u16,
+    pub remaining_hp: u16,
+    pub remaining_turns: u32,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusEffectKind, remaining_turns: u32, potency: u16) -> Self {
+        Self {
+            kind,
+            remaining_turns,
+            potency,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining_turns == 0
+    }
+
+    /// Determines if the effect is refreshed instead of added when applied again
+    pub fn stacks(&self) -> bool {
+        self.kind == StatusEffectKind::Bleed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_expired() {
+        assert!(StatusEffect::new(StatusEffectKind::Poison, 0, 2).is_expired());
+        assert!(!StatusEffect::new(StatusEffectKind::Poison, 1, 2).is_expired());
+    }
+
+    #[test]
+    fn only_bleed_stacks() {
+        assert!(StatusEffect::new(StatusEffectKind::Bleed, 1, 2).stacks());
+        assert!(!StatusEffect::new(StatusEffectKind::Poison, 1, 2).stacks());
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashSet;
+
+/// How the matches of a tournament are scheduled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TournamentFormat {
+    /// A single loss eliminates
+    SingleElimination,
+    /// Two losses eliminate, the first loss moves the participant to the losers bracket
+    DoubleElimination,
+    /// Every participant meets every other participant once
+    RoundRobin,
+    /// The given number of rounds, each pairing participants with the same number of wins
+    Swiss(u32),
+}
+
+/// A single match, the participants are given by their seed (their position in the registration)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TournamentMatch {
+    pub round: u32,
+    pub first: usize,
+    /// Not set if the first participant has a bye and advances without a battle
+    pub second: Option<usize>,
+    /// Not set if the battle ended without a winner
+    pub winner: Option<usize>,
+    /// Only used by double elimination, the match is played by participants with one loss
+    pub losers_bracket: bool,
+}
+
+impl TournamentMatch {
+    /// The participant that advances, the first participant after a draw
+    pub fn advancing(&self) -> usize {
+        self.winner.unwrap_or(self.first)
+    }
+
+    /// The participant that lost the match, if any
+    pub fn loser(&self) -> Option<usize> {
+        let second = self.second?;
+
+        match self.advancing() == self.first {
+            true => Some(second),
+            false => Some(self.first),
+        }
+    }
+
+    fn is_between(&self, a: usize, b: usize) -> bool {
+        (self.first == a && self.second == Some(b)) || (self.first == b && self.second == Some(a))
+    }
+}
+
+/// The results of a single participant, byes count as wins
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Standing {
+    pub participant: usize,
+    pub matches: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+pub struct Tournament {
+    format: TournamentFormat,
+    participants: usize,
+}
+
+impl Tournament {
+    pub fn new(format: TournamentFormat, participants: usize) -> Self {
+        Self {
+            format,
+            participants,
+        }
+    }
+
+    /// The default number of swiss rounds, enough to leave a single participant without a loss
+    pub fn swiss_rounds(participants: usize) -> u32 {
+        (participants.max(1) as f64).log2().ceil() as u32
+    }
+
+    /// Schedules and plays all matches
+    ///
+    /// The play function receives the seeds of both participants and returns the seed of the winner,
+    /// or none if the battle ended in a draw.
+    pub fn run(&self, mut play: impl FnMut(usize, usize) -> Option<usize>) -> Vec<TournamentMatch> {
+        match self.format {
+            TournamentFormat::SingleElimination => self.single_elimination(&mut play),
+            TournamentFormat::DoubleElimination => self.double_elimination(&mut play),
+            TournamentFormat::RoundRobin => self.round_robin(&mut play),
+            TournamentFormat::Swiss(rounds) => self.swiss(rounds, &mut play),
+        }
+    }
+
+    /// Sums up the matches per participant, ordered by wins, then losses, then seed
+    pub fn standings(participants: usize, matches: &[TournamentMatch]) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = (0..participants)
+            .map(|participant| Standing {
+                participant,
+                ..Default::default()
+            })
+            .collect();
+
+        for m in matches.iter() {
+            let players: Vec<usize> = std::iter::once(m.first).chain(m.second).collect();
+
+            for player in players.into_iter().filter(|p| *p < participants) {
+                let standing = &mut standings[player];
+                standing.matches += 1;
+
+                match (m.second, m.winner) {
+                    (None, _) => standing.wins += 1,
+                    (Some(_), None) => standing.draws += 1,
+                    (Some(_), Some(winner)) if winner == player => standing.wins += 1,
+                    (Some(_), Some(_)) => standing.losses += 1,
+                }
+            }
+        }
+
+        standings.sort_by(|a, b| {
+            b.wins
+                .cmp(&a.wins)
+                .then(a.losses.cmp(&b.losses))
+                .then(a.participant.cmp(&b.participant))
+        });
+
+        standings
+    }
+
+    fn single_elimination(&self, play: &mut impl FnMut(usize, usize) -> Option<usize>) -> Vec<TournamentMatch> {
+        let mut matches: Vec<TournamentMatch> = vec![];
+        let mut remaining: Vec<usize> = (0..self.participants).collect();
+        let mut round = 0;
+
+        while remaining.len() > 1 {
+            round += 1;
+            let played = Tournament::play_pairs(&remaining, round, false, play);
+            remaining = played.iter().map(|m| m.advancing()).collect();
+            matches.extend(played);
+        }
+
+        matches
+    }
+
+    fn double_elimination(&self, play: &mut impl FnMut(usize, usize) -> Option<usize>) -> Vec<TournamentMatch> {
+        let mut matches: Vec<TournamentMatch> = vec![];
+        let mut losses: Vec<u32> = vec![0; self.participants];
+        let mut round = 0;
+
+        loop {
+            let winners: Vec<usize> = (0..self.participants).filter(|p| losses[*p] == 0).collect();
+            let losers: Vec<usize> = (0..self.participants).filter(|p| losses[*p] == 1).collect();
+
+            if winners.len() + losers.len() < 2 {
+                break;
+            }
+            round += 1;
+
+            let played: Vec<TournamentMatch> = match (winners.len(), losers.len()) {
+                // The grand final, a win of the losers bracket leads to a rematch
+                (1, 1) => vec![Tournament::play_match(winners[0], Some(losers[0]), round, false, play)],
+                // A participant alone in its bracket waits for the other bracket
+                _ => {
+                    let mut played = vec![];
+                    if winners.len() > 1 {
+                        played.extend(Tournament::play_pairs(&winners, round, false, play));
+                    }
+                    if losers.len() > 1 {
+                        played.extend(Tournament::play_pairs(&losers, round, true, play));
+                    }
+                    played
+                }
+            };
+
+            for m in played.iter() {
+                if let Some(loser) = m.loser() {
+                    losses[loser] += 1;
+                }
+            }
+            matches.extend(played);
+        }
+
+        matches
+    }
+
+    /// Plays the rounds of the circle method, every participant rests once if their number is odd
+    fn round_robin(&self, play: &mut impl FnMut(usize, usize) -> Option<usize>) -> Vec<TournamentMatch> {
+        let mut matches: Vec<TournamentMatch> = vec![];
+        let mut circle: Vec<Option<usize>> = (0..self.participants).map(Some).collect();
+        if circle.len() % 2 == 1 {
+            circle.push(None);
+        }
+
+        for round in 1..circle.len() as u32 {
+            for i in 0..circle.len() / 2 {
+                if let (Some(first), Some(second)) = (circle[i], circle[circle.len() - 1 - i]) {
+                    matches.push(Tournament::play_match(first, Some(second), round, false, play));
+                }
+            }
+
+            // The first participant stays in place, all others move one position
+            let last = circle.pop().unwrap();
+            circle.insert(1, last);
+        }
+
+        matches
+    }
+
+    fn swiss(&self, rounds: u32, play: &mut impl FnMut(usize, usize) -> Option<usize>) -> Vec<TournamentMatch> {
+        let mut matches: Vec<TournamentMatch> = vec![];
+        let mut had_bye: HashSet<usize> = HashSet::new();
+
+        for round in 1..=rounds {
+            let mut unpaired: Vec<usize> = Tournament::standings(self.participants, &matches)
+                .iter()
+                .map(|s| s.participant)
+                .collect();
+
+            // The lowest ranked participant without a bye sits this round out
+            if unpaired.len() % 2 == 1 {
+                let index = unpaired
+                    .iter()
+                    .rposition(|p| !had_bye.contains(p))
+                    .unwrap_or(unpaired.len() - 1);
+                let bye = unpaired.remove(index);
+                had_bye.insert(bye);
+                matches.push(Tournament::play_match(bye, None, round, false, play));
+            }
+
+            while unpaired.len() > 1 {
+                let first = unpaired.remove(0);
+                // The next ranked participant not met before, the next ranked one if all were met
+                let index = unpaired
+                    .iter()
+                    .position(|p| !matches.iter().any(|m| m.is_between(first, *p)))
+                    .unwrap_or(0);
+                let second = unpaired.remove(index);
+
+                matches.push(Tournament::play_match(first, Some(second), round, false, play));
+            }
+        }
+
+        matches
+    }
+
+    /// Pairs the participants in order, the last one has a bye if their number is odd
+    fn play_pairs(
+        participants: &[usize],
+        round: u32,
+        losers_bracket: bool,
+        play: &mut impl FnMut(usize, usize) -> Option<usize>,
+    ) -> Vec<TournamentMatch> {
+        participants
+            .chunks(2)
+            .map(|pair| Tournament::play_match(pair[0], pair.get(1).copied(), round, losers_bracket, play))
+            .collect()
+    }
+
+    fn play_match(
+        first: usize,
+        second: Option<usize>,
+        round: u32,
+        losers_bracket: bool,
+        play: &mut impl FnMut(usize, usize) -> Option<usize>,
+    ) -> TournamentMatch {
+        let winner = match second {
+            Some(s) => play(first, s),
+            None => Some(first),
+        };
+
+        TournamentMatch {
+            round,
+            first,
+            second,
+            winner,
+            losers_bracket,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The participant with the lower seed always wins
+    fn lower_seed_wins(first: usize, second: usize) -> Option<usize> {
+        Some(first.min(second))
+    }
+
+    #[test]
+    fn single_elimination_with_bye() {
+        let matches = Tournament::new(TournamentFormat::SingleElimination, 5).run(lower_seed_wins);
+
+        assert_eq!(3, matches.iter().filter(|m| m.round == 1).count());
+        assert_eq!(Some(0), matches.last().unwrap().winner);
+        assert_eq!(3, matches.last().unwrap().round);
+    }
+
+    #[test]
+    fn double_elimination_needs_two_losses() {
+        let matches = Tournament::new(TournamentFormat::DoubleElimination, 4).run(lower_seed_wins);
+        let standings = Tournament::standings(4, &matches);
+
+        assert_eq!(0, standings[0].participant);
+        assert_eq!(0, standings[0].losses);
+        assert!(standings[1..].iter().all(|s| s.losses == 2));
+        assert!(matches.iter().any(|m| m.losers_bracket));
+    }
+
+    #[test]
+    fn double_elimination_grand_final_rematch() {
+        // The participant with the highest seed wins every match but the first
+        let mut played = 0;
+        let matches = Tournament::new(TournamentFormat::DoubleElimination, 2).run(|first, second| {
+            played += 1;
+            match played {
+                1 => Some(first.min(second)),
+                _ => Some(first.max(second)),
+            }
+        });
+
+        assert_eq!(3, matches.len());
+        assert_eq!(Some(1), matches.last().unwrap().winner);
+    }
+
+    #[test]
+    fn round_robin_pairs_everyone_once() {
+        let matches = Tournament::new(TournamentFormat::RoundRobin, 5).run(lower_seed_wins);
+
+        assert_eq!(10, matches.len());
+        for a in 0..5 {
+            for b in (a + 1)..5 {
+                assert_eq!(1, matches.iter().filter(|m| m.is_between(a, b)).count());
+            }
+        }
+        assert_eq!(5, matches.iter().map(|m| m.round).max().unwrap());
+    }
+
+    #[test]
+    fn swiss_avoids_rematches() {
+        let matches = Tournament::new(TournamentFormat::Swiss(3), 4).run(lower_seed_wins);
+
+        assert_eq!(6, matches.len());
+        for a in 0..4 {
+            for b in (a + 1)..4 {
+                assert!(matches.iter().filter(|m| m.is_between(a, b)).count() <= 1);
+            }
+        }
+        assert_eq!(2, Tournament::swiss_rounds(4));
+        assert_eq!(3, Tournament::swiss_rounds(5));
+    }
+
+    #[test]
+    fn standings_count_draws_and_byes() {
+        let matches = vec![
+            TournamentMatch { round: 1, first: 0, second: Some(1), winner: None, losers_bracket: false },
+            TournamentMatch { round: 1, first: 2, second: None, winner: Some(2), losers_bracket: false },
+        ];
+
+        let standings = Tournament::standings(3, &matches);
+
+        assert_eq!(2, standings[0].participant);
+        assert_eq!(1, standings[0].wins);
+        assert_eq!(1, standings[1].draws);
+    }
+}
@@ -0,0 +1,4 @@
+//! The battle engine, usable without the REST API and its storage
+pub mod engine;
+pub mod render;
+pub mod types;
@@ -1,6 +1,10 @@
-use std::net::Ipv4Addr;
+use std::{env, process};
 
-use rocket::Config;
+use api::{auth::AuthMiddleware, versioning::ApiVersioning};
+use clap::Parser;
+use cli::{Cli, Command};
+use config::{AppConfig, LoggingConfig};
+use rocket::{Build, Rocket};
 use storage::middleware::DbMiddleware;
 
 #[macro_use]
@@ -12,46 +16,164 @@ extern crate log;
 use env_logger::{Builder, Target};
 
 mod api;
-mod engine;
+mod cli;
+mod config;
 mod storage;
-mod types;
 
-#[launch]
-fn rocket() -> _ {
+use ribs::{engine, render, types};
+
+#[rocket::main]
+async fn main() -> Result<(), rocket::Error> {
+    match Cli::parse().command {
+        None | Some(Command::Serve) => {
+            rocket().launch().await?;
+        }
+        Some(command) => {
+            if let Err(message) = cli::run(command) {
+                eprintln!("{}", message);
+                process::exit(1);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn rocket() -> Rocket<Build> {
+    let figment = config::figment();
+    let app_config = match AppConfig::load(&figment) {
+        Ok(c) => c,
+        Err(problems) => panic!("Invalid configuration:\n{}", problems.join("\n")),
+    };
+
+    init_logging(&app_config.logging);
+    for warning in config::deprecated_env() {
+        warn!("{}", warning);
+    }
+
+    info!("starting up with profile {}", figment.profile());
+
+    mount_api(rocket::custom(figment))
+        .manage(app_config)
+        .attach(DbMiddleware)
+        .attach(AuthMiddleware)
+}
+
+/// Initializes the logger, the RUST_LOG environment variable takes precedence over the configured level
+fn init_logging(logging_config: &LoggingConfig) {
     let mut builder = Builder::from_default_env();
-    builder.target(Target::Stdout);
+
+    if env::var("RUST_LOG").is_err() {
+        builder.parse_filters(&logging_config.level);
+    }
+
+    match logging_config.target.to_lowercase().as_str() {
+        "stderr" => builder.target(Target::Stderr),
+        _ => builder.target(Target::Stdout),
+    };
 
     builder.init();
+}
 
-    info!("starting up");
+/// Mounts all API routes, independent of the storage used
+fn mount_api(rocket: Rocket<Build>) -> Rocket<Build> {
+    let rocket = rocket
+        .mount(
+            "/",
+            routes![api::index, api::openapi::specification, api::openapi::docs],
+        )
+        .mount("/health", routes![api::health::live, api::health::ready])
+        .attach(ApiVersioning);
 
-    rocket::build()
-        .mount("/", routes![api::index])
+    let rocket = mount_resources(rocket, "v1").mount(
+        "/v1/battle",
+        routes![
+            api::battle::start_new_battle,
+            api::replay::get_state,
+            api::battle_log::get_log,
+            api::battle_log::import_log,
+            api::render::render_text,
+            api::render::render_svg,
+            api::render::render_gif
+        ],
+    );
+
+    mount_resources(rocket, "v2").mount(
+        "/v2/battle",
+        routes![
+            api::v2::battle::start_new_battle,
+            api::replay::get_state,
+            api::battle_log::get_log,
+            api::battle_log::import_log,
+            api::render::render_text,
+            api::render::render_svg,
+            api::render::render_gif
+        ],
+    )
+}
+
+/// Mounts the resources that did not change between the API versions
+fn mount_resources(rocket: Rocket<Build>, version: &str) -> Rocket<Build> {
+    rocket
         .mount(
-            "/battlefields",
+            format!("/{}/battlefields", version),
             routes![
                 api::battlefield::get_all,
                 api::battlefield::create_new,
                 api::battlefield::update,
                 api::battlefield::delete,
-                api::battlefield::get_by_id
+                api::battlefield::get_by_id,
+                api::battlefield::export,
+                api::battlefield::import
             ],
         )
         .mount(
-            "/combatants",
+            format!("/{}/combatants", version),
             routes![
                 api::combatant::get_all,
                 api::combatant::create_new,
                 api::combatant::update,
                 api::combatant::delete,
-                api::combatant::get_by_id
+                api::combatant::get_by_id,
+                api::combatant::equip,
+                api::combatant::unequip,
+                api::combatant::export,
+                api::combatant::import
+            ],
+        )
+        .mount(
+            format!("/{}/items", version),
+            routes![
+                api::item::get_all,
+                api::item::create_new,
+                api::item::update,
+                api::item::delete,
+                api::item::get_by_id
+            ],
+        )
+        .mount(
+            format!("/{}/tournaments", version),
+            routes![
+                api::tournament::get_all,
+                api::tournament::create_new,
+                api::tournament::delete,
+                api::tournament::get_by_id,
+                api::tournament::register,
+                api::tournament::run,
+                api::tournament::standings
+            ],
+        )
+        .mount(
+            format!("/{}/leaderboard", version),
+            routes![api::leaderboard::get_leaderboard],
+        )
+        .mount(
+            format!("/{}/users", version),
+            routes![
+                api::user::get_all,
+                api::user::me,
+                api::user::create_new,
+                api::user::delete
             ],
         )
-        .mount("/battle", routes![api::battle::start_new_battle])
-        .attach(DbMiddleware)
-        .configure(Config {
-            port: 7777,
-            address: Ipv4Addr::new(0, 0, 0, 0).into(),
-            ..Config::debug_default()
-        })
 }
@@ -0,0 +1,111 @@
+//! Draws battles for people, independent of how the battle was stored
+use std::collections::HashMap;
+
+use crate::{
+    engine::{combatant::Combatant, map::Map},
+    types::point::{Coordinate, Point},
+};
+
+pub mod gif;
+pub mod svg;
+pub mod text;
+
+// Larger maps only show this many locations around the combatants
+const MAX_FRAME_SIZE: Coordinate = 64;
+
+// Labels of the combatants without a single character avatar, by their position
+const LABELS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+// The label of every combatant beyond the ones above
+const OVERFLOW_LABEL: &str = "*";
+
+// Colors of the teams in images, one per team
+const TEAM_COLORS: [[u8; 3]; 6] = [
+    [0xe6, 0x19, 0x4b],
+    [0x3c, 0xb4, 0x4b],
+    [0xff, 0xe1, 0x19],
+    [0x43, 0x63, 0xd8],
+    [0xf5, 0x82, 0x31],
+    [0x91, 0x1e, 0xb4],
+];
+
+// Colors of the hit point bars in images, by health
+const HEALTH_COLORS: [[u8; 3]; 3] = [[0xd0, 0x30, 0x30], [0xe0, 0xc0, 0x30], [0x40, 0xc0, 0x40]];
+
+/// The label of the combatant at the given position, its avatar if that is a single character
+fn label(index: usize, combatant: &Combatant) -> String {
+    if let Some(avatar) = &combatant.avatar {
+        let mut chars = avatar.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if !c.is_whitespace() {
+                return c.to_string();
+            }
+        }
+    }
+
+    LABELS
+        .chars()
+        .nth(index)
+        .map(|c| c.to_string())
+        .unwrap_or_else(|| String::from(OVERFLOW_LABEL))
+}
+
+/// The team of every combatant, numbered in order of appearance, combatants without a team are a team of their own
+fn teams(combatants: &[Combatant]) -> Vec<usize> {
+    let mut teams: Vec<&str> = vec![];
+
+    combatants
+        .iter()
+        .map(|c| {
+            let team = c.team.as_deref().unwrap_or(&c.name);
+            match teams.iter().position(|t| *t == team) {
+                Some(i) => i,
+                None => {
+                    teams.push(team);
+                    teams.len() - 1
+                }
+            }
+        })
+        .collect()
+}
+
+/// How healthy the combatant is, 0 below a quarter of its maximal hit points, 1 below half and 2 above
+fn health(hp: u16, max_hp: u16) -> usize {
+    match hp as u32 * 4 / max_hp.max(hp).max(1) as u32 {
+        0 => 0,
+        1 => 1,
+        _ => 2,
+    }
+}
+
+/// The part of a hit point bar of the given length that is filled, any hit point left shows
+fn filled(hp: u16, max_hp: u16, length: u32) -> u32 {
+    (hp as u32 * length).div_ceil(max_hp.max(hp).max(1) as u32)
+}
+
+/// The first and last location shown, the whole map unless it is too large
+fn window(map: &Map) -> Option<(Point, Point)> {
+    if map.get_width() == 0 || map.get_height() == 0 {
+        return None;
+    }
+
+    let pois: HashMap<Point, String> = map.get_pois().into_iter().collect();
+    let (from_x, to_x) = axis_window(map.get_width(), pois.keys().map(|p| p.x).min());
+    let (from_y, to_y) = axis_window(map.get_height(), pois.keys().map(|p| p.y).min());
+
+    Some((Point::new(from_x, from_y), Point::new(to_x, to_y)))
+}
+
+/// Starts just before the combatant closest to the origin if the axis is too long to show
+fn axis_window(size: Coordinate, first_occupied: Option<Coordinate>) -> (Coordinate, Coordinate) {
+    if size <= MAX_FRAME_SIZE {
+        return (0, size - 1);
+    }
+
+    let from = first_occupied
+        .unwrap_or(0)
+        .saturating_sub(1)
+        .min(size - MAX_FRAME_SIZE);
+
+    (from, from + MAX_FRAME_SIZE - 1)
+}
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use gif::{Encoder, Frame, Repeat};
+
+use crate::{
+    engine::{combatant::Combatant, map::Map},
+    types::{point::Point, topology::Topology},
+};
+
+use super::{filled, health, teams, window, HEALTH_COLORS, TEAM_COLORS};
+
+// The size of a location in pixels
+const CELL: u16 = 12;
+
+// The height of the hit point bar at the bottom of every combatant
+const BAR: u16 = 2;
+
+// Indices of the palette, the team colors follow the health colors
+const BACKGROUND: u8 = 0;
+const EMPTY: u8 = 1;
+const HEALTH: u8 = 2;
+const TEAMS: u8 = HEALTH + HEALTH_COLORS.len() as u8;
+
+/// Draws one frame per round into an animated image that repeats forever
+///
+/// Every frame shows the part of the map shown for the first one. Combatants are drawn as squares
+/// in the color of their team, with their hit point bar below, avatars are left to the SVG frames.
+pub struct GifAnimation {
+    encoder: Encoder<Vec<u8>>,
+    from: Point,
+    to: Point,
+    topology: Topology,
+    width: u16,
+    height: u16,
+    delay: u16,
+}
+
+impl GifAnimation {
+    /// Starts the animation with the part of the map shown for the given map, usually the one before the first round
+    pub fn new(map: &Map, delay: Duration) -> Result<Self, String> {
+        let (from, to) = window(map).ok_or_else(|| String::from("the map has no locations"))?;
+        let (columns, rows) = (to.x - from.x + 1, to.y - from.y + 1);
+        let topology = map.get_topology();
+        // Every hex row is shifted half a location against the previous one
+        let shift = match topology {
+            Topology::Hex => (rows - 1) * CELL / 2,
+            _ => 0,
+        };
+        let (width, height) = (columns * CELL + shift, rows * CELL);
+
+        let mut encoder = Encoder::new(vec![], width, height, &palette()).map_err(|e| e.to_string())?;
+        encoder.set_repeat(Repeat::Infinite).map_err(|e| e.to_string())?;
+
+        Ok(GifAnimation {
+            encoder,
+            from,
+            to,
+            topology,
+            width,
+            height,
+            // GIF counts in hundredths of a second
+            delay: (delay.as_millis() / 10).min(u16::MAX as u128) as u16,
+        })
+    }
+
+    pub fn add_frame(&mut self, map: &Map, combatants: &[Combatant]) -> Result<(), String> {
+        let mut pixels: Vec<u8> = vec![BACKGROUND; self.width as usize * self.height as usize];
+
+        for y in self.from.y..=self.to.y {
+            for x in self.from.x..=self.to.x {
+                let (left, top) = self.cell_at(Point::new(x, y));
+                self.fill(&mut pixels, left + 1, top + 1, CELL - 2, CELL - 2, EMPTY);
+            }
+        }
+
+        let teams = teams(combatants);
+        for (i, combatant) in combatants.iter().enumerate() {
+            let Some(location) = map.position_for(&combatant.name) else {
+                continue;
+            };
+            if location.x < self.from.x || location.x > self.to.x || location.y < self.from.y || location.y > self.to.y
+            {
+                continue;
+            }
+
+            let (left, top) = self.cell_at(location);
+            let team = TEAMS + (teams[i] % TEAM_COLORS.len()) as u8;
+            let health = HEALTH + health(combatant.hp, combatant.max_hp) as u8;
+            let bar = filled(combatant.hp, combatant.max_hp, (CELL - 2) as u32) as u16;
+
+            self.fill(&mut pixels, left + 2, top + 1, CELL - 4, CELL - 3 - BAR, team);
+            self.fill(&mut pixels, left + 1, top + CELL - 1 - BAR, CELL - 2, BAR, BACKGROUND);
+            self.fill(&mut pixels, left + 1, top + CELL - 1 - BAR, bar, BAR, health);
+        }
+
+        let mut frame = Frame::from_indexed_pixels(self.width, self.height, &pixels, None);
+        frame.delay = self.delay;
+
+        self.encoder.write_frame(&frame).map_err(|e| e.to_string())
+    }
+
+    /// The encoded animation
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        self.encoder.into_inner().map_err(|e| e.to_string())
+    }
+
+    /// The top left pixel of the location
+    fn cell_at(&self, location: Point) -> (u16, u16) {
+        let (column, row) = (location.x - self.from.x, location.y - self.from.y);
+        let shift = match self.topology {
+            Topology::Hex => row * CELL / 2,
+            _ => 0,
+        };
+
+        (column * CELL + shift, row * CELL)
+    }
+
+    fn fill(&self, pixels: &mut [u8], left: u16, top: u16, width: u16, height: u16, color: u8) {
+        for y in top..top + height {
+            let row = y as usize * self.width as usize;
+            pixels[row + left as usize..row + (left + width) as usize].fill(color);
+        }
+    }
+}
+
+fn palette() -> Vec<u8> {
+    let mut palette: Vec<u8> = vec![0x1e, 0x1e, 0x1e, 0x2d, 0x2d, 0x2d];
+    palette.extend(HEALTH_COLORS.iter().flatten());
+    palette.extend(TEAM_COLORS.iter().flatten());
+
+    palette
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gif::DecodeOptions;
+
+    fn combatant(name: &str, hp: u16) -> Combatant {
+        Combatant {
+            name: String::from(name),
+            hp,
+            max_hp: 10,
+            ..Default::default()
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Vec<(u16, Vec<u8>)> {
+        let mut options = DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(bytes).unwrap();
+
+        let mut frames = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.delay, frame.buffer.to_vec()));
+        }
+
+        frames
+    }
+
+    #[test]
+    fn one_frame_per_round() {
+        let mut map = Map::new(4, 3);
+        map.place(String::from("first"), Point::new(0, 0)).unwrap();
+        map.place(String::from("second"), Point::new(3, 2)).unwrap();
+        let combatants = [combatant("first", 10), combatant("second", 10)];
+
+        let mut animation = GifAnimation::new(&map, Duration::from_millis(500)).unwrap();
+        animation.add_frame(&map, &combatants).unwrap();
+        map.move_to(Point::new(0, 0), Point::new(1, 0)).unwrap();
+        animation.add_frame(&map, &combatants).unwrap();
+        let frames = decode(&animation.finish().unwrap());
+
+        assert_eq!(2, frames.len());
+        assert!(frames.iter().all(|(delay, _)| *delay == 50));
+        let width = 4 * CELL as usize;
+        let pixel = |frame: usize, x: u16, y: u16| frames[frame].1[y as usize * width + x as usize];
+        // The center of the first location and of the one next to it
+        assert_eq!(TEAMS, pixel(0, 6, 5));
+        assert_eq!(EMPTY, pixel(0, 18, 5));
+        assert_eq!(EMPTY, pixel(1, 6, 5));
+        assert_eq!(TEAMS, pixel(1, 18, 5));
+    }
+
+    #[test]
+    fn hit_points_below_the_combatants() {
+        let mut map = Map::new(2, 1);
+        map.place(String::from("first"), Point::new(0, 0)).unwrap();
+        map.place(String::from("second"), Point::new(1, 0)).unwrap();
+
+        let mut animation = GifAnimation::new(&map, Duration::from_millis(100)).unwrap();
+        animation.add_frame(&map, &[combatant("first", 10), combatant("second", 2)]).unwrap();
+        let frames = decode(&animation.finish().unwrap());
+
+        let width = 2 * CELL as usize;
+        let bar = (CELL - 2) as usize * width;
+        assert_eq!(HEALTH + 2, frames[0].1[bar + CELL as usize - 2]);
+        assert_eq!(HEALTH, frames[0].1[bar + CELL as usize + 1]);
+        assert_eq!(BACKGROUND, frames[0].1[bar + 2 * CELL as usize - 2]);
+        assert_eq!(TEAMS + 1, frames[0].1[5 * width + CELL as usize + 6]);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use crate::{
+    engine::{combatant::Combatant, map::Map},
+    types::{point::Point, topology::Topology},
+};
+
+use super::{filled, health, label, teams, window, HEALTH_COLORS, TEAM_COLORS};
+
+// The size of a location
+const CELL: u32 = 32;
+
+const MARGIN: u32 = 8;
+
+// The height of the title and of every line of the legend
+const LINE: u32 = 20;
+
+// The legend is never narrower than this
+const MIN_WIDTH: u32 = 320;
+
+const BACKGROUND: &str = "#1e1e1e";
+const EMPTY: &str = "#2d2d2d";
+const FOREGROUND: &str = "#eeeeee";
+
+/// Draws the map and the hit points of the combatants as a scalable image
+///
+/// Combatants whose avatar is more than a single character are drawn with the avatar as image,
+/// all others as a circle in the color of their team with their label, as in the text frames.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SvgRenderer;
+
+impl SvgRenderer {
+    /// The map at the end of the round, followed by the hit points of every combatant
+    pub fn frame(&self, round: u32, map: &Map, combatants: &[Combatant]) -> String {
+        let teams = teams(combatants);
+        let by_name: HashMap<&str, usize> = combatants
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.name.as_str(), i))
+            .collect();
+
+        let shown = window(map);
+        let (from, to) = shown.unwrap_or((Point::new(0, 0), Point::new(0, 0)));
+        let (columns, rows) = match shown {
+            Some(_) => ((to.x - from.x) as u32 + 1, (to.y - from.y) as u32 + 1),
+            None => (0, 0),
+        };
+        let hex = map.get_topology() == Topology::Hex;
+        // Every hex row is shifted half a location against the previous one
+        let grid_width = columns * CELL + if hex { rows.saturating_sub(1) * CELL / 2 } else { 0 };
+        let grid_top = MARGIN + LINE;
+        let legend_top = grid_top + rows * CELL + MARGIN;
+
+        let width = grid_width.max(MIN_WIDTH) + 2 * MARGIN;
+        let height = legend_top + combatants.len() as u32 * LINE + MARGIN;
+
+        let mut svg: Vec<String> = vec![
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="monospace" font-size="14">"#,
+                w = width,
+                h = height
+            ),
+            format!(r#"<rect width="100%" height="100%" fill="{}"/>"#, BACKGROUND),
+            format!(
+                r#"<text x="{}" y="{}" fill="{}">Round {}</text>"#,
+                MARGIN,
+                MARGIN + 14,
+                FOREGROUND,
+                round
+            ),
+        ];
+
+        let cell_at = |location: Point| -> (u32, u32) {
+            let (column, row) = ((location.x - from.x) as u32, (location.y - from.y) as u32);
+            let shift = if hex { row * CELL / 2 } else { 0 };
+
+            (MARGIN + column * CELL + shift, grid_top + row * CELL)
+        };
+
+        if columns > 0 {
+            for y in from.y..=to.y {
+                for x in from.x..=to.x {
+                    let (left, top) = cell_at(Point::new(x, y));
+                    svg.push(match hex {
+                        true => format!(
+                            r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+                            left + CELL / 2,
+                            top + CELL / 2,
+                            CELL / 2 - 1,
+                            EMPTY
+                        ),
+                        false => format!(
+                            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                            left + 1,
+                            top + 1,
+                            CELL - 2,
+                            CELL - 2,
+                            EMPTY
+                        ),
+                    });
+                }
+            }
+        }
+
+        for (location, name) in map.get_pois() {
+            let Some(&index) = by_name.get(name.as_str()) else {
+                continue;
+            };
+            if location.x < from.x || location.x > to.x || location.y < from.y || location.y > to.y {
+                continue;
+            }
+
+            let (left, top) = cell_at(location);
+            svg.push(token(index, &combatants[index], teams[index], left, top));
+        }
+
+        for (i, combatant) in combatants.iter().enumerate() {
+            let top = legend_top + i as u32 * LINE;
+            let status = match combatant.is_alive() {
+                true => format!("{}/{}", combatant.hp, combatant.max_hp),
+                false => String::from("dead"),
+            };
+
+            svg.push(format!(
+                r#"<text x="{}" y="{}" fill="{}">{}</text>"#,
+                MARGIN,
+                top + 14,
+                rgb(TEAM_COLORS[teams[i] % TEAM_COLORS.len()]),
+                escape(&label(i, combatant))
+            ));
+            svg.push(format!(
+                r#"<text x="{}" y="{}" fill="{}">{} {}</text>"#,
+                MARGIN + 16,
+                top + 14,
+                FOREGROUND,
+                escape(&combatant.name),
+                status
+            ));
+        }
+
+        svg.push(String::from("</svg>"));
+        svg.join("\n") + "\n"
+    }
+}
+
+/// The combatant on the location with the given top left corner, with its hit point bar
+fn token(index: usize, combatant: &Combatant, team: usize, left: u32, top: u32) -> String {
+    let color = rgb(TEAM_COLORS[team % TEAM_COLORS.len()]);
+    let (cx, cy, r) = (left + CELL / 2, top + CELL / 2 - 2, CELL / 2 - 4);
+
+    let body = match &combatant.avatar {
+        Some(avatar) if avatar.chars().count() > 1 => format!(
+            r#"<clipPath id="avatar-{i}"><circle cx="{cx}" cy="{cy}" r="{r}"/></clipPath><image href="{href}" x="{x}" y="{y}" width="{d}" height="{d}" clip-path="url(#avatar-{i})"/><circle cx="{cx}" cy="{cy}" r="{r}" fill="none" stroke="{color}" stroke-width="2"/>"#,
+            i = index,
+            cx = cx,
+            cy = cy,
+            r = r,
+            href = escape(avatar),
+            x = cx - r,
+            y = cy - r,
+            d = 2 * r,
+            color = color
+        ),
+        _ => format!(
+            r#"<circle cx="{cx}" cy="{cy}" r="{r}" fill="{color}"/><text x="{cx}" y="{ty}" fill="{bg}" text-anchor="middle" font-weight="bold">{label}</text>"#,
+            cx = cx,
+            cy = cy,
+            r = r,
+            color = color,
+            ty = cy + 5,
+            bg = BACKGROUND,
+            label = escape(&label(index, combatant))
+        ),
+    };
+
+    let bar = CELL - 6;
+    format!(
+        r#"<g><title>{name} {hp}/{max_hp}</title>{body}<rect x="{x}" y="{y}" width="{bar}" height="3" fill="{bg}"/><rect x="{x}" y="{y}" width="{filled}" height="3" fill="{health}"/></g>"#,
+        name = escape(&combatant.name),
+        hp = combatant.hp,
+        max_hp = combatant.max_hp,
+        body = body,
+        x = left + 3,
+        y = top + CELL - 5,
+        bar = bar,
+        bg = BACKGROUND,
+        filled = filled(combatant.hp, combatant.max_hp, bar),
+        health = rgb(HEALTH_COLORS[health(combatant.hp, combatant.max_hp)])
+    )
+}
+
+fn rgb(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combatant(name: &str, hp: u16) -> Combatant {
+        Combatant {
+            name: String::from(name),
+            hp,
+            max_hp: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn frame_has_every_location_and_combatant() {
+        let mut map = Map::new(4, 3);
+        map.place(String::from("first"), Point::new(0, 0)).unwrap();
+        map.place(String::from("second"), Point::new(2, 1)).unwrap();
+
+        let svg = SvgRenderer.frame(2, &map, &[combatant("first", 10), combatant("second", 4)]);
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains(">Round 2</text>"));
+        assert_eq!(12, svg.matches(&format!(r#"fill="{}""#, EMPTY)).count());
+        assert!(svg.contains("<title>first 10/10</title>"));
+        assert!(svg.contains("<title>second 4/10</title>"));
+        // The second combatant is at the third column of the second row
+        assert!(svg.contains(r##"<circle cx="88" cy="74" r="12" fill="#3cb44b"/>"##));
+    }
+
+    #[test]
+    fn avatars_are_images() {
+        let mut map = Map::new(2, 1);
+        map.place(String::from("first"), Point::new(0, 0)).unwrap();
+        let mut first = combatant("first", 10);
+        first.avatar = Some(String::from("https://example.com/a.png?size=1&round=2"));
+
+        let svg = SvgRenderer.frame(0, &map, &[first]);
+
+        assert!(svg.contains(r#"<image href="https://example.com/a.png?size=1&amp;round=2""#));
+    }
+
+    #[test]
+    fn names_are_escaped() {
+        let map = Map::new(1, 1);
+
+        let svg = SvgRenderer.frame(0, &map, &[combatant("<script>", 0)]);
+
+        assert!(svg.contains("&lt;script&gt; dead"));
+        assert!(!svg.contains("<script>"));
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use crate::{
+    engine::{combatant::Combatant, map::Map},
+    types::{point::Point, topology::Topology},
+};
+
+use super::{filled, health, label, teams, window};
+
+// The number of characters of the hit point bars
+const HP_BAR_WIDTH: u32 = 10;
+
+const EMPTY: &str = ".";
+
+// ANSI foreground colors, one per team
+const TEAM_COLORS: [u8; 6] = [31, 32, 33, 34, 35, 36];
+
+// ANSI foreground colors of the hit point bars, by health
+const HEALTH_COLORS: [u8; 3] = [31, 33, 32];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextStyle {
+    #[default]
+    Plain,
+    /// Colors the combatants by team and the hit point bars by how much is left
+    Ansi,
+}
+
+/// Draws the map and the hit points of the combatants as lines of text
+///
+/// Combatants are labeled by their position in the given list, or by their avatar if it is a single character,
+/// so frames of the same battle keep the labels as long as the combatants are passed in the same order.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextRenderer {
+    style: TextStyle,
+}
+
+impl TextRenderer {
+    pub fn new(style: TextStyle) -> Self {
+        TextRenderer { style }
+    }
+
+    /// The map at the end of the round, followed by one line per combatant
+    pub fn frame(&self, round: u32, map: &Map, combatants: &[Combatant]) -> String {
+        let labels: Vec<String> = combatants.iter().enumerate().map(|(i, c)| label(i, c)).collect();
+        let colors: Vec<u8> = teams(combatants)
+            .iter()
+            .map(|t| TEAM_COLORS[t % TEAM_COLORS.len()])
+            .collect();
+        let by_name: HashMap<&str, usize> = combatants
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.name.as_str(), i))
+            .collect();
+        let pois: HashMap<Point, String> = map.get_pois().into_iter().collect();
+
+        let mut lines: Vec<String> = vec![format!("Round {}", round)];
+
+        if let Some((from, to)) = window(map) {
+            if from != Point::new(0, 0) || to != Point::new(map.get_width() - 1, map.get_height() - 1) {
+                lines.push(format!(
+                    "x {}..{}, y {}..{} of {}x{}",
+                    from.x,
+                    to.x,
+                    from.y,
+                    to.y,
+                    map.get_width(),
+                    map.get_height()
+                ));
+            }
+
+            for y in from.y..=to.y {
+                let cells: Vec<String> = (from.x..=to.x)
+                    .map(|x| match pois.get(&Point::new(x, y)).and_then(|n| by_name.get(n.as_str())) {
+                        Some(i) => self.paint(&labels[*i], colors[*i]),
+                        None => String::from(EMPTY),
+                    })
+                    .collect();
+                // Every hex row is shifted half a location against the previous one
+                let indent = match map.get_topology() {
+                    Topology::Hex => " ".repeat((y - from.y) as usize),
+                    _ => String::new(),
+                };
+
+                lines.push(indent + &cells.join(" "));
+            }
+        }
+
+        lines.push(String::new());
+
+        let name_width = combatants.iter().map(|c| c.name.chars().count()).max().unwrap_or(0);
+        for (i, combatant) in combatants.iter().enumerate() {
+            let status = match combatant.is_alive() {
+                true => format!(
+                    "{} {:>3}/{}",
+                    self.hp_bar(combatant.hp, combatant.max_hp),
+                    combatant.hp,
+                    combatant.max_hp
+                ),
+                false => String::from("dead"),
+            };
+            let team = match &combatant.team {
+                Some(team) => format!("  {}", team),
+                None => String::new(),
+            };
+
+            lines.push(format!(
+                "{} {:<width$}  {}{}",
+                self.paint(&labels[i], colors[i]),
+                combatant.name,
+                status,
+                team,
+                width = name_width
+            ));
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    fn hp_bar(&self, hp: u16, max_hp: u16) -> String {
+        let filled = filled(hp, max_hp, HP_BAR_WIDTH);
+        let bar = "#".repeat(filled as usize) + &".".repeat((HP_BAR_WIDTH - filled) as usize);
+
+        self.paint(&bar, HEALTH_COLORS[health(hp, max_hp)])
+    }
+
+    fn paint(&self, text: &str, color: u8) -> String {
+        match self.style {
+            TextStyle::Plain => String::from(text),
+            TextStyle::Ansi => format!("\x1b[1;{}m{}\x1b[0m", color, text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::MAX_FRAME_SIZE;
+
+    fn combatant(name: &str, hp: u16) -> Combatant {
+        Combatant {
+            name: String::from(name),
+            hp,
+            max_hp: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn frame_of_square_map() {
+        let mut map = Map::new(4, 3);
+        map.place(String::from("first"), Point::new(0, 0)).unwrap();
+        map.place(String::from("second"), Point::new(2, 1)).unwrap();
+
+        let frame = TextRenderer::default().frame(2, &map, &[combatant("first", 10), combatant("second", 4)]);
+
+        assert_eq!(
+            "Round 2\n\
+             A . . .\n\
+             . . B .\n\
+             . . . .\n\
+             \n\
+             A first   ##########  10/10\n\
+             B second  ####......   4/10\n",
+            frame
+        );
+    }
+
+    #[test]
+    fn hex_rows_are_shifted() {
+        let mut map = Map::with_topology(3, 3, Topology::Hex);
+        map.place(String::from("first"), Point::new(1, 2)).unwrap();
+
+        let frame = TextRenderer::default().frame(0, &map, &[combatant("first", 10)]);
+        let rows: Vec<&str> = frame.lines().skip(1).take(3).collect();
+
+        assert_eq!(vec![". . .", " . . .", "  . A ."], rows);
+    }
+
+    #[test]
+    fn dead_combatants_are_listed_only() {
+        let mut map = Map::new(2, 1);
+        map.place(String::from("first"), Point::new(0, 0)).unwrap();
+        let mut second = combatant("second", 0);
+        second.team = Some(String::from("red"));
+
+        let frame = TextRenderer::default().frame(5, &map, &[combatant("first", 1), second]);
+
+        assert!(frame.contains("A .\n"));
+        assert!(frame.contains("A first   #.........   1/10\n"));
+        assert!(frame.contains("B second  dead  red\n"));
+    }
+
+    #[test]
+    fn single_character_avatars_are_labels() {
+        let mut map = Map::new(2, 1);
+        map.place(String::from("first"), Point::new(1, 0)).unwrap();
+        let mut first = combatant("first", 10);
+        first.avatar = Some(String::from("@"));
+
+        let frame = TextRenderer::default().frame(0, &map, &[first]);
+
+        assert!(frame.starts_with("Round 0\n. @\n"));
+    }
+
+    #[test]
+    fn large_maps_show_the_area_around_the_combatants() {
+        let mut map = Map::new(1000, 10);
+        map.place(String::from("first"), Point::new(500, 3)).unwrap();
+
+        let frame = TextRenderer::default().frame(0, &map, &[combatant("first", 10)]);
+        let lines: Vec<&str> = frame.lines().collect();
+
+        assert_eq!("x 499..562, y 0..9 of 1000x10", lines[1]);
+        assert!(lines[5].starts_with(". A ."));
+        assert_eq!(MAX_FRAME_SIZE as usize * 2 - 1, lines[5].len());
+    }
+
+    #[test]
+    fn ansi_colors_only_when_asked() {
+        let mut map = Map::new(1, 1);
+        map.place(String::from("first"), Point::new(0, 0)).unwrap();
+        let combatants = [combatant("first", 10)];
+
+        assert!(!TextRenderer::default().frame(0, &map, &combatants).contains('\x1b'));
+        assert!(TextRenderer::new(TextStyle::Ansi)
+            .frame(0, &map, &combatants)
+            .starts_with("Round 0\n\x1b[1;31mA\x1b[0m\n"));
+    }
+}
@@ -1,16 +1,22 @@
+pub mod battle_results;
+pub mod battles;
 pub mod battlefields;
 pub mod combatants;
+pub mod err;
+pub mod items;
+pub mod memory;
 pub mod middleware;
+pub mod migrations;
+pub mod surreal;
+pub mod tournaments;
+pub mod users;
 
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use surrealdb::engine::remote::ws::Client;
-use surrealdb::Surreal;
 
-pub struct GenericEntity<'a> {
-    db_connection: &'a Surreal<Client>,
-    collection_name: String,
-}
+use self::err::Error;
+use self::memory::MemoryRepository;
+use self::surreal::SurrealRepository;
 
 pub trait Entity: DeserializeOwned + Serialize + std::marker::Send + std::marker::Sync {
     fn collection_name() -> &'static str;
@@ -21,78 +27,247 @@ pub trait Record<TEntity: Entity>:
 {
     fn get_id(&self) -> String;
     fn get_entity(&self) -> TEntity;
+    fn get_version(&self) -> u32;
+
+    /// The id of the user owning the record, records without an owner are only accessible by admins
+    fn get_owner(&self) -> Option<String> {
+        None
+    }
+}
+
+/// The stored shape of an entity
+///
+/// Every write stores the entity together with its version, so that concurrent writers can be detected,
+/// and the user that created it
+#[derive(Serialize)]
+struct VersionedEntity<'e, TEntity: Entity> {
+    #[serde(flatten)]
+    entity: &'e TEntity,
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+}
+
+/// A single write of a batch, see [`Repository::write_all`]
+pub enum BatchWrite<TEntity: Entity> {
+    Create {
+        entity: TEntity,
+        owner: Option<String>,
+    },
+    /// Keeps the owner of the stored record
+    Update {
+        id: String,
+        entity: TEntity,
+        expected_version: Option<u32>,
+    },
+}
+
+/// Access to the stored entities, independent of where they are stored
+///
+/// Every write increments the version of the entity.
+/// If an expected version is given and it does not match the stored version the write is rejected.
+#[rocket::async_trait]
+pub trait Repository: std::marker::Send + std::marker::Sync {
+    async fn get_all<TEntity, TRecord>(&self, collection_name: &str) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>;
+
+    async fn get_by_id<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>;
+
+    /// Returns all records where the given field has the given value
+    async fn find_by<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>;
+
+    /// Stores a new entity, the owner is kept for the whole lifetime of the entity
+    async fn create_new<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        entity: TEntity,
+        owner: Option<String>,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>;
+
+    async fn update<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+        entity: TEntity,
+        expected_version: Option<u32>,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>;
+
+    async fn delete<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+        expected_version: Option<u32>,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>;
+
+    /// Applies either all writes or none of them, the written records are in the order of the writes
+    async fn write_all<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        writes: Vec<BatchWrite<TEntity>>,
+    ) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>;
+}
+
+/// The storage backend selected at application start
+pub enum Storage {
+    SurrealDb(SurrealRepository),
+    Memory(MemoryRepository),
+}
+
+impl Storage {
+    /// Determines if the storage backend can currently serve requests
+    pub async fn is_ready(&self) -> bool {
+        match self {
+            Storage::SurrealDb(r) => r.is_healthy().await,
+            Storage::Memory(_) => true,
+        }
+    }
+}
+
+pub struct GenericEntity<'a> {
+    storage: &'a Storage,
+    collection_name: String,
 }
 
 impl<'a> GenericEntity<'a> {
-    pub fn new<TEntity>(db: &'a Surreal<Client>) -> Self
+    pub fn new<TEntity>(storage: &'a Storage) -> Self
     where
         TEntity: Entity,
     {
         GenericEntity {
-            db_connection: db,
+            storage,
             collection_name: TEntity::collection_name().to_owned(),
         }
     }
 
-    pub async fn get_all<TEntity, TRecord>(&self) -> surrealdb::Result<Vec<TRecord>>
+    pub async fn get_all<TEntity, TRecord>(&self) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        match self.storage {
+            Storage::SurrealDb(r) => r.get_all(&self.collection_name).await,
+            Storage::Memory(r) => r.get_all(&self.collection_name).await,
+        }
+    }
+
+    pub async fn find_by<TEntity, TRecord>(&self, field: &str, value: &str) -> Result<Vec<TRecord>, Error>
     where
         TEntity: Entity,
         TRecord: Record<TEntity>,
     {
-        return self
-            .db_connection
-            .select(self.collection_name.clone())
-            .await;
+        match self.storage {
+            Storage::SurrealDb(r) => r.find_by(&self.collection_name, field, value).await,
+            Storage::Memory(r) => r.find_by(&self.collection_name, field, value).await,
+        }
     }
 
-    pub async fn create_new<TEntity, TRecord>(&self, entity: TEntity) -> surrealdb::Result<TRecord>
+    pub async fn create_new<TEntity, TRecord>(
+        &self,
+        entity: TEntity,
+        owner: Option<String>,
+    ) -> Result<TRecord, Error>
     where
         TEntity: Entity,
         TRecord: Record<TEntity>,
     {
-        return self
-            .db_connection
-            .create(self.collection_name.clone())
-            .content(entity)
-            .await;
+        match self.storage {
+            Storage::SurrealDb(r) => r.create_new(&self.collection_name, entity, owner).await,
+            Storage::Memory(r) => r.create_new(&self.collection_name, entity, owner).await,
+        }
     }
 
     pub async fn update<TEntity, TRecord>(
         &self,
         id: &str,
         entity: TEntity,
-    ) -> surrealdb::Result<TRecord>
+        expected_version: Option<u32>,
+    ) -> Result<TRecord, Error>
     where
         TEntity: Entity,
         TRecord: Record<TEntity>,
     {
-        return self
-            .db_connection
-            .update((self.collection_name.clone(), id))
-            .content(entity)
-            .await;
+        match self.storage {
+            Storage::SurrealDb(r) => {
+                r.update(&self.collection_name, id, entity, expected_version)
+                    .await
+            }
+            Storage::Memory(r) => {
+                r.update(&self.collection_name, id, entity, expected_version)
+                    .await
+            }
+        }
     }
 
-    pub async fn get_by_id<TEntity, TRecord>(&self, id: &str) -> surrealdb::Result<TRecord>
+    pub async fn get_by_id<TEntity, TRecord>(&self, id: &str) -> Result<TRecord, Error>
     where
         TEntity: Entity,
         TRecord: Record<TEntity>,
     {
-        return self
-            .db_connection
-            .select((self.collection_name.clone(), id))
-            .await;
+        match self.storage {
+            Storage::SurrealDb(r) => r.get_by_id(&self.collection_name, id).await,
+            Storage::Memory(r) => r.get_by_id(&self.collection_name, id).await,
+        }
     }
 
-    pub async fn delete<TEntity, TRecord>(&self, id: &str) -> surrealdb::Result<TRecord>
+    pub async fn delete<TEntity, TRecord>(
+        &self,
+        id: &str,
+        expected_version: Option<u32>,
+    ) -> Result<TRecord, Error>
     where
         TEntity: Entity,
         TRecord: Record<TEntity>,
     {
-        return self
-            .db_connection
-            .delete((self.collection_name.clone(), id))
-            .await;
+        match self.storage {
+            Storage::SurrealDb(r) => {
+                r.delete::<TEntity, TRecord>(&self.collection_name, id, expected_version)
+                    .await
+            }
+            Storage::Memory(r) => {
+                r.delete::<TEntity, TRecord>(&self.collection_name, id, expected_version)
+                    .await
+            }
+        }
+    }
+
+    pub async fn write_all<TEntity, TRecord>(&self, writes: Vec<BatchWrite<TEntity>>) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        match self.storage {
+            Storage::SurrealDb(r) => r.write_all(&self.collection_name, writes).await,
+            Storage::Memory(r) => r.write_all(&self.collection_name, writes).await,
+        }
     }
 }
 
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::{Entity, Record};
+
+pub const COLLECTION_NAME: &'static str = "BattleResults";
+
+/// A stored combatant that took part in a battle
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ParticipantEntity {
+    pub combatant: String,
+    pub place: u32,
+    pub rating_before: f64,
+    pub rating_after: f64,
+}
+
+/// The outcome of a battle with at least two stored combatants, the ratings are replayed from these
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BattleResultEntity {
+    /// The id of the stored battlefield, if the battle was fought on one
+    pub battlefield: Option<String>,
+    pub fought_at: DateTime<Utc>,
+    pub participants: Vec<ParticipantEntity>,
+}
+
+impl Entity for BattleResultEntity {
+    fn collection_name() -> &'static str {
+        COLLECTION_NAME
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BattleResultRecord {
+    pub id: Thing,
+    pub battlefield: Option<String>,
+    pub fought_at: DateTime<Utc>,
+    pub participants: Vec<ParticipantEntity>,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+impl Record<BattleResultEntity> for BattleResultRecord {
+    fn get_id(&self) -> String {
+        self.id.id.to_raw()
+    }
+
+    fn get_entity(&self) -> BattleResultEntity {
+        BattleResultEntity {
+            battlefield: self.battlefield.clone(),
+            fought_at: self.fought_at,
+            participants: self.participants.to_vec(),
+        }
+    }
+
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    fn get_owner(&self) -> Option<String> {
+        self.owner.clone()
+    }
+}
@@ -1,14 +1,16 @@
 use serde::{Deserialize, Serialize};
 use surrealdb::sql::Thing;
 
+use crate::types::point::Coordinate;
+
 use super::{Entity, Record, combatants::{CombatantEntity}};
 
 pub const COLLECTION_NAME: &'static str = "Battlefields";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BattleFieldEntity {
-    pub height: u8,
-    pub width: u8,
+    pub height: Coordinate,
+    pub width: Coordinate,
     pub combatants: Vec<CombatantEntity>
 }
 
@@ -20,10 +22,14 @@ impl Entity for BattleFieldEntity {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BattleFieldRecord {
-    pub height: u8,
-    pub width: u8,
+    pub height: Coordinate,
+    pub width: Coordinate,
     pub id: Thing,
-    pub combatants: Vec<CombatantEntity>
+    pub combatants: Vec<CombatantEntity>,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub owner: Option<String>,
 }
 
 impl Record<BattleFieldEntity> for BattleFieldRecord {
@@ -38,4 +44,12 @@ impl Record<BattleFieldEntity> for BattleFieldRecord {
             combatants: self.combatants.to_vec()
         }
     }
+
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    fn get_owner(&self) -> Option<String> {
+        self.owner.clone()
+    }
 }
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use crate::types::point::Coordinate;
+
+use super::{
+    combatants::{AbilityEntity, AbilityKindEntity, StatusEffectEntity, StatusEffectKindEntity},
+    items::ItemEntity,
+    Entity, Record,
+};
+
+pub const COLLECTION_NAME: &'static str = "Battles";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct PointEntity {
+    pub x: Coordinate,
+    pub y: Coordinate,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TopologyEntity {
+    Square4,
+    Square8,
+    Hex,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ReviveRulesEntity {
+    pub after_rounds: u32,
+    pub hp_percent: u8,
+    pub max_revives: u32,
+}
+
+/// The rules the battle was played by, the topology is kept with the map
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct BattleRulesEntity {
+    pub max_rounds: u32,
+    pub max_combatant_move: u32,
+    pub revive: Option<ReviveRulesEntity>,
+}
+
+/// A combatant as it entered the battle, the stats of its equipment included
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FighterEntity {
+    pub name: String,
+    /// The id of the stored combatant, if the combatant was stored
+    pub combatant: Option<String>,
+    pub avatar: Option<String>,
+    pub team: Option<String>,
+    pub hp: u16,
+    pub max_hp: u16,
+    pub dmg: u16,
+    pub armor: u16,
+    pub attack_range: u32,
+    pub speed: i8,
+    pub effects: Vec<StatusEffectEntity>,
+    pub on_hit: Vec<StatusEffectEntity>,
+    pub abilities: Vec<AbilityEntity>,
+    pub consumables: Vec<ItemEntity>,
+    pub location: PointEntity,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MovementEntity {
+    pub start: PointEntity,
+    pub goal: PointEntity,
+    pub steps: Vec<PointEntity>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AbilityTargetEntity {
+    pub name: String,
+    pub amount: u16,
+    pub remaining_hp: u16,
+}
+
+/// A single logged action, the hit points are the ones right after the action
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BattleActionEntity {
+    Move {
+        round: u32,
+        combatant: String,
+        movement: MovementEntity,
+    },
+    Attack {
+        round: u32,
+        attacker: String,
+        attacked: String,
+        damage: u16,
+        remaining_hp: u16,
+    },
+    EffectApplied {
+        round: u32,
+        combatant: String,
+        effect: StatusEffectEntity,
+    },
+    EffectTicked {
+        round: u32,
+        combatant: String,
+        kind: StatusEffectKindEntity,
+        amount: u16,
+        remaining_hp: u16,
+        remaining_turns: u32,
+    },
+    Ability {
+        round: u32,
+        combatant: String,
+        ability: String,
+        kind: AbilityKindEntity,
+        targets: Vec<AbilityTargetEntity>,
+        movement: Option<MovementEntity>,
+    },
+    Heal {
+        round: u32,
+        healer: String,
+        healed: String,
+        amount: u16,
+        remaining_hp: u16,
+        item: Option<String>,
+    },
+    Revive {
+        round: u32,
+        combatant: String,
+        location: PointEntity,
+        hp: u16,
+    },
+}
+
+/// A played battle, the state of any round is replayed from the fighters and the actions
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BattleEntity {
+    pub fought_at: DateTime<Utc>,
+    /// Random decisions are drawn from a generator seeded with this
+    #[serde(default)]
+    pub seed: u64,
+    /// Battles stored without rules were played by the default rules
+    #[serde(default)]
+    pub rules: Option<BattleRulesEntity>,
+    pub width: Coordinate,
+    pub height: Coordinate,
+    pub topology: TopologyEntity,
+    pub fighters: Vec<FighterEntity>,
+    pub actions: Vec<BattleActionEntity>,
+    pub rounds: u32,
+    pub winner: Option<String>,
+}
+
+impl Entity for BattleEntity {
+    fn collection_name() -> &'static str {
+        COLLECTION_NAME
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BattleRecord {
+    pub id: Thing,
+    pub fought_at: DateTime<Utc>,
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub rules: Option<BattleRulesEntity>,
+    pub width: Coordinate,
+    pub height: Coordinate,
+    pub topology: TopologyEntity,
+    pub fighters: Vec<FighterEntity>,
+    pub actions: Vec<BattleActionEntity>,
+    pub rounds: u32,
+    pub winner: Option<String>,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+impl Record<BattleEntity> for BattleRecord {
+    fn get_id(&self) -> String {
+        self.id.id.to_raw()
+    }
+
+    fn get_entity(&self) -> BattleEntity {
+        BattleEntity {
+            fought_at: self.fought_at,
+            seed: self.seed,
+            rules: self.rules,
+            width: self.width,
+            height: self.height,
+            topology: self.topology,
+            fighters: self.fighters.to_vec(),
+            actions: self.actions.to_vec(),
+            rounds: self.rounds,
+            winner: self.winner.clone(),
+        }
+    }
+
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    fn get_owner(&self) -> Option<String> {
+        self.owner.clone()
+    }
+}
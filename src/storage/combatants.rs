@@ -1,16 +1,92 @@
 use serde::{Deserialize, Serialize};
 use surrealdb::{sql::Thing};
 
+use crate::engine::rating::INITIAL_RATING;
+
 use super::{Entity, Record};
 
 pub const COLLECTION_NAME: &'static str = "Combatants";
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusEffectKindEntity {
+    Poison,
+    Bleed,
+    Stun,
+    Slow,
+    Regeneration,
+    Shield,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AbilityKindEntity {
+    AreaAttack,
+    Charge,
+    HealAlly,
+    Shield,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AbilityEntity {
+    pub name: String,
+    pub kind: AbilityKindEntity,
+    pub range: u32,
+    pub cooldown: u32,
+    pub power: u16,
+    pub effect: Option<StatusEffectEntity>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StatusEffectEntity {
+    pub kind: StatusEffectKindEntity,
+    pub turns: u32,
+    pub potency: u16,
+}
+
+/// The battles a stored combatant took part in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CareerEntity {
+    pub battles: u32,
+    pub wins: u32,
+    pub kills: u32,
+}
+
+// Combatants stored before progression existed start at the first level
+fn first_level() -> u32 {
+    1
+}
+
+fn initial_rating() -> f64 {
+    INITIAL_RATING
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CombatantEntity {
     pub name: String,
     pub damage_rating: u16,
     pub hit_points: u16,
+    #[serde(default)]
+    pub max_hit_points: Option<u16>,
     pub avatar: Option<String>,
+    /// The effects applied to every opponent the combatant hits
+    #[serde(default)]
+    pub on_hit: Vec<StatusEffectEntity>,
+    #[serde(default)]
+    pub abilities: Vec<AbilityEntity>,
+    #[serde(default)]
+    pub team: Option<String>,
+    /// Ids of the equipped items
+    #[serde(default)]
+    pub equipment: Vec<String>,
+    #[serde(default)]
+    pub experience: u32,
+    #[serde(default = "first_level")]
+    pub level: u32,
+    #[serde(default)]
+    pub career: CareerEntity,
+    #[serde(default = "initial_rating")]
+    pub rating: f64,
 }
 
 impl Entity for CombatantEntity {
@@ -25,7 +101,29 @@ pub struct CombatantRecord {
     pub name: String,
     pub damage_rating: u16,
     pub hit_points: u16,
+    #[serde(default)]
+    pub max_hit_points: Option<u16>,
     pub avatar: Option<String>,
+    #[serde(default)]
+    pub on_hit: Vec<StatusEffectEntity>,
+    #[serde(default)]
+    pub abilities: Vec<AbilityEntity>,
+    #[serde(default)]
+    pub team: Option<String>,
+    #[serde(default)]
+    pub equipment: Vec<String>,
+    #[serde(default)]
+    pub experience: u32,
+    #[serde(default = "first_level")]
+    pub level: u32,
+    #[serde(default)]
+    pub career: CareerEntity,
+    #[serde(default = "initial_rating")]
+    pub rating: f64,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub owner: Option<String>,
 }
 
 impl Record<CombatantEntity> for CombatantRecord {
@@ -38,7 +136,24 @@ impl Record<CombatantEntity> for CombatantRecord {
             name: self.name.clone(),
             damage_rating: self.damage_rating,
             hit_points: self.hit_points,
-            avatar: self.avatar.clone()
+            max_hit_points: self.max_hit_points,
+            avatar: self.avatar.clone(),
+            on_hit: self.on_hit.to_vec(),
+            abilities: self.abilities.to_vec(),
+            team: self.team.clone(),
+            equipment: self.equipment.to_vec(),
+            experience: self.experience,
+            level: self.level,
+            career: self.career,
+            rating: self.rating,
         }
     }
+
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    fn get_owner(&self) -> Option<String> {
+        self.owner.clone()
+    }
 }
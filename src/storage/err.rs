@@ -0,0 +1,38 @@
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The requested record does not exist in the collection
+    NotFound,
+    /// The stored record has a different version than the expected one (expected, actual)
+    VersionMismatch(u32, u32),
+    Database(surrealdb::Error),
+    Serialization(serde_json::Error),
+}
+
+impl From<surrealdb::Error> for Error {
+    fn from(value: surrealdb::Error) -> Self {
+        Error::Database(value)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Error::Serialization(value)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound => write!(f, "record not found"),
+            Error::VersionMismatch(expected, actual) => write!(
+                f,
+                "expected version {} but the stored version is {}",
+                expected, actual
+            ),
+            Error::Database(e) => write!(f, "{}", e),
+            Error::Serialization(e) => write!(f, "{}", e),
+        }
+    }
+}
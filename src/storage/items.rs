@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::{Entity, Record};
+
+pub const COLLECTION_NAME: &'static str = "Items";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKindEntity {
+    Weapon,
+    Armor,
+    Consumable,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ItemEntity {
+    pub name: String,
+    pub kind: ItemKindEntity,
+    #[serde(default)]
+    pub damage: u16,
+    #[serde(default)]
+    pub armor: u16,
+    #[serde(default)]
+    pub range: u32,
+    #[serde(default)]
+    pub speed: i8,
+    /// Hit points restored when a consumable is used
+    #[serde(default)]
+    pub heal: u16,
+}
+
+impl Entity for ItemEntity {
+    fn collection_name() -> &'static str {
+        COLLECTION_NAME
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ItemRecord {
+    pub id: Thing,
+    pub name: String,
+    pub kind: ItemKindEntity,
+    #[serde(default)]
+    pub damage: u16,
+    #[serde(default)]
+    pub armor: u16,
+    #[serde(default)]
+    pub range: u32,
+    #[serde(default)]
+    pub speed: i8,
+    #[serde(default)]
+    pub heal: u16,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+impl Record<ItemEntity> for ItemRecord {
+    fn get_id(&self) -> String {
+        self.id.id.to_raw()
+    }
+
+    fn get_entity(&self) -> ItemEntity {
+        ItemEntity {
+            name: self.name.clone(),
+            kind: self.kind,
+            damage: self.damage,
+            armor: self.armor,
+            range: self.range,
+            speed: self.speed,
+            heal: self.heal,
+        }
+    }
+
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    fn get_owner(&self) -> Option<String> {
+        self.owner.clone()
+    }
+}
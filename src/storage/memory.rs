@@ -0,0 +1,429 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde_json::Value;
+use surrealdb::sql::Thing;
+
+use super::{err::Error, BatchWrite, Entity, Record, Repository, VersionedEntity};
+
+// The length of the generated record ids, the same as SurrealDB uses
+const ID_LENGTH: usize = 20;
+
+/// Keeps all entities in process memory
+///
+/// Nothing is persisted, so all data is lost when the application stops.
+/// Intended for local runs and tests that should not depend on an external database.
+#[derive(Default)]
+pub struct MemoryRepository {
+    collections: RwLock<HashMap<String, HashMap<String, Value>>>,
+}
+
+impl MemoryRepository {
+    /// Builds the stored document in the same shape SurrealDB would return it
+    fn to_document<TEntity: Entity>(
+        collection_name: &str,
+        id: &str,
+        entity: &TEntity,
+        version: u32,
+        owner: Option<String>,
+    ) -> Result<Value, Error> {
+        let mut document = serde_json::to_value(VersionedEntity {
+            entity,
+            version,
+            owner,
+        })?;
+
+        if let Value::Object(fields) = &mut document {
+            fields.insert(
+                String::from("id"),
+                serde_json::to_value(Thing::from((collection_name.to_owned(), id.to_owned())))?,
+            );
+        }
+
+        Ok(document)
+    }
+
+    fn owner_of(document: &Value) -> Option<String> {
+        document
+            .get("owner")
+            .and_then(|o| o.as_str())
+            .map(|o| o.to_owned())
+    }
+
+    fn version_of(document: &Value) -> u32 {
+        document
+            .get("version")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32)
+            .unwrap_or(0)
+    }
+
+    /// Fails if the document does not have the expected version
+    fn check_version(document: &Value, expected_version: Option<u32>) -> Result<(), Error> {
+        let current_version = MemoryRepository::version_of(document);
+
+        match expected_version {
+            Some(expected) if expected != current_version => {
+                Err(Error::VersionMismatch(expected, current_version))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn generate_id() -> String {
+        thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(ID_LENGTH)
+            .map(char::from)
+            .collect::<String>()
+            .to_lowercase()
+    }
+}
+
+#[rocket::async_trait]
+impl Repository for MemoryRepository {
+    async fn get_all<TEntity, TRecord>(&self, collection_name: &str) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let collections = self.collections.read().unwrap();
+
+        match collections.get(collection_name) {
+            Some(collection) => collection
+                .values()
+                .map(|document| Ok(serde_json::from_value(document.clone())?))
+                .collect(),
+            None => Ok(vec![]),
+        }
+    }
+
+    async fn get_by_id<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let collections = self.collections.read().unwrap();
+        let document = collections
+            .get(collection_name)
+            .and_then(|collection| collection.get(id))
+            .ok_or(Error::NotFound)?;
+
+        return Ok(serde_json::from_value(document.clone())?);
+    }
+
+    async fn find_by<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let collections = self.collections.read().unwrap();
+
+        match collections.get(collection_name) {
+            Some(collection) => collection
+                .values()
+                .filter(|document| document.get(field).and_then(|f| f.as_str()) == Some(value))
+                .map(|document| Ok(serde_json::from_value(document.clone())?))
+                .collect(),
+            None => Ok(vec![]),
+        }
+    }
+
+    async fn create_new<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        entity: TEntity,
+        owner: Option<String>,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let id = MemoryRepository::generate_id();
+        let document = MemoryRepository::to_document(collection_name, &id, &entity, 1, owner)?;
+
+        let mut collections = self.collections.write().unwrap();
+        collections
+            .entry(collection_name.to_owned())
+            .or_default()
+            .insert(id, document.clone());
+
+        return Ok(serde_json::from_value(document)?);
+    }
+
+    async fn update<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+        entity: TEntity,
+        expected_version: Option<u32>,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let mut collections = self.collections.write().unwrap();
+        let stored = collections
+            .get_mut(collection_name)
+            .and_then(|collection| collection.get_mut(id))
+            .ok_or(Error::NotFound)?;
+
+        MemoryRepository::check_version(stored, expected_version)?;
+
+        let new_version = MemoryRepository::version_of(stored) + 1;
+        let owner = MemoryRepository::owner_of(stored);
+        *stored = MemoryRepository::to_document(collection_name, id, &entity, new_version, owner)?;
+
+        return Ok(serde_json::from_value(stored.clone())?);
+    }
+
+    async fn delete<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+        expected_version: Option<u32>,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let mut collections = self.collections.write().unwrap();
+        let collection = collections
+            .get_mut(collection_name)
+            .ok_or(Error::NotFound)?;
+
+        MemoryRepository::check_version(
+            collection.get(id).ok_or(Error::NotFound)?,
+            expected_version,
+        )?;
+
+        let document = collection.remove(id).ok_or(Error::NotFound)?;
+
+        return Ok(serde_json::from_value(document)?);
+    }
+
+    /// All documents are built and checked before the first one is stored
+    async fn write_all<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        writes: Vec<BatchWrite<TEntity>>,
+    ) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let mut collections = self.collections.write().unwrap();
+        let collection = collections.entry(collection_name.to_owned()).or_default();
+
+        let mut documents: Vec<(String, Value)> = vec![];
+        for write in writes.iter() {
+            documents.push(match write {
+                BatchWrite::Create { entity, owner } => {
+                    let id = MemoryRepository::generate_id();
+                    let document = MemoryRepository::to_document(collection_name, &id, entity, 1, owner.clone())?;
+                    (id, document)
+                }
+                BatchWrite::Update {
+                    id,
+                    entity,
+                    expected_version,
+                } => {
+                    let stored = collection.get(id).ok_or(Error::NotFound)?;
+                    MemoryRepository::check_version(stored, *expected_version)?;
+
+                    let new_version = MemoryRepository::version_of(stored) + 1;
+                    let owner = MemoryRepository::owner_of(stored);
+                    let document = MemoryRepository::to_document(collection_name, id, entity, new_version, owner)?;
+                    (id.clone(), document)
+                }
+            });
+        }
+
+        let records = documents
+            .iter()
+            .map(|(_, document)| Ok(serde_json::from_value(document.clone())?))
+            .collect::<Result<Vec<TRecord>, Error>>()?;
+        collection.extend(documents);
+
+        return Ok(records);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::combatants::{CareerEntity, CombatantEntity, CombatantRecord, COLLECTION_NAME};
+
+    fn combatant(name: &str) -> CombatantEntity {
+        CombatantEntity {
+            name: name.to_owned(),
+            damage_rating: 2,
+            hit_points: 10,
+            max_hit_points: None,
+            avatar: None,
+            on_hit: vec![],
+            abilities: vec![],
+            team: None,
+            equipment: vec![],
+            experience: 0,
+            level: 1,
+            career: CareerEntity::default(),
+            rating: 1500.0,
+        }
+    }
+
+    #[rocket::async_test]
+    async fn create_and_get_by_id() {
+        let test_object = MemoryRepository::default();
+
+        let created: CombatantRecord = test_object
+            .create_new(COLLECTION_NAME, combatant("test"), None)
+            .await
+            .unwrap();
+        let loaded: CombatantRecord = test_object
+            .get_by_id::<CombatantEntity, CombatantRecord>(COLLECTION_NAME, &created.get_id())
+            .await
+            .unwrap();
+
+        assert_eq!("test", loaded.name);
+        assert_eq!(1, loaded.version);
+    }
+
+    #[rocket::async_test]
+    async fn update_increments_version() {
+        let test_object = MemoryRepository::default();
+        let created: CombatantRecord = test_object
+            .create_new(COLLECTION_NAME, combatant("test"), None)
+            .await
+            .unwrap();
+
+        let updated: CombatantRecord = test_object
+            .update(COLLECTION_NAME, &created.get_id(), combatant("renamed"), Some(1))
+            .await
+            .unwrap();
+
+        assert_eq!("renamed", updated.name);
+        assert_eq!(2, updated.version);
+    }
+
+    #[rocket::async_test]
+    async fn update_with_stale_version() {
+        let test_object = MemoryRepository::default();
+        let created: CombatantRecord = test_object
+            .create_new(COLLECTION_NAME, combatant("test"), None)
+            .await
+            .unwrap();
+        let _: CombatantRecord = test_object
+            .update(COLLECTION_NAME, &created.get_id(), combatant("first"), Some(1))
+            .await
+            .unwrap();
+
+        let result = test_object
+            .update::<CombatantEntity, CombatantRecord>(
+                COLLECTION_NAME,
+                &created.get_id(),
+                combatant("second"),
+                Some(1),
+            )
+            .await;
+
+        assert!(matches!(result, Err(Error::VersionMismatch(1, 2))));
+    }
+
+    #[rocket::async_test]
+    async fn update_keeps_owner() {
+        let test_object = MemoryRepository::default();
+        let created: CombatantRecord = test_object
+            .create_new(COLLECTION_NAME, combatant("test"), Some(String::from("user")))
+            .await
+            .unwrap();
+
+        let updated: CombatantRecord = test_object
+            .update(COLLECTION_NAME, &created.get_id(), combatant("renamed"), None)
+            .await
+            .unwrap();
+
+        assert_eq!(Some(String::from("user")), updated.get_owner());
+    }
+
+    #[rocket::async_test]
+    async fn find_by_name() {
+        let test_object = MemoryRepository::default();
+        let _: CombatantRecord = test_object
+            .create_new(COLLECTION_NAME, combatant("first"), None)
+            .await
+            .unwrap();
+        let _: CombatantRecord = test_object
+            .create_new(COLLECTION_NAME, combatant("second"), None)
+            .await
+            .unwrap();
+
+        let found: Vec<CombatantRecord> = test_object
+            .find_by(COLLECTION_NAME, "name", "second")
+            .await
+            .unwrap();
+
+        assert_eq!(1, found.len());
+        assert_eq!("second", found[0].name);
+    }
+
+    #[rocket::async_test]
+    async fn write_all_or_nothing() {
+        let test_object = MemoryRepository::default();
+        let created: CombatantRecord = test_object
+            .create_new(COLLECTION_NAME, combatant("first"), None)
+            .await
+            .unwrap();
+
+        let stale = test_object
+            .write_all::<CombatantEntity, CombatantRecord>(
+                COLLECTION_NAME,
+                vec![
+                    BatchWrite::Create { entity: combatant("second"), owner: None },
+                    BatchWrite::Update { id: created.get_id(), entity: combatant("renamed"), expected_version: Some(2) },
+                ],
+            )
+            .await;
+        let all: Vec<CombatantRecord> = test_object.get_all(COLLECTION_NAME).await.unwrap();
+
+        assert!(matches!(stale, Err(Error::VersionMismatch(2, 1))));
+        assert_eq!(1, all.len());
+        assert_eq!("first", all[0].name);
+
+        let written: Vec<CombatantRecord> = test_object
+            .write_all(
+                COLLECTION_NAME,
+                vec![
+                    BatchWrite::Create { entity: combatant("second"), owner: None },
+                    BatchWrite::Update { id: created.get_id(), entity: combatant("renamed"), expected_version: Some(1) },
+                ],
+            )
+            .await
+            .unwrap();
+        let all: Vec<CombatantRecord> = test_object.get_all(COLLECTION_NAME).await.unwrap();
+
+        assert_eq!(vec!["second", "renamed"], written.iter().map(|r| r.name.as_str()).collect::<Vec<&str>>());
+        assert_eq!(2, written[1].version);
+        assert_eq!(2, all.len());
+    }
+
+    #[rocket::async_test]
+    async fn delete_unknown_id() {
+        let test_object = MemoryRepository::default();
+
+        let result = test_object
+            .delete::<CombatantEntity, CombatantRecord>(COLLECTION_NAME, "unknown", None)
+            .await;
+
+        assert!(matches!(result, Err(Error::NotFound)));
+    }
+}
@@ -1,60 +1,32 @@
-use std::env;
+use std::time::Duration;
 
 use rocket::{
     fairing::{Fairing, Info, Kind, Result},
-    Build, Rocket,
+    tokio::{self, time::sleep},
+    Build, Orbit, Rocket,
 };
-use serde::Deserialize;
 use surrealdb::{
     engine::remote::ws::{Ws, Client},
     opt::auth::Root,
     Surreal,
 };
 
-#[derive(Deserialize)]
-struct DbConfig {
-    namespace: String,
-    database: String,
-    user: String,
-    pass: String,
-    address: String,
-}
+use crate::config::{DbConfig, StorageEngine};
 
-impl Default for DbConfig {
-    fn default() -> Self {
-        let address = env_or_default("db_address", "127.0.0.1:8000");
-        let user = env_or_default("db_username", "root");
-        let pass = env_or_default("db_password", "root");
-        let namespace = env_or_default("db_namespace", "development");
-        let database = env_or_default("db_name", "ribs");
-        Self {
-            namespace,
-            database,
-            user,
-            pass,
-            address,
-        }
-    }
-}
+use super::{memory::MemoryRepository, migrations::MIGRATIONS, surreal::SurrealRepository, Storage};
 
-fn env_or_default<'a>(key: &'a str, default: &'a str) -> String {
-    let env_opt_val = env::var(key);
+// The delay before the first connection retry, doubled with each failed attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
 
-    match env_opt_val {
-        Ok(val) => val,
-        Err(_) => default.to_owned(),
-    }
-}
+// The upper limit for the delay between two connection attempts
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
 
 pub struct DbMiddleware;
 
 impl DbMiddleware {
 
     /// Returns an active connection to the database
-    /// 
-    /// The connection data is retrieved from the DbConfig default value 
-    async fn connect(&self) -> Result<Surreal<Client>, surrealdb::Error> {
-        let db_config: DbConfig = DbConfig::default();
+    async fn connect(db_config: &DbConfig) -> std::result::Result<Surreal<Client>, surrealdb::Error> {
         let db = Surreal::new::<Ws>(db_config.address.clone()).await?;
 
         db.signin(Root {
@@ -63,12 +35,60 @@ impl DbMiddleware {
         })
         .await?;
 
-        db.use_ns(db_config.namespace)
-        .use_db(db_config.database)
+        db.use_ns(db_config.namespace.clone())
+        .use_db(db_config.database.clone())
         .await?;
 
         return Ok(db);
     }
+
+    /// Tries to connect to the database until it succeeds or the configured number of retries is exhausted
+    ///
+    /// The delay between the attempts is doubled after each failure, so a database that is still starting
+    /// up (as in docker compose) is not flooded with connection attempts
+    async fn connect_with_backoff(db_config: &DbConfig) -> std::result::Result<Surreal<Client>, surrealdb::Error> {
+        let mut delay = INITIAL_RETRY_DELAY;
+        let mut attempt: u32 = 0;
+
+        loop {
+            match DbMiddleware::connect(db_config).await {
+                Ok(db) => return Ok(db),
+                Err(e) if attempt < db_config.connect_retries => {
+                    attempt = attempt + 1;
+                    warn!(
+                        "Could not connect to {} ({}), retry {} of {} in {:?}",
+                        db_config.address, e, attempt, db_config.connect_retries, delay
+                    );
+                    sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Periodically checks the database connection and replaces it if the database stopped answering
+    async fn watch_connection(repository: SurrealRepository, db_config: DbConfig) {
+        let interval = Duration::from_secs(db_config.health_interval);
+
+        loop {
+            sleep(interval).await;
+
+            if repository.is_healthy().await {
+                continue;
+            }
+
+            warn!("Lost the connection to {}, reconnecting", db_config.address);
+
+            match DbMiddleware::connect(&db_config).await {
+                Ok(db_connection) => {
+                    repository.replace_connection(db_connection).await;
+                    info!("Reconnected to {}", db_config.address);
+                }
+                Err(e) => warn!("Could not reconnect to {}: {}", db_config.address, e),
+            }
+        }
+    }
 }
 
 #[rocket::async_trait]
@@ -76,28 +96,72 @@ impl Fairing for DbMiddleware {
     fn info(&self) -> Info {
         Info {
             name: "Database Middleware",
-            kind: Kind::Ignite,
+            kind: Kind::Ignite | Kind::Liftoff,
         }
     }
 
-    /// Enrolls the db connection state management at application "ignite"
+    /// Enrolls the storage state management at application "ignite"
+    /// 
+    /// The database settings are read from the "database" section of the configuration
+    /// 
+    /// When the memory engine is configured no database connection is opened at all
     /// 
     /// EXPLANATION:
     /// We can do this because we are using a standing WS (WebSocket) connection to the database
     /// Because of that we only need to open a connection at application start and then only reuse it
     /// 
+    /// Pending migrations are applied before the storage is managed, see [`super::migrations`]
+    ///
     /// NOTE:
-    /// In the case that the connection to the database is interrupted we do not reestablish automatically!
-    /// The application has to be restarted!
+    /// If the database can not be reached after all retries or a migration fails the application does not start
     async fn on_ignite(&self, rocket: Rocket<Build>) -> Result {
-        let db = self.connect().await;
-        match db {
-            Ok(db_connection) => Ok(rocket.manage(db_connection)),
-            Err(e) => panic!(
-                "Could not connect to {} with reason: {}",
-                DbConfig::default().address,
-                e.to_string()
-            ),
+        let db_config: DbConfig = match rocket.figment().extract_inner("database") {
+            Ok(c) => c,
+            Err(e) => {
+                error!("Invalid database configuration: {}", e);
+                return Err(rocket);
+            }
+        };
+
+        if db_config.engine == StorageEngine::Memory {
+            info!("using the in-memory storage, nothing will be persisted");
+            return Ok(rocket.manage(Storage::Memory(MemoryRepository::default())));
+        }
+
+        let repository = match DbMiddleware::connect_with_backoff(&db_config).await {
+            Ok(db_connection) => SurrealRepository::new(db_connection),
+            Err(e) => {
+                error!(
+                    "Could not connect to {} with reason: {}",
+                    db_config.address,
+                    e.to_string()
+                );
+                return Err(rocket);
+            }
+        };
+
+        match repository.migrate(&MIGRATIONS).await {
+            Ok(applied) if applied.is_empty() => info!("the database schema is up to date"),
+            Ok(applied) => info!("applied the database migrations {:?}", applied),
+            Err(e) => {
+                error!("Could not migrate {}: {}", db_config.address, e);
+                return Err(rocket);
+            }
+        }
+
+        Ok(rocket.manage(Storage::SurrealDb(repository)))
+    }
+
+    /// Starts watching the database connection once the application is running
+    ///
+    /// In the case that the connection to the database is interrupted it is reestablished automatically
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let db_config: Option<DbConfig> = rocket.figment().extract_inner("database").ok();
+
+        if let (Some(Storage::SurrealDb(repository)), Some(db_config)) =
+            (rocket.state::<Storage>(), db_config)
+        {
+            tokio::spawn(DbMiddleware::watch_connection(repository.clone(), db_config));
         }
     }
 }
@@ -0,0 +1,242 @@
+use std::{collections::HashSet, fmt::Display};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use surrealdb::sql::Thing;
+
+use super::err::Error;
+
+/// The collection the applied migrations are tracked in
+pub const COLLECTION_NAME: &str = "Migrations";
+
+/// Defines the collection of the applied migrations, run before every migration
+pub const TRACKING_SCRIPT: &str = "
+DEFINE TABLE Migrations SCHEMAFULL;
+DEFINE FIELD version ON TABLE Migrations TYPE int ASSERT $value != NONE;
+DEFINE FIELD name ON TABLE Migrations TYPE string ASSERT $value != NONE;
+DEFINE FIELD checksum ON TABLE Migrations TYPE string ASSERT $value != NONE;
+DEFINE FIELD applied_at ON TABLE Migrations TYPE datetime;
+";
+
+/// A SurrealQL script changing the schema or the stored documents, applied once
+///
+/// Applied scripts must not be changed anymore, every change needs a new migration with a higher version.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub script: &'static str,
+    /// The collection whose documents are renamed before the script runs, so that no owner uses a name twice
+    pub unique_names: Option<&'static str>,
+}
+
+impl Migration {
+    /// Identifies the script, so that scripts changed after they were applied are detected
+    pub fn checksum(&self) -> String {
+        format!("{:x}", Sha256::digest(self.script.as_bytes()))
+    }
+}
+
+/// All migrations in the order they are applied in
+pub const MIGRATIONS: [Migration; 2] = [
+    Migration {
+        version: 1,
+        name: "combatants",
+        script: include_str!("migrations/0001_combatants.surql"),
+        unique_names: Some("Combatants"),
+    },
+    Migration {
+        version: 2,
+        name: "battlefields",
+        script: include_str!("migrations/0002_battlefields.surql"),
+        unique_names: None,
+    },
+];
+
+/// A migration as tracked in the database
+#[derive(Debug, Deserialize)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub checksum: String,
+}
+
+/// A stored document that has to be named uniquely for its owner
+#[derive(Debug, Deserialize)]
+pub struct NamedDocument {
+    pub id: Thing,
+    #[serde(default)]
+    pub owner: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// An applied migration is not known, the database was migrated by a newer version of the application (version, name)
+    Unknown(u32, String),
+    /// The script of an applied migration was changed since
+    Changed(u32),
+    /// Nothing of the migration was applied (version, reason)
+    Failed(u32, Error),
+    Storage(Error),
+}
+
+impl From<Error> for MigrationError {
+    fn from(value: Error) -> Self {
+        MigrationError::Storage(value)
+    }
+}
+
+impl From<surrealdb::Error> for MigrationError {
+    fn from(value: surrealdb::Error) -> Self {
+        MigrationError::Storage(Error::from(value))
+    }
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::Unknown(version, name) => {
+                write!(f, "the applied migration {} ({}) is unknown", version, name)
+            }
+            MigrationError::Changed(version) => {
+                write!(f, "the migration {} was changed after it was applied", version)
+            }
+            MigrationError::Failed(version, e) => write!(f, "the migration {} failed: {}", version, e),
+            MigrationError::Storage(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// The migrations not applied yet, in the order they are applied in
+///
+/// Fails if the applied migrations do not match the known ones.
+pub fn pending<'m>(
+    migrations: &'m [Migration],
+    applied: &[AppliedMigration],
+) -> Result<Vec<&'m Migration>, MigrationError> {
+    for migration in applied.iter() {
+        match migrations.iter().find(|m| m.version == migration.version) {
+            None => return Err(MigrationError::Unknown(migration.version, migration.name.clone())),
+            Some(known) if known.checksum() != migration.checksum => {
+                return Err(MigrationError::Changed(migration.version))
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(migrations
+        .iter()
+        .filter(|m| !applied.iter().any(|a| a.version == m.version))
+        .collect())
+}
+
+/// The new names of the documents that share their name with an earlier document of the same owner, by id
+///
+/// The first document keeps the name, the others are numbered from 2 on, skipping names already in use.
+pub fn unique_names(documents: &[NamedDocument]) -> Vec<(String, String)> {
+    let mut taken: HashSet<(Option<&str>, String)> = documents
+        .iter()
+        .map(|d| (d.owner.as_deref(), d.name.clone()))
+        .collect();
+    let mut seen: HashSet<(Option<&str>, &str)> = HashSet::new();
+    let mut renames: Vec<(String, String)> = vec![];
+
+    for document in documents.iter() {
+        let owner = document.owner.as_deref();
+        if seen.insert((owner, &document.name)) {
+            continue;
+        }
+
+        let name = (2..)
+            .map(|n| format!("{} ({})", document.name, n))
+            .find(|name| !taken.contains(&(owner, name.clone())))
+            .unwrap();
+        taken.insert((owner, name.clone()));
+        renames.push((document.id.id.to_raw(), name));
+    }
+
+    renames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(migration: &Migration) -> AppliedMigration {
+        AppliedMigration {
+            version: migration.version,
+            name: migration.name.to_owned(),
+            checksum: migration.checksum(),
+        }
+    }
+
+    #[test]
+    fn versions_increase() {
+        assert!(MIGRATIONS.windows(2).all(|pair| pair[0].version < pair[1].version));
+        assert!(MIGRATIONS.iter().all(|m| !m.script.trim().is_empty()));
+    }
+
+    #[test]
+    fn all_pending_on_empty_database() {
+        let pending = pending(&MIGRATIONS, &[]).unwrap();
+
+        assert_eq!(
+            MIGRATIONS.iter().map(|m| m.version).collect::<Vec<u32>>(),
+            pending.iter().map(|m| m.version).collect::<Vec<u32>>()
+        );
+    }
+
+    #[test]
+    fn applied_migrations_are_skipped() {
+        let pending = pending(&MIGRATIONS, &[applied(&MIGRATIONS[0])]).unwrap();
+
+        assert_eq!(vec![2], pending.iter().map(|m| m.version).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn changed_and_unknown_migrations() {
+        let mut changed = applied(&MIGRATIONS[0]);
+        changed.checksum = String::from("edited");
+        let unknown = AppliedMigration {
+            version: 99,
+            name: String::from("future"),
+            checksum: String::new(),
+        };
+
+        assert!(matches!(pending(&MIGRATIONS, &[changed]), Err(MigrationError::Changed(1))));
+        assert!(matches!(pending(&MIGRATIONS, &[unknown]), Err(MigrationError::Unknown(99, _))));
+    }
+
+    fn document(id: &str, owner: Option<&str>, name: &str) -> NamedDocument {
+        NamedDocument {
+            id: Thing::from(("Combatants", id)),
+            owner: owner.map(String::from),
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn duplicate_names_are_numbered() {
+        let documents = vec![
+            document("a", Some("first"), "knight"),
+            document("b", Some("first"), "knight"),
+            document("c", Some("first"), "knight (2)"),
+            document("d", Some("first"), "knight"),
+            document("e", Some("second"), "knight"),
+            document("f", None, "knight"),
+            document("g", None, "knight"),
+        ];
+
+        let renames = unique_names(&documents);
+
+        assert_eq!(
+            vec![
+                (String::from("b"), String::from("knight (3)")),
+                (String::from("d"), String::from("knight (4)")),
+                (String::from("g"), String::from("knight (2)")),
+            ],
+            renames
+        );
+        assert_eq!(1, unique_names(&documents[..3]).len());
+    }
+}
@@ -0,0 +1,412 @@
+use std::{sync::Arc, time::Duration};
+
+use rocket::tokio::{sync::RwLock, time::timeout};
+use surrealdb::{engine::remote::ws::Client, Surreal};
+
+use super::{
+    err::Error,
+    migrations::{
+        pending, unique_names, AppliedMigration, Migration, MigrationError, NamedDocument, COLLECTION_NAME as MIGRATIONS,
+        TRACKING_SCRIPT,
+    },
+    BatchWrite, Entity, Record, Repository, VersionedEntity,
+};
+
+// How long a health check may take before the database is considered unreachable
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Stores the entities in a SurrealDB instance
+///
+/// The connection is a standing WS (WebSocket) connection established by the DbMiddleware.
+/// Clones share the same connection, so a replaced connection is picked up by all of them.
+#[derive(Clone)]
+pub struct SurrealRepository {
+    db_connection: Arc<RwLock<Surreal<Client>>>,
+}
+
+impl SurrealRepository {
+    pub fn new(db_connection: Surreal<Client>) -> Self {
+        SurrealRepository {
+            db_connection: Arc::new(RwLock::new(db_connection)),
+        }
+    }
+
+    async fn connection(&self) -> Surreal<Client> {
+        self.db_connection.read().await.clone()
+    }
+
+    /// Swaps the used connection for a freshly established one
+    pub async fn replace_connection(&self, db_connection: Surreal<Client>) {
+        *self.db_connection.write().await = db_connection;
+    }
+
+    /// Determines if the database answers queries over the current connection
+    pub async fn is_healthy(&self) -> bool {
+        let db = self.connection().await;
+
+        match timeout(HEALTH_CHECK_TIMEOUT, db.query("RETURN true")).await {
+            Ok(Ok(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Applies the migrations not applied yet, returns the versions applied
+    ///
+    /// Every migration is applied in a transaction together with its entry in the tracking collection,
+    /// so that a failing script leaves nothing behind and an instance migrating at the same time fails on the entry.
+    pub async fn migrate(&self, migrations: &[Migration]) -> Result<Vec<u32>, MigrationError> {
+        let db = self.connection().await;
+
+        db.query(TRACKING_SCRIPT).await?;
+        let applied: Vec<AppliedMigration> = db
+            .query("SELECT version, name, checksum FROM type::table($collection) ORDER BY version")
+            .bind(("collection", MIGRATIONS))
+            .await?
+            .take(0)?;
+
+        let mut versions: Vec<u32> = vec![];
+        for migration in pending(migrations, &applied)? {
+            let failed = |e: surrealdb::Error| MigrationError::Failed(migration.version, Error::from(e));
+
+            // Documents named like an earlier one of their owner are renamed first, unique indexes would fail on them
+            let renames: Vec<(String, String)> = match migration.unique_names {
+                Some(collection) => {
+                    let documents: Vec<NamedDocument> = db
+                        .query("SELECT id, owner, name FROM type::table($collection) ORDER BY id")
+                        .bind(("collection", collection))
+                        .await
+                        .map_err(failed)?
+                        .take(0)
+                        .map_err(failed)?;
+                    let renames = unique_names(&documents);
+                    for (id, name) in renames.iter() {
+                        warn!("Renaming {}:{} to {}, its owner uses the name more than once", collection, id, name);
+                    }
+
+                    renames
+                }
+                None => vec![],
+            };
+            let rename_statements: String = (0..renames.len())
+                .map(|i| format!("UPDATE type::thing($renamed, $rename_id{i}) SET name = $rename_name{i};\n", i = i))
+                .collect();
+
+            // The entry comes first, every statement of a failed transaction reports the failure
+            let mut query = db
+                .query(format!(
+                    "BEGIN TRANSACTION;\n\
+                     CREATE type::thing($collection, $version) SET version = $version, name = $name, checksum = $checksum, applied_at = time::now();\n\
+                     {}{}\n\
+                     COMMIT TRANSACTION;",
+                    rename_statements, migration.script
+                ))
+                .bind(("collection", MIGRATIONS))
+                .bind(("version", migration.version))
+                .bind(("name", migration.name))
+                .bind(("checksum", migration.checksum()))
+                .bind(("renamed", migration.unique_names.unwrap_or_default()));
+            for (i, (id, name)) in renames.into_iter().enumerate() {
+                query = query.bind((format!("rename_id{}", i), id)).bind((format!("rename_name{}", i), name));
+            }
+            let mut response = query.await.map_err(failed)?;
+            let _: Vec<AppliedMigration> = response.take(0).map_err(failed)?;
+
+            versions.push(migration.version);
+        }
+
+        Ok(versions)
+    }
+
+    /// Returns the currently stored record if its version matches the expected version
+    async fn check_version<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+        expected_version: Option<u32>,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let current: TRecord = self.get_by_id(collection_name, id).await?;
+        let current_version = current.get_version();
+
+        match expected_version {
+            Some(expected) if expected != current_version => {
+                Err(Error::VersionMismatch(expected, current_version))
+            }
+            _ => Ok(current),
+        }
+    }
+
+    /// Determines why a conditional write did not touch any record
+    async fn conflict_for<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+        expected_version: u32,
+    ) -> Error
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        match self.get_by_id::<TEntity, TRecord>(collection_name, id).await {
+            Ok(record) => Error::VersionMismatch(expected_version, record.get_version()),
+            Err(e) => e,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Repository for SurrealRepository {
+    async fn get_all<TEntity, TRecord>(&self, collection_name: &str) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let records: Vec<TRecord> = self.connection().await.select(collection_name).await?;
+
+        return Ok(records);
+    }
+
+    async fn get_by_id<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let record: Option<TRecord> = self
+            .connection()
+            .await
+            .select((collection_name, id))
+            .await?;
+
+        return record.ok_or(Error::NotFound);
+    }
+
+    async fn find_by<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        field: &str,
+        value: &str,
+    ) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        // the field name is never user input, only the value is
+        let mut response = self
+            .connection()
+            .await
+            .query(format!(
+                "SELECT * FROM type::table($collection) WHERE {} = $value",
+                field
+            ))
+            .bind(("collection", collection_name))
+            .bind(("value", value))
+            .await?;
+        let records: Vec<TRecord> = response.take(0)?;
+
+        return Ok(records);
+    }
+
+    async fn create_new<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        entity: TEntity,
+        owner: Option<String>,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let record: TRecord = self
+            .connection()
+            .await
+            .create(collection_name)
+            .content(VersionedEntity {
+                entity: &entity,
+                version: 1,
+                owner,
+            })
+            .await?;
+
+        return Ok(record);
+    }
+
+    /// The version check is part of the update statement, so a concurrent write in between is detected as well
+    async fn update<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+        entity: TEntity,
+        expected_version: Option<u32>,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let current: TRecord = self
+            .check_version(collection_name, id, expected_version)
+            .await?;
+        let current_version = current.get_version();
+
+        let mut response = self
+            .connection()
+            .await
+            .query("UPDATE type::thing($collection, $id) CONTENT $content WHERE (version OR 0) = $version RETURN AFTER")
+            .bind(("collection", collection_name))
+            .bind(("id", id))
+            .bind((
+                "content",
+                VersionedEntity {
+                    entity: &entity,
+                    version: current_version + 1,
+                    owner: current.get_owner(),
+                },
+            ))
+            .bind(("version", current_version))
+            .await?;
+        let updated: Vec<TRecord> = response.take(0)?;
+
+        match updated.into_iter().next() {
+            Some(record) => Ok(record),
+            None => Err(self
+                .conflict_for::<TEntity, TRecord>(collection_name, id, current_version)
+                .await),
+        }
+    }
+
+    async fn delete<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        id: &str,
+        expected_version: Option<u32>,
+    ) -> Result<TRecord, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let current_version = self
+            .check_version::<TEntity, TRecord>(collection_name, id, expected_version)
+            .await?
+            .get_version();
+
+        let mut response = self
+            .connection()
+            .await
+            .query("DELETE type::thing($collection, $id) WHERE (version OR 0) = $version RETURN BEFORE")
+            .bind(("collection", collection_name))
+            .bind(("id", id))
+            .bind(("version", current_version))
+            .await?;
+        let deleted: Vec<TRecord> = response.take(0)?;
+
+        match deleted.into_iter().next() {
+            Some(record) => Ok(record),
+            None => Err(self
+                .conflict_for::<TEntity, TRecord>(collection_name, id, current_version)
+                .await),
+        }
+    }
+
+    /// All writes are sent as a single transaction, a failing statement cancels all of them
+    ///
+    /// Like single updates the version check is part of each update statement, so a concurrent write in between
+    /// fails the batch with a version mismatch.
+    async fn write_all<TEntity, TRecord>(
+        &self,
+        collection_name: &str,
+        writes: Vec<BatchWrite<TEntity>>,
+    ) -> Result<Vec<TRecord>, Error>
+    where
+        TEntity: Entity,
+        TRecord: Record<TEntity>,
+    {
+        let mut statements: Vec<String> = vec![String::from("BEGIN TRANSACTION")];
+        // the guard of an update is its id and the version it is written against
+        let mut bindings: Vec<(String, Option<(String, u32)>, VersionedEntity<'_, TEntity>)> = vec![];
+
+        for (i, write) in writes.iter().enumerate() {
+            match write {
+                BatchWrite::Create { entity, owner } => {
+                    statements.push(format!("CREATE type::table($collection) CONTENT $content{}", i));
+                    bindings.push((
+                        format!("content{}", i),
+                        None,
+                        VersionedEntity {
+                            entity,
+                            version: 1,
+                            owner: owner.clone(),
+                        },
+                    ));
+                }
+                BatchWrite::Update {
+                    id,
+                    entity,
+                    expected_version,
+                } => {
+                    let current: TRecord = self
+                        .check_version(collection_name, id, *expected_version)
+                        .await?;
+
+                    statements.push(format!(
+                        "UPDATE type::thing($collection, $id{i}) CONTENT $content{i} \
+                         WHERE (version OR 0) = $version{i} RETURN AFTER",
+                        i = i
+                    ));
+                    bindings.push((
+                        format!("content{}", i),
+                        Some((id.clone(), current.get_version())),
+                        VersionedEntity {
+                            entity,
+                            version: current.get_version() + 1,
+                            owner: current.get_owner(),
+                        },
+                    ));
+                }
+            }
+        }
+        statements.push(String::from("COMMIT TRANSACTION"));
+
+        let guards: Vec<Option<(String, u32)>> = bindings.iter().map(|(_, guard, _)| guard.clone()).collect();
+        let mut query = self
+            .connection()
+            .await
+            .query(statements.join(";\n"))
+            .bind(("collection", collection_name));
+        for (i, (name, id, content)) in bindings.into_iter().enumerate() {
+            if let Some((id, version)) = id {
+                query = query
+                    .bind((format!("id{}", i), id))
+                    .bind((format!("version{}", i), version));
+            }
+            query = query.bind((name, content));
+        }
+        let mut response = query.await?;
+
+        // The statements starting and committing the transaction have no results
+        let mut records: Vec<TRecord> = vec![];
+        for (i, guard) in guards.iter().enumerate() {
+            let written: Vec<TRecord> = response.take(i)?;
+            match (written.into_iter().next(), guard) {
+                (Some(record), _) => records.push(record),
+                // the guard of an update matched no row, the record was written by someone else in between
+                (None, Some((id, version))) => {
+                    return Err(
+                        match self.conflict_for::<TEntity, TRecord>(collection_name, id, *version).await {
+                            Error::NotFound => Error::VersionMismatch(*version, 0),
+                            e => e,
+                        },
+                    );
+                }
+                (None, None) => return Err(Error::NotFound),
+            }
+        }
+
+        Ok(records)
+    }
+}
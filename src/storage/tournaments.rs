@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::{Entity, Record};
+
+pub const COLLECTION_NAME: &'static str = "Tournaments";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentFormatEntity {
+    SingleElimination,
+    DoubleElimination,
+    RoundRobin,
+    Swiss,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TournamentStatusEntity {
+    Registration,
+    Finished,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MatchEntity {
+    pub round: u32,
+    /// The ids of the combatants, the second one is not set for a bye
+    pub first: String,
+    pub second: Option<String>,
+    pub winner: Option<String>,
+    #[serde(default)]
+    pub losers_bracket: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TournamentEntity {
+    pub name: String,
+    pub format: TournamentFormatEntity,
+    /// The number of rounds of a swiss tournament
+    pub rounds: Option<u32>,
+    /// The id of the battlefield all matches are played on
+    pub battlefield: String,
+    /// The ids of the registered combatants in the order of their seed
+    pub combatants: Vec<String>,
+    pub status: TournamentStatusEntity,
+    pub matches: Vec<MatchEntity>,
+}
+
+impl Entity for TournamentEntity {
+    fn collection_name() -> &'static str {
+        COLLECTION_NAME
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TournamentRecord {
+    pub id: Thing,
+    pub name: String,
+    pub format: TournamentFormatEntity,
+    pub rounds: Option<u32>,
+    pub battlefield: String,
+    pub combatants: Vec<String>,
+    pub status: TournamentStatusEntity,
+    pub matches: Vec<MatchEntity>,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+impl Record<TournamentEntity> for TournamentRecord {
+    fn get_id(&self) -> String {
+        self.id.id.to_raw()
+    }
+
+    fn get_entity(&self) -> TournamentEntity {
+        TournamentEntity {
+            name: self.name.clone(),
+            format: self.format,
+            rounds: self.rounds,
+            battlefield: self.battlefield.clone(),
+            combatants: self.combatants.to_vec(),
+            status: self.status,
+            matches: self.matches.to_vec(),
+        }
+    }
+
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    fn get_owner(&self) -> Option<String> {
+        self.owner.clone()
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::{Entity, Record};
+
+pub const COLLECTION_NAME: &'static str = "Users";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserEntity {
+    pub name: String,
+    /// Only the hash of the API key is stored, the key itself is shown once when the user is created
+    pub api_key_hash: String,
+    pub is_admin: bool,
+}
+
+impl Entity for UserEntity {
+    fn collection_name() -> &'static str {
+        COLLECTION_NAME
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserRecord {
+    pub id: Thing,
+    pub name: String,
+    pub api_key_hash: String,
+    pub is_admin: bool,
+    #[serde(default)]
+    pub version: u32,
+}
+
+impl Record<UserEntity> for UserRecord {
+    fn get_id(&self) -> String {
+        self.id.id.to_raw()
+    }
+
+    fn get_entity(&self) -> UserEntity {
+        UserEntity {
+            name: self.name.clone(),
+            api_key_hash: self.api_key_hash.clone(),
+            is_admin: self.is_admin,
+        }
+    }
+
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+}
@@ -1 +1,2 @@
-pub mod point;
\ No newline at end of file
+pub mod point;
+pub mod topology;
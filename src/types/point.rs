@@ -2,22 +2,33 @@ use std::fmt::Display;
 
 use rand::{thread_rng, Rng};
 
+use super::topology::Topology;
+
+/// A single coordinate, limits maps to 65536 locations in each direction
+pub type Coordinate = u16;
+
 #[derive(Debug, PartialEq, PartialOrd, Clone, Copy, Eq, Hash)]
 pub struct Point {
-    pub x: u8,
-    pub y: u8,
+    pub x: Coordinate,
+    pub y: Coordinate,
 }
 
 impl Point {
-    pub fn distance_to(&self, other: &Point) -> f64 {
-        let x_dif: i32 = self.x as i32 - other.x as i32;
-        let y_dif: i32 = self.y as i32 - other.y as i32;
+    /// The straight line distance, hex locations are measured between their centers
+    pub fn distance_to(&self, other: &Point, topology: Topology) -> f64 {
+        let (x, y) = topology.plane_position(self.x, self.y);
+        let (other_x, other_y) = topology.plane_position(other.x, other.y);
 
-        return ((x_dif.pow(2) + y_dif.pow(2)) as f64).sqrt();
+        ((x - other_x).powi(2) + (y - other_y).powi(2)).sqrt()
     }
 
-    pub fn closest(&self, others: Vec<Point>) -> Point {
-        let distances: Vec<(f64, Point)> = others.iter().map(|p| (p.distance_to(self), p.clone())).collect::<Vec<(f64, Point)>>();
+    /// The number of steps needed to reach the other point, moving only to neighbours
+    pub fn steps_to(&self, other: &Point, topology: Topology) -> u32 {
+        topology.steps(other.x as i32 - self.x as i32, other.y as i32 - self.y as i32)
+    }
+
+    pub fn closest(&self, others: Vec<Point>, topology: Topology) -> Point {
+        let distances: Vec<(f64, Point)> = others.iter().map(|p| (p.distance_to(self, topology), p.clone())).collect::<Vec<(f64, Point)>>();
 
         let mut minimal_distance: (f64, Point) = (f64::MAX, Point::default());
 
@@ -31,70 +42,58 @@ impl Point {
     }
 
     pub fn random(bounds: Option<Point>) -> Point {
+        Point::random_with(bounds, &mut thread_rng())
+    }
+
+    /// A random location drawn from the given generator, so seeded generators draw the same locations
+    pub fn random_with(bounds: Option<Point>, rng: &mut impl Rng) -> Point {
         match bounds {
             Some(b) => {
-                let mut rng = thread_rng();
-                let gen_x: u8 = rng.gen_range(0..b.x);
-                let gen_y: u8 = rng.gen_range(0..b.y);
+                let gen_x: Coordinate = rng.gen_range(0..b.x);
+                let gen_y: Coordinate = rng.gen_range(0..b.y);
 
                 Point::new(gen_x, gen_y)
             }
             None => {
-                let mut rng = thread_rng();
-                let gen_x: u8 = rng.gen();
-                let gen_y: u8 = rng.gen();
+                let gen_x: Coordinate = rng.gen();
+                let gen_y: Coordinate = rng.gen();
 
                 Point::new(gen_x, gen_y)
             }
         }
     }
 
-    pub fn neighbors(&self, bounds: Option<Point>) -> Vec<Point>{
-        match bounds {
-            Some(boundary) => {
-                let mut neighbors: Vec<Point> = vec![];
-
-                if self > &boundary {
-                    return vec![];
-                }
-
-                let move_north = self.y.overflowing_add(1);
-                let north_neighbor = Point::new(self.x, move_north.0);
-                if !move_north.1 && north_neighbor <= boundary {
-                    neighbors.push(north_neighbor);
-                }
-
-                let move_east = self.x.overflowing_add(1);
-                let east_neighbor = Point::new(move_east.0, self.y);
-                if !move_east.1 && east_neighbor <= boundary {
-                    neighbors.push(east_neighbor);
-                }
-
-                let move_south = self.y.overflowing_sub(1);
-                let south_neighbor = Point::new(self.x, move_south.0);
-                if !move_south.1 && south_neighbor <= boundary {
-                    neighbors.push(south_neighbor);
-                }
-
-                let move_west = self.x.overflowing_sub(1);
-                let west_neighbor = Point::new(move_west.0, self.y);
-                if !move_west.1 && west_neighbor <= boundary {
-                    neighbors.push(west_neighbor);
-                }
-                
-                return neighbors;
-            },
-            None => {
-                self.neighbors(Some(Point::MAX))
-            },
+    /// The locations one step away that lie within the bounds, the whole coordinate range if no bounds are given
+    pub fn neighbors(&self, topology: Topology, bounds: Option<Point>) -> Vec<Point> {
+        let boundary = bounds.unwrap_or(Point::MAX);
+
+        if !self.within(&boundary) {
+            return vec![];
         }
+
+        topology
+            .offsets()
+            .iter()
+            .filter_map(|(x_dif, y_dif)| {
+                let x = Coordinate::try_from(self.x as i32 + *x_dif as i32).ok()?;
+                let y = Coordinate::try_from(self.y as i32 + *y_dif as i32).ok()?;
+
+                Some(Point::new(x, y))
+            })
+            .filter(|neighbor| neighbor.within(&boundary))
+            .collect()
+    }
+
+    /// Whether neither coordinate is larger than the one of the bounds
+    pub fn within(&self, bounds: &Point) -> bool {
+        self.x <= bounds.x && self.y <= bounds.y
     }
 
-    pub fn new(x: u8, y: u8) -> Point {
+    pub fn new(x: Coordinate, y: Coordinate) -> Point {
         Point { x, y }
     }
 
-    pub const MAX : Point = Point { x: u8::MAX, y: u8::MAX };
+    pub const MAX : Point = Point { x: Coordinate::MAX, y: Coordinate::MAX };
 }
 
 impl Display for Point {
@@ -122,14 +121,23 @@ mod tests {
         let p1: Point = Point { x: 1, y: 1 };
         let p2: Point = Point { x: 2, y: 2 };
 
-        assert_relative_eq!(1.4, p1.distance_to(&p2), max_relative = 0.02);
+        assert_relative_eq!(1.4, p1.distance_to(&p2, Topology::Square4), max_relative = 0.02);
+    }
+
+    #[test]
+    fn steps_to() {
+        let p1: Point = Point { x: 1, y: 4 };
+        let p2: Point = Point { x: 3, y: 1 };
+
+        assert_eq!(5, p1.steps_to(&p2, Topology::Square4));
+        assert_eq!(5, p2.steps_to(&p1, Topology::Square4));
     }
 
 
     #[test]
     fn neighbors_center(){
         let center = Point::new(1, 1);
-        let neighbors = center.neighbors(None);
+        let neighbors = center.neighbors(Topology::Square4, None);
 
         assert!(neighbors.contains(&Point::new(1, 0)));
         assert!(neighbors.contains(&Point::new(1, 2)));
@@ -140,7 +148,7 @@ mod tests {
     #[test]
     fn neighbors_0_0(){
         let point = Point::new(0, 0);
-        let neighbors: Vec<Point> = point.neighbors(None);
+        let neighbors: Vec<Point> = point.neighbors(Topology::Square4, None);
 
         assert_eq!(neighbors.len(), 2);
         assert!(neighbors.contains(&Point::new(1, 0)));
@@ -150,17 +158,17 @@ mod tests {
     #[test]
     fn neighbors_max_max(){
         let point = Point::MAX;
-        let neighbors: Vec<Point> = point.neighbors(None);
+        let neighbors: Vec<Point> = point.neighbors(Topology::Square4, None);
 
         assert_eq!(neighbors.len(), 2);
-        assert!(neighbors.contains(&Point::new(u8::MAX, 254)));
-        assert!(neighbors.contains(&Point::new(254, u8::MAX)));
+        assert!(neighbors.contains(&Point::new(Coordinate::MAX, Coordinate::MAX - 1)));
+        assert!(neighbors.contains(&Point::new(Coordinate::MAX - 1, Coordinate::MAX)));
     }
 
     #[test]
     fn neighbors_out_of_bounds(){
         let point = Point::MAX;
-        let neighbors: Vec<Point> = point.neighbors(Some(Point::new(10, 10)));
+        let neighbors: Vec<Point> = point.neighbors(Topology::Square4, Some(Point::new(10, 10)));
 
         assert_eq!(neighbors.len(), 0);
     }
@@ -169,7 +177,7 @@ mod tests {
     fn neighbors_on_boundary_line(){
         let point = Point::new(10, 10);
         let boundary = Point::new(10, 12);
-        let neighbors: Vec<Point> = point.neighbors(Some(boundary));
+        let neighbors: Vec<Point> = point.neighbors(Topology::Square4, Some(boundary));
 
         assert_eq!(neighbors.len(), 3);
         assert!(neighbors.contains(&Point::new(10, 9)));
@@ -180,13 +188,61 @@ mod tests {
     #[test]
     fn neighbors_on_boundary_corner(){
         let point = Point::new(10, 10);
-        let neighbors: Vec<Point> = point.neighbors(Some(point));
+        let neighbors: Vec<Point> = point.neighbors(Topology::Square4, Some(point));
 
         assert_eq!(neighbors.len(), 2);
         assert!(neighbors.contains(&Point::new(10, 9)));
         assert!(neighbors.contains(&Point::new(9, 10)));
     }
 
+    #[test]
+    fn neighbors_with_diagonals(){
+        let neighbors: Vec<Point> = Point::new(1, 1).neighbors(Topology::Square8, None);
+
+        assert_eq!(neighbors.len(), 8);
+        assert!(neighbors.contains(&Point::new(0, 0)));
+        assert!(neighbors.contains(&Point::new(2, 2)));
+        assert_eq!(Point::new(0, 0).neighbors(Topology::Square8, None).len(), 3);
+    }
+
+    #[test]
+    fn neighbors_hex(){
+        let neighbors: Vec<Point> = Point::new(1, 1).neighbors(Topology::Hex, None);
+
+        assert_eq!(neighbors.len(), 6);
+        assert!(neighbors.contains(&Point::new(2, 0)));
+        assert!(neighbors.contains(&Point::new(0, 2)));
+        assert!(!neighbors.contains(&Point::new(0, 0)));
+        assert!(!neighbors.contains(&Point::new(2, 2)));
+    }
+
+    #[test]
+    fn steps_to_per_topology() {
+        let p1: Point = Point { x: 1, y: 4 };
+        let p2: Point = Point { x: 3, y: 1 };
+
+        assert_eq!(3, p1.steps_to(&p2, Topology::Square8));
+        assert_eq!(3, p1.steps_to(&p2, Topology::Hex));
+        assert_eq!(5, Point::new(1, 1).steps_to(&Point::new(3, 4), Topology::Hex));
+    }
+
+    #[test]
+    fn neighbors_beyond_boundary_column(){
+        // lies before the boundary when compared as a whole, but beyond it in y
+        let point = Point::new(3, 20);
+        let neighbors: Vec<Point> = point.neighbors(Topology::Square4, Some(Point::new(10, 10)));
+
+        assert_eq!(neighbors.len(), 0);
+    }
+
+    #[test]
+    fn neighbors_beyond_u8(){
+        let neighbors: Vec<Point> = Point::new(255, 255).neighbors(Topology::Square8, None);
+
+        assert_eq!(neighbors.len(), 8);
+        assert!(neighbors.contains(&Point::new(256, 256)));
+    }
+
     #[test]
     fn closest(){
         let p1 = Point::new(0, 0);
@@ -194,7 +250,7 @@ mod tests {
         let p3 = Point::new(6, 5);
         let p4 = Point::new(5, 5);
 
-        let closest = p1.closest(vec![p2, p3, p4]);
+        let closest = p1.closest(vec![p2, p3, p4], Topology::Square4);
 
         assert_eq!(closest, p2);
     }
@@ -0,0 +1,74 @@
+use super::point::Coordinate;
+
+/// How the locations of a map are connected
+///
+/// Hex maps use axial coordinates, x is the column and y the diagonal row,
+/// so a map of a given width and height has the shape of a rhombus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Topology {
+    /// Steps go north, east, south or west
+    #[default]
+    Square4,
+    /// Steps go north, east, south or west or diagonally
+    Square8,
+    /// Six neighbours per location
+    Hex,
+}
+
+// The offsets of the neighbours, in the order they are returned
+const SQUARE4_OFFSETS: [(i16, i16); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const SQUARE8_OFFSETS: [(i16, i16); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+const HEX_OFFSETS: [(i16, i16); 6] = [(0, 1), (1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1)];
+
+impl Topology {
+    pub fn offsets(&self) -> &'static [(i16, i16)] {
+        match self {
+            Topology::Square4 => &SQUARE4_OFFSETS,
+            Topology::Square8 => &SQUARE8_OFFSETS,
+            Topology::Hex => &HEX_OFFSETS,
+        }
+    }
+
+    /// The number of steps between two locations, given by their coordinate differences
+    pub fn steps(&self, x_dif: i32, y_dif: i32) -> u32 {
+        match self {
+            Topology::Square4 => x_dif.unsigned_abs() + y_dif.unsigned_abs(),
+            Topology::Square8 => x_dif.unsigned_abs().max(y_dif.unsigned_abs()),
+            Topology::Hex => (x_dif.unsigned_abs() + y_dif.unsigned_abs() + (x_dif + y_dif).unsigned_abs()) / 2,
+        }
+    }
+
+    /// The position of a location on the plane, hex locations are shifted so all neighbours are one unit apart
+    pub fn plane_position(&self, x: Coordinate, y: Coordinate) -> (f64, f64) {
+        match self {
+            Topology::Square4 | Topology::Square8 => (x as f64, y as f64),
+            Topology::Hex => (x as f64 + y as f64 / 2.0, y as f64 * 3f64.sqrt() / 2.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps() {
+        assert_eq!(5, Topology::Square4.steps(2, -3));
+        assert_eq!(3, Topology::Square8.steps(2, -3));
+        assert_eq!(3, Topology::Hex.steps(2, -3));
+        assert_eq!(5, Topology::Hex.steps(2, 3));
+    }
+
+    #[test]
+    fn hex_neighbours_are_one_unit_apart() {
+        let (x, y) = Topology::Hex.plane_position(5, 5);
+
+        for (dx, dy) in Topology::Hex.offsets() {
+            let (nx, ny) = Topology::Hex.plane_position((5 + dx) as Coordinate, (5 + dy) as Coordinate);
+            let distance = ((nx - x).powi(2) + (ny - y).powi(2)).sqrt();
+
+            assert!((distance - 1.0).abs() < 1e-9);
+            assert_eq!(1, Topology::Hex.steps(*dx as i32, *dy as i32));
+        }
+    }
+}